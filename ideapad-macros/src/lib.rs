@@ -0,0 +1,177 @@
+//! Procedural macros for the `ideapad` crate. Use them through `ideapad::macros` rather than
+//! depending on this crate directly.
+
+use proc_macro::{Delimiter, Group, Literal, Span, TokenStream, TokenTree};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Embed every `*.toml` profile of a directory into the binary, parsing them into a `Vec` of
+/// `Profile`s.
+///
+/// See `ideapad::include_profiles` for the documentation.
+#[proc_macro]
+pub fn include_profiles(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(output) => output,
+        Err((message, span)) => compile_error(&message, span),
+    }
+}
+
+type Error = (String, Span);
+
+fn expand(input: TokenStream) -> Result<TokenStream, Error> {
+    let (directory, span) = parse_directory(input)?;
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| ("`CARGO_MANIFEST_DIR` isn't set".to_string(), span))?;
+    let directory = Path::new(&manifest_dir).join(directory);
+    let files = profile_files(&directory).map_err(|error| {
+        (
+            format!(
+                "failed to read the directory '{}': {}",
+                directory.display(),
+                error
+            ),
+            span,
+        )
+    })?;
+
+    let mut profiles = String::new();
+
+    for file in files {
+        check_profile(&file).map_err(|message| {
+            (
+                format!("invalid profile at '{}': {}", file.display(), message),
+                span,
+            )
+        })?;
+
+        let path = Literal::string(&file.to_string_lossy()).to_string();
+        profiles += &format!(
+            "::ideapad::profile::Profile::from_toml(::core::include_str!({path}))\
+                .unwrap_or_else(|error| ::core::panic!(\
+                    \"failed to parse the profile at '{{}}': {{}}\", {path}, error\
+                )),"
+        );
+    }
+
+    format!("::std::vec![{profiles}]")
+        .parse()
+        .map_err(|error| (format!("failed to expand the profiles: {error}"), span))
+}
+
+fn parse_directory(input: TokenStream) -> Result<(String, Span), Error> {
+    let mut tokens = input.into_iter();
+    let error = |span| ("expected a directory as a string literal".to_string(), span);
+
+    let token = match tokens.next() {
+        // `macro_rules!` forwarding wraps the literal in an invisible group
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::None => {
+            return parse_directory(group.stream())
+        }
+        Some(token) => token,
+        None => return Err(error(Span::call_site())),
+    };
+    let span = token.span();
+    let literal = match token {
+        TokenTree::Literal(literal) => literal.to_string(),
+        _ => return Err(error(span)),
+    };
+
+    match tokens.next() {
+        None => {}
+        Some(TokenTree::Punct(punct)) if punct.as_char() == ',' && tokens.next().is_none() => {}
+        Some(token) => return Err(error(token.span())),
+    }
+
+    let directory = literal
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+        .filter(|directory| !directory.contains('\\'))
+        .ok_or_else(|| error(span))?;
+
+    Ok((directory.to_string(), span))
+}
+
+/// The `*.toml` files of the directory, sorted by path so the order of the profiles is stable.
+fn profile_files(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.is_file()
+            && path
+                .extension()
+                .is_some_and(|extension| extension == "toml")
+        {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Catch the mistakes which don't need the profile schema: invalid TOML and missing
+/// identification. Everything else is checked by `Profile::from_toml` when the profiles are
+/// parsed.
+fn check_profile(file: &Path) -> Result<(), String> {
+    let contents = fs::read_to_string(file).map_err(|error| error.to_string())?;
+    let table = contents
+        .parse::<toml::Table>()
+        .map_err(|error| error.message().to_string())?;
+
+    if !table.get("name").is_some_and(toml::Value::is_str) {
+        return Err("missing the string `name`".to_string());
+    }
+
+    match table.get("expected_product_names") {
+        Some(toml::Value::Array(names)) if names.iter().all(toml::Value::is_str) => Ok(()),
+        _ => Err("missing the string array `expected_product_names`".to_string()),
+    }
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut message = TokenTree::Literal(Literal::string(message));
+    message.set_span(span);
+
+    let mut group = TokenTree::Group(Group::new(Delimiter::Parenthesis, message.into()));
+    group.set_span(span);
+
+    let mut output: TokenStream = "::core::compile_error!".parse().unwrap();
+    output.extend([group]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../tests/fixtures")
+            .join(path)
+    }
+
+    #[test]
+    fn test_profile_files() {
+        assert_eq!(
+            profile_files(&fixture("profiles")).unwrap(),
+            [
+                fixture("profiles/first.toml"),
+                fixture("profiles/second.toml")
+            ]
+        );
+        assert!(profile_files(&fixture("missing")).is_err());
+    }
+
+    #[test]
+    fn test_check_profile() {
+        assert_eq!(check_profile(&fixture("profiles/first.toml")), Ok(()));
+        assert!(check_profile(&fixture("invalid_profiles/broken.toml")).is_err());
+        assert_eq!(
+            check_profile(&fixture("invalid_profiles/unnamed.toml")),
+            Err("missing the string `name`".to_string())
+        );
+    }
+}
@@ -1,17 +1,22 @@
 //! Basic wrapper for the `acpi_call` kernel module.
 //!
-//! Only exposed for [`Result`] and [`enum@Error`].
+//! Only exposed for [`Result`], [`enum@Error`], [`Output`], [`AcpiEvent`] (and [`AcpiTap`]),
+//! [`CommandRewriter`], [`Backend`] (and its implementations), [`PathStyle`] (and its detection)
+//! and [`PreflightError`] (and [`preflight_at`]).
 //!
 //! `acpi_call` support is very basic; there is no verification of commands, the only supported data
 //! type for parameters is [`u32`], and the only output from `acpi_call` which is considered valid
 //! are [`u32`]s. Regardless, these features are enough for this crate.
 
-use std::borrow::Cow;
-use std::{fs, io, iter};
-use tap::Pipe;
+use crate::profile::{OutputRadix, Profile};
+use std::fmt::{self, Write as _};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
 use thiserror::Error;
 
-const PATH: &str = "/proc/acpi/call";
+pub(crate) const PATH: &str = "/proc/acpi/call";
 
 /// Handy wrapper for [`enum@Error`].
 pub type Result<T> = std::result::Result<T, Error>;
@@ -48,6 +53,41 @@ pub enum Error {
         method: String,
     },
 
+    /// The state which was read back after a write didn't match what was written. This is only
+    /// checked if [`Context::verify_writes`](crate::context::Context::verify_writes) is set.
+    #[error("command '{command}' was issued, but reading the state back shows it wasn't applied")]
+    WriteNotApplied {
+        /// The command which wasn't applied.
+        command: String,
+    },
+
+    /// The profile doesn't have the section which a feature needs, for example a battery only
+    /// profile without a `system_performance` section.
+    #[error("feature unavailable, as the profile has no `{section}` section")]
+    FeatureUnavailableInProfile {
+        /// The missing section of the profile.
+        section: &'static str,
+    },
+
+    /// A write was attempted through a [read only](crate::context::Context::read_only) context.
+    #[error("refusing to write through a read only context")]
+    ReadOnly,
+
+    /// A parameter doesn't fit in the
+    /// [parameter width](crate::profile::Profile::parameter_width) of the profile, so the
+    /// embedded controller would have silently truncated it.
+    #[error("parameter {parameter:#x} of method '{method}' doesn't fit in {width} bits")]
+    ParameterOutOfRange {
+        /// The method which the parameter was passed to.
+        method: String,
+
+        /// The parameter which is out of range.
+        parameter: u32,
+
+        /// The width of parameters in bits.
+        width: u32,
+    },
+
     /// A generic IO error happened when using `acpi_call`.
     #[error("{error}")]
     Io {
@@ -60,6 +100,23 @@ pub enum Error {
 impl Error {
     const AE_NOT_FOUND: &'static str = "AE_NOT_FOUND";
 
+    /// If this is a [`Error::MethodNotFound`], get a hint which points at the field of the
+    /// profile which refers to the method, as the most likely cause is that the profile is wrong
+    /// for this model.
+    pub fn profile_hint(&self, profile: &Profile) -> Option<String> {
+        let command = match self {
+            Self::MethodNotFound { method } => method,
+            _ => return None,
+        };
+        let method = command.split(' ').next().unwrap_or_default();
+        let field = profile.field_of(method)?;
+
+        Some(format!(
+            "method {} ({}) not found; your profile ({}) may be wrong for this model",
+            method, field, profile.name
+        ))
+    }
+
     fn maybe_method_not_found(message: String, method: String) -> Self {
         match message.as_str() {
             Self::AE_NOT_FOUND => Self::MethodNotFound { method },
@@ -68,60 +125,900 @@ impl Error {
     }
 }
 
-pub(crate) enum Output {
+/// Serialized as an object tagged with the name of the variant, for example
+/// `{ "type": "MethodNotFound", "method": "..." }`. [`io::Error`]s are serialized as their string
+/// form.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        use std::borrow::Cow;
+
+        let (r#type, field, value) = match self {
+            Self::KernelModuleNotLoaded { source } => (
+                "KernelModuleNotLoaded",
+                "source",
+                Cow::Owned(source.to_string()),
+            ),
+            Self::UnknownValue { value } => {
+                ("UnknownValue", "value", Cow::Borrowed(value.as_str()))
+            }
+            Self::UnknownError { message } => {
+                ("UnknownError", "message", Cow::Borrowed(message.as_str()))
+            }
+            Self::MethodNotFound { method } => {
+                ("MethodNotFound", "method", Cow::Borrowed(method.as_str()))
+            }
+            Self::WriteNotApplied { command } => (
+                "WriteNotApplied",
+                "command",
+                Cow::Borrowed(command.as_str()),
+            ),
+            Self::FeatureUnavailableInProfile { section } => (
+                "FeatureUnavailableInProfile",
+                "section",
+                Cow::Borrowed(*section),
+            ),
+            Self::Io { error } => ("Io", "error", Cow::Owned(error.to_string())),
+            Self::ReadOnly => {
+                let mut state = serializer.serialize_struct("Error", 1)?;
+                state.serialize_field("type", "ReadOnly")?;
+                return state.end();
+            }
+            Self::ParameterOutOfRange {
+                method,
+                parameter,
+                width,
+            } => {
+                let mut state = serializer.serialize_struct("Error", 4)?;
+                state.serialize_field("type", "ParameterOutOfRange")?;
+                state.serialize_field("method", method)?;
+                state.serialize_field("parameter", parameter)?;
+                state.serialize_field("width", width)?;
+                return state.end();
+            }
+        };
+
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("type", r#type)?;
+        state.serialize_field(field, &value)?;
+        state.end()
+    }
+}
+
+/// Something which `acpi_call` commands can be issued through.
+///
+/// Usually this is the [`KernelModule`], but it could be swapped out for something else in the
+/// [`Context`](crate::context::Context).
+pub trait Backend: Send + Sync {
+    /// Issue the command, which is the method followed by its space separated parameters, and
+    /// return the raw output.
+    fn call(&self, command: &str) -> Result<String>;
+
+    /// Check whether commands could be issued through this backend at all, without issuing any.
+    fn available(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Check that commands could be issued through this backend, including whether there are the
+    /// permissions to do so, without issuing any. See [`preflight_at`].
+    fn preflight(&self) -> std::result::Result<(), PreflightError> {
+        Ok(())
+    }
+}
+
+/// The `acpi_call` kernel module, which is accessed through `/proc/acpi/call`.
+#[derive(Debug, Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct KernelModule;
+
+impl Backend for KernelModule {
+    fn call(&self, command: &str) -> Result<String> {
+        if let Err(error) = fs::write(PATH, command) {
+            return if let io::ErrorKind::NotFound = error.kind() {
+                Err(Error::KernelModuleNotLoaded { source: error })
+            } else {
+                Err(Error::Io { error })
+            };
+        }
+
+        Ok(fs::read_to_string(PATH)?)
+    }
+
+    fn available(&self) -> Result<()> {
+        available_at(Path::new(PATH))
+    }
+
+    fn preflight(&self) -> std::result::Result<(), PreflightError> {
+        preflight_at(Path::new(PATH))
+    }
+}
+
+/// The `acpi_call` kernel module, accessed through a handle to `/proc/acpi/call` which is kept
+/// open between commands, unlike [`KernelModule`] which opens and closes it for every command.
+/// This saves syscalls when polling.
+///
+/// If writing the command or reading its output through the handle fails, for example because the
+/// kernel module was reloaded, it is reopened and the command is retried once.
+#[derive(Debug)]
+pub struct PersistentKernelModule {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl PersistentKernelModule {
+    /// Issue commands through `/proc/acpi/call`.
+    pub fn new() -> Self {
+        Self::with_path(PATH)
+    }
+
+    /// Issue commands through the specified file instead of `/proc/acpi/call`, which is mostly
+    /// useful for testing.
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: Mutex::new(None),
+        }
+    }
+
+    fn open(&self) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path)
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::NotFound => Error::KernelModuleNotLoaded { source: error },
+                _ => Error::Io { error },
+            })
+    }
+
+    fn write(file: &mut File, command: &str) -> io::Result<()> {
+        // `/proc/acpi/call` always reports a length of 0, but a regular file used in its place
+        // would otherwise keep the tail of a longer previous command
+        if file.metadata()?.len() > 0 {
+            file.set_len(0)?;
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(command.as_bytes())
+    }
+
+    fn read(file: &mut File) -> io::Result<String> {
+        let mut output = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut output)?;
+
+        Ok(output)
+    }
+
+    fn exchange(file: &mut File, command: &str) -> io::Result<String> {
+        Self::write(file, command)?;
+        Self::read(file)
+    }
+}
+
+impl Default for PersistentKernelModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for PersistentKernelModule {
+    fn call(&self, command: &str) -> Result<String> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(file) = file.as_mut() {
+            if let Ok(output) = Self::exchange(file, command) {
+                return Ok(output);
+            }
+        }
+
+        // either nothing was opened yet, or the handle went stale
+        let file = file.insert(self.open()?);
+
+        Ok(Self::exchange(file, command)?)
+    }
+
+    fn available(&self) -> Result<()> {
+        available_at(&self.path)
+    }
+
+    fn preflight(&self) -> std::result::Result<(), PreflightError> {
+        preflight_at(&self.path)
+    }
+}
+
+/// Why `acpi_call` commands can't be issued at all. See [`preflight_at`].
+#[derive(Debug, Error)]
+pub enum PreflightError {
+    /// The `acpi_call` kernel module is not loaded, so the file doesn't exist.
+    #[error("`acpi_call` kernel module not loaded ('{}' doesn't exist), load it with `modprobe acpi_call`", .path.display())]
+    KernelModuleNotLoaded {
+        /// The path which doesn't exist.
+        path: PathBuf,
+    },
+
+    /// The file exists, but can't be written to. Usually, this means that the program isn't
+    /// running as root.
+    #[error("'{}' isn't writable, try running as root", .path.display())]
+    InsufficientPrivileges {
+        /// The path which isn't writable.
+        path: PathBuf,
+    },
+
+    /// Another error occurred when checking the file.
+    #[error("failed to check '{}': {source}", .path.display())]
+    Io {
+        /// The path which was checked.
+        path: PathBuf,
+
+        /// The underlying error itself.
+        source: io::Error,
+    },
+}
+
+/// Check that `acpi_call` commands could be issued through the specified file, usually
+/// `/proc/acpi/call`, without issuing any. Unlike [`Backend::available`], this also checks that
+/// the file is writable, which catches not running as root up front.
+pub fn preflight_at(path: &Path) -> std::result::Result<(), PreflightError> {
+    let error = |source: io::Error| match source.kind() {
+        io::ErrorKind::NotFound => PreflightError::KernelModuleNotLoaded {
+            path: path.to_path_buf(),
+        },
+        io::ErrorKind::PermissionDenied => PreflightError::InsufficientPrivileges {
+            path: path.to_path_buf(),
+        },
+        _ => PreflightError::Io {
+            path: path.to_path_buf(),
+            source,
+        },
+    };
+
+    // root can open files without any write permissions for writing, but `acpi_call` wouldn't
+    // accept commands through them
+    if fs::metadata(path).map_err(error)?.permissions().readonly() {
+        return Err(PreflightError::InsufficientPrivileges {
+            path: path.to_path_buf(),
+        });
+    }
+
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map(|_| ())
+        .map_err(error)
+}
+
+fn available_at(path: &Path) -> Result<()> {
+    match fs::metadata(path) {
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            Err(Error::KernelModuleNotLoaded { source: error })
+        }
+        Err(error) => Err(Error::Io { error }),
+    }
+}
+
+/// Runs shell commands on a remote machine, returning their standard output. See
+/// [`RemoteBackend`].
+#[cfg(feature = "remote")]
+pub trait CommandRunner: Send + Sync {
+    /// Run the shell command on the remote machine.
+    fn run(&self, command: &str) -> io::Result<String>;
+}
+
+/// Runs shell commands on a remote machine over `ssh`.
+#[cfg(feature = "remote")]
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Ssh {
+    /// The destination which is passed to `ssh`, for example `root@ideapad.local`.
+    pub destination: String,
+}
+
+#[cfg(feature = "remote")]
+impl Ssh {
+    /// Run commands on the specified destination.
+    pub fn new(destination: impl Into<String>) -> Self {
+        Self {
+            destination: destination.into(),
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl CommandRunner for Ssh {
+    fn run(&self, command: &str) -> io::Result<String> {
+        let output = std::process::Command::new("ssh")
+            .arg(&self.destination)
+            .arg(command)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "remote command on '{}' failed ({}): {}",
+                self.destination,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        String::from_utf8(output.stdout)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+/// The `acpi_call` kernel module of a remote machine, which is accessed by running shell commands
+/// through a [`CommandRunner`], usually [`Ssh`].
+///
+/// Every controller works unchanged when this is set as the
+/// [backend](crate::context::Context::backend) of a context, although the profile still has to
+/// match the remote machine.
+#[cfg(feature = "remote")]
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct RemoteBackend<R = Ssh> {
+    /// What runs the shell commands on the remote machine.
+    pub runner: R,
+}
+
+#[cfg(feature = "remote")]
+impl<R: CommandRunner> RemoteBackend<R> {
+    /// Issue commands through the specified command runner.
+    pub fn new(runner: R) -> Self {
+        Self { runner }
+    }
+
+    /// Build the shell command which writes the `acpi_call` command on the remote machine, then
+    /// reads its output.
+    fn remote_command(command: &str) -> String {
+        format!(
+            "printf '%s' '{}' > {} && cat {}",
+            command.replace('\'', r#"'\''"#),
+            PATH,
+            PATH
+        )
+    }
+}
+
+#[cfg(feature = "remote")]
+impl RemoteBackend {
+    /// Issue commands over `ssh` to the specified destination.
+    pub fn ssh(destination: impl Into<String>) -> Self {
+        Self::new(Ssh::new(destination))
+    }
+}
+
+#[cfg(feature = "remote")]
+impl<R: CommandRunner> Backend for RemoteBackend<R> {
+    fn call(&self, command: &str) -> Result<String> {
+        Ok(self.runner.run(&Self::remote_command(command))?)
+    }
+
+    fn available(&self) -> Result<()> {
+        self.runner
+            .run(&format!("test -e {}", PATH))
+            .map(|_| ())
+            .map_err(|source| Error::KernelModuleNotLoaded { source })
+    }
+}
+
+/// The output of an `acpi_call` command, after trailing nul bytes and line endings are stripped.
+///
+/// Outputs are valid if they are a [`u32`], written either in hexadecimal (`0x1`) or decimal.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Output {
+    /// The output was a [`u32`].
     Valid(u32),
+
+    /// The output wasn't a [`u32`], which is the raw output.
     Invalid(String),
 }
 
+impl Output {
+    /// Get the value of the output if it is [valid](Self::Valid).
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Self::Valid(value) => Some(*value),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+/// Valid outputs are shown in hexadecimal like `acpi_call` does, while invalid outputs are shown
+/// as is.
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Valid(value) => write!(f, "{:#x}", value),
+            Self::Invalid(output) => f.write_str(output),
+        }
+    }
+}
+
+/// A single interaction with the embedded controller, as observed by
+/// [`Context::acpi_tap`](crate::Context::acpi_tap).
+#[derive(Debug)]
+pub struct AcpiEvent<'a> {
+    /// The method which was called, in the [path style](crate::Context::path_style) it was issued in.
+    pub command: &'a str,
+
+    /// The parameters which the method was called with.
+    pub params: &'a [u32],
+
+    /// The output of the call, or why it failed.
+    pub result: &'a Result<Output>,
+}
+
+/// A hook which observes [`AcpiEvent`]s.
+pub type AcpiTap = Arc<dyn Fn(&AcpiEvent<'_>) + Send + Sync>;
+
+/// A hook which rewrites the method and parameters of a command before it is issued.
+pub type CommandRewriter = Arc<dyn Fn(&str, &[u32]) -> (String, Vec<u32>) + Send + Sync>;
+
+/// How the `acpi_call` kernel module expects the name segments of ACPI method paths to be written.
+/// Which one is accepted depends on the version of the module, and a method written in the wrong
+/// style isn't found even if the profile is right.
+#[derive(Debug, Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PathStyle {
+    /// Trailing underscores of name segments are left out, for example `\_SB.PCI0.LPC0.EC0`. This
+    /// is the style which the built in profiles are written in.
+    #[default]
+    Short,
+
+    /// Name segments are padded with underscores to four characters, for example
+    /// `\_SB_.PCI0.LPC0.EC0_`.
+    Padded,
+}
+
+impl PathStyle {
+    /// A harmless method which is read to find out which path style is accepted, written in the
+    /// short style. It is the address of the root PCI bridge, which practically every machine has.
+    pub const PROBE_METHOD: &'static str = r#"\_SB.PCI0._ADR"#;
+
+    /// [`Self::PROBE_METHOD`] written in the padded style.
+    pub const PADDED_PROBE_METHOD: &'static str = r#"\_SB_.PCI0._ADR"#;
+}
+
+/// Rewrite the ACPI method path into the path style, by padding the name segments with
+/// underscores to four characters or removing their trailing underscores. Root (`\`) and parent
+/// (`^`) prefixes are kept as is.
+pub fn normalize_method(method: &str, style: PathStyle) -> String {
+    let path = method.trim_start_matches(['\\', '^']);
+    let prefix = &method[..method.len() - path.len()];
+    let segments = path
+        .split('.')
+        .map(|segment| match style {
+            // a segment which is only underscores keeps one, so that it isn't left empty
+            PathStyle::Short => match segment.trim_end_matches('_') {
+                "" => &segment[..segment.len().min(1)],
+                trimmed => trimmed,
+            }
+            .to_string(),
+            PathStyle::Padded => format!("{:_<4}", segment),
+        })
+        .collect::<Vec<_>>();
+
+    format!("{}{}", prefix, segments.join("."))
+}
+
+/// Find out which [`PathStyle`] the `acpi_call` kernel module accepts. See
+/// [`detect_path_style_with`].
+pub fn detect_path_style() -> Result<PathStyle> {
+    detect_path_style_with(&KernelModule)
+}
+
+/// Find out which [`PathStyle`] the backend accepts, by reading the
+/// [probe method](PathStyle::PROBE_METHOD) in each style, starting with the short style.
+///
+/// # Errors
+/// If the probe method isn't found in either style, an [`Error::MethodNotFound`] is returned.
+pub fn detect_path_style_with(backend: &dyn Backend) -> Result<PathStyle> {
+    detect_path_style_through(|method| acpi_call(backend, method, [], OutputRadix::Decimal))
+}
+
+/// Like [`detect_path_style_with`], but the probe method is read through `read`, so that a
+/// [`Context`](crate::Context) can issue it like any other command.
+pub(crate) fn detect_path_style_through(
+    mut read: impl FnMut(&str) -> Result<Output>,
+) -> Result<PathStyle> {
+    let mut not_found = None;
+
+    for (style, method) in [
+        (PathStyle::Short, PathStyle::PROBE_METHOD),
+        (PathStyle::Padded, PathStyle::PADDED_PROBE_METHOD),
+    ] {
+        match read(method) {
+            Ok(_) => return Ok(style),
+            Err(error @ Error::MethodNotFound { .. }) => not_found = Some(error),
+            Err(error) => return Err(error),
+        }
+    }
+
+    Err(not_found.expect("at least one path style was probed"))
+}
+
+/// Build the command which is written to `acpi_call`, which is the method followed by its space
+/// separated parameters.
+///
+/// The command is written into a single buffer, which is allocated once with enough room for the
+/// common case of a single parameter.
+pub(crate) fn command(method: &str, parameters: impl IntoIterator<Item = u32>) -> String {
+    // a space and at most 10 digits for a parameter
+    const PARAMETER_LEN: usize = 11;
+
+    let mut command = String::with_capacity(method.len() + PARAMETER_LEN);
+    command.push_str(method);
+
+    for parameter in parameters {
+        // writing to a string never fails
+        let _ = write!(command, " {}", parameter);
+    }
+
+    command
+}
+
+/// Strip any combination of trailing nul bytes and line endings from the raw output of the kernel
+/// module, since different versions terminate it differently.
+pub(crate) fn normalize_response(output: &str) -> &str {
+    output.trim_end_matches(['\0', '\n', '\r'])
+}
+
+/// Check that every parameter fits in the specified width in bits, if there is one.
+pub(crate) fn check_parameters(method: &str, parameters: &[u32], width: Option<u32>) -> Result<()> {
+    let width = match width {
+        Some(width) => width,
+        None => return Ok(()),
+    };
+
+    match parameters
+        .iter()
+        .find(|&&parameter| parameter.checked_shr(width).is_some_and(|rest| rest != 0))
+    {
+        Some(&parameter) => Err(Error::ParameterOutOfRange {
+            method: method.to_string(),
+            parameter,
+            width,
+        }),
+        None => Ok(()),
+    }
+}
+
 pub(crate) fn acpi_call(
-    command: String,
+    backend: &dyn Backend,
+    method: &str,
     parameters: impl IntoIterator<Item = u32>,
+    radix: OutputRadix,
 ) -> Result<Output> {
-    let command = iter::once(Cow::Borrowed(command.as_str()))
-        .chain(
-            parameters
-                .into_iter()
-                .map(|parameter| parameter.to_string())
-                .map(Cow::Owned),
-        )
-        .collect::<Vec<_>>()
-        .join(" ");
-
-    if let Err(error) = fs::write(PATH, &command) {
-        return if let io::ErrorKind::NotFound = error.kind() {
-            Err(Error::KernelModuleNotLoaded { source: error })
-        } else {
-            Err(Error::Io { error })
-        };
-    }
+    let command = command(method, parameters);
 
-    let output = fs::read_to_string(PATH)?.trim_end_matches('\0').to_string();
+    let output = normalize_response(&backend.call(&command)?).to_string();
 
     if let Some(("Error", message)) = output.split_once(": ") {
         return Err(Error::maybe_method_not_found(message.to_string(), command));
     }
 
-    if output.starts_with("0x") {
-        Ok(output
-            .trim_start_matches("0x")
-            .pipe(|output| u32::from_str_radix(output, 16))
-            .map(Output::Valid)
-            .unwrap_or_else(|_| Output::Invalid(output)))
-    } else {
-        Ok(output
-            .parse::<u32>()
-            .map(Output::Valid)
-            .unwrap_or_else(|_| Output::Invalid(output)))
+    Ok(parse_output(output, radix))
+}
+
+/// Parse the output of `acpi_call`. Outputs with a `0x` prefix are always hexadecimal, while
+/// outputs without one are parsed in the radix. See [`Profile::output_radix`].
+pub(crate) fn parse_output(output: String, radix: OutputRadix) -> Output {
+    match output.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => u32::from_str_radix(&output, radix.radix()),
     }
+    .map(Output::Valid)
+    .unwrap_or_else(|_| Output::Invalid(output))
 }
 
-pub(crate) fn acpi_call_expect_valid(
-    command: String,
-    parameters: impl IntoIterator<Item = u32>,
-) -> Result<u32> {
-    match acpi_call(command, parameters) {
-        Ok(Output::Valid(value)) => Ok(value),
-        Ok(Output::Invalid(value)) => Err(Error::UnknownValue { value }),
-        Err(error) => Err(error),
+#[cfg(test)]
+mod tests {
+    use crate::acpi_call::{
+        check_parameters, command, normalize_response, parse_output, Error, Output,
+    };
+    use crate::profile::OutputRadix;
+    #[cfg(feature = "serde")]
+    use serde_json::json;
+    #[cfg(feature = "serde")]
+    use std::io;
+
+    #[test]
+    fn test_command() {
+        let method = r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC"#;
+
+        assert_eq!(command(method, []), method);
+        assert_eq!(command(method, [0x000FB001]), format!("{} 1028097", method));
+        assert_eq!(
+            command(method, [3, 0, u32::MAX]),
+            format!("{} 3 0 4294967295", method)
+        );
+    }
+
+    #[test]
+    fn test_check_parameters() {
+        let method = r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#;
+
+        check_parameters(method, &[0x03, 0xFF], Some(8)).expect("in range parameter was rejected");
+        check_parameters(method, &[u32::MAX], Some(32)).expect("in range parameter was rejected");
+        check_parameters(method, &[0x000FB001], None).expect("unchecked parameter was rejected");
+        assert!(matches!(
+            check_parameters(method, &[0x03, 0x100], Some(8)),
+            Err(Error::ParameterOutOfRange {
+                parameter: 0x100,
+                width: 8,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_profile_hint() {
+        use crate::profile::Profile;
+
+        let error = Error::maybe_method_not_found(
+            "AE_NOT_FOUND".to_string(),
+            r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 3"#.to_string(),
+        );
+
+        assert_eq!(
+            error.profile_hint(&Profile::IDEAPAD_AMD).as_deref(),
+            Some(
+                r#"method \_SB.PCI0.LPC0.EC0.VPC0.SBMC (battery.set_command) not found; your profile (IDEAPAD_AMD) may be wrong for this model"#
+            )
+        );
+        assert_eq!(
+            Profile::IDEAPAD_AMD.field_of(r#"\_SB.PCI0.LPC0.EC0.BTSM"#),
+            Some("battery.conservation.get_command")
+        );
+        assert_eq!(
+            Error::UnknownValue {
+                value: "?".to_string()
+            }
+            .profile_hint(&Profile::IDEAPAD_AMD),
+            None
+        );
+    }
+
+    #[test]
+    fn test_output() {
+        let valid = Output::Valid(0x1);
+        let invalid = Output::Invalid("not a number".to_string());
+
+        assert_eq!(valid.as_u32(), Some(1));
+        assert_eq!(invalid.as_u32(), None);
+        assert_eq!(valid.to_string(), "0x1");
+        assert_eq!(invalid.to_string(), "not a number");
+    }
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(
+            parse_output("0x1F".to_string(), OutputRadix::Decimal),
+            Output::Valid(0x1F)
+        );
+        assert_eq!(
+            parse_output("31".to_string(), OutputRadix::Decimal),
+            Output::Valid(31)
+        );
+        assert_eq!(
+            parse_output("AB".to_string(), OutputRadix::Decimal),
+            Output::Invalid("AB".to_string())
+        );
+
+        assert_eq!(
+            parse_output("AB".to_string(), OutputRadix::Hex),
+            Output::Valid(0xAB)
+        );
+        assert_eq!(
+            parse_output("0xAB".to_string(), OutputRadix::Hex),
+            Output::Valid(0xAB)
+        );
+        assert_eq!(
+            parse_output("10".to_string(), OutputRadix::Hex),
+            Output::Valid(0x10)
+        );
+        assert_eq!(
+            parse_output("10".to_string(), OutputRadix::Decimal),
+            Output::Valid(10)
+        );
+    }
+
+    #[test]
+    fn test_normalize_response() {
+        assert_eq!(normalize_response("0x1"), "0x1");
+        assert_eq!(normalize_response("0x1\0"), "0x1");
+        assert_eq!(normalize_response("0x1\n"), "0x1");
+        assert_eq!(normalize_response("0x1\r\n"), "0x1");
+        assert_eq!(normalize_response("0x1\0\n"), "0x1");
+        assert_eq!(normalize_response("0x1\n\0"), "0x1");
+        assert_eq!(normalize_response("\0"), "");
+    }
+
+    #[test]
+    fn test_normalize_method() {
+        use crate::acpi_call::{normalize_method, PathStyle};
+
+        let cases = [
+            (
+                r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC"#,
+                r#"\_SB_.PCI0.LPC0.EC0_.VPC0.DYTC"#,
+            ),
+            (r#"\_SB.A.BC.DEF"#, r#"\_SB_.A___.BC__.DEF_"#),
+            (r#"^^EC0.GBMD"#, r#"^^EC0_.GBMD"#),
+            (r#"\_SB._"#, r#"\_SB_.____"#),
+        ];
+
+        for (short, padded) in cases {
+            assert_eq!(normalize_method(short, PathStyle::Padded), padded);
+            assert_eq!(normalize_method(padded, PathStyle::Short), short);
+            assert_eq!(normalize_method(short, PathStyle::Short), short);
+            assert_eq!(normalize_method(padded, PathStyle::Padded), padded);
+        }
+    }
+
+    #[test]
+    fn test_detect_path_style() {
+        use crate::acpi_call::{detect_path_style_with, Backend, PathStyle};
+
+        /// Only accepts the specified method, as if it only understood that path style.
+        struct OnlyAccepts(&'static str);
+
+        impl Backend for OnlyAccepts {
+            fn call(&self, command: &str) -> crate::acpi_call::Result<String> {
+                if command == self.0 {
+                    Ok("0x0".to_string())
+                } else {
+                    Ok("Error: AE_NOT_FOUND".to_string())
+                }
+            }
+        }
+
+        assert_eq!(
+            detect_path_style_with(&OnlyAccepts(PathStyle::PROBE_METHOD))
+                .expect("failed to detect path style"),
+            PathStyle::Short
+        );
+        assert_eq!(
+            detect_path_style_with(&OnlyAccepts(PathStyle::PADDED_PROBE_METHOD))
+                .expect("failed to detect path style"),
+            PathStyle::Padded
+        );
+        assert!(matches!(
+            detect_path_style_with(&OnlyAccepts("")),
+            Err(Error::MethodNotFound { method }) if method == PathStyle::PADDED_PROBE_METHOD
+        ));
+    }
+
+    #[test]
+    fn test_preflight_not_loaded() {
+        use crate::acpi_call::{preflight_at, PreflightError};
+
+        let root = tempfile::tempdir().expect("failed to create fixture directory");
+
+        assert!(matches!(
+            preflight_at(&root.path().join("call")),
+            Err(PreflightError::KernelModuleNotLoaded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_preflight_not_writable() {
+        use crate::acpi_call::{preflight_at, PreflightError};
+        use std::fs;
+
+        let file = tempfile::NamedTempFile::new().expect("failed to create temporary file");
+        assert!(preflight_at(file.path()).is_ok());
+
+        let mut permissions = fs::metadata(file.path())
+            .expect("failed to get metadata")
+            .permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(file.path(), permissions).expect("failed to set permissions");
+
+        assert!(matches!(
+            preflight_at(file.path()),
+            Err(PreflightError::InsufficientPrivileges { .. })
+        ));
+    }
+
+    #[test]
+    fn test_persistent_kernel_module() {
+        use crate::acpi_call::{Backend, PersistentKernelModule};
+        use std::fs::{File, OpenOptions};
+        use std::sync::PoisonError;
+
+        let file = tempfile::NamedTempFile::new().expect("failed to create temporary file");
+        let backend = PersistentKernelModule::with_path(file.path());
+
+        // the output is read from the start, where the command was just written
+        assert_eq!(backend.call("ab 1").expect("failed to call"), "ab 1");
+        assert_eq!(backend.call("cd 23").expect("failed to call"), "cd 23");
+
+        // nothing of a longer previous command is left over
+        assert_eq!(backend.call("g 6").expect("failed to call"), "g 6");
+
+        // simulate the handle being invalidated, as if the kernel module was reloaded, so that
+        // either writing or reading through it fails
+        *backend.file.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some(File::open(file.path()).expect("failed to open temporary file"));
+        assert_eq!(backend.call("ef 45").expect("failed to reopen"), "ef 45");
+        *backend.file.lock().unwrap_or_else(PoisonError::into_inner) = Some(
+            OpenOptions::new()
+                .write(true)
+                .open(file.path())
+                .expect("failed to open temporary file"),
+        );
+        assert_eq!(backend.call("hi 7").expect("failed to reopen"), "hi 7");
+        backend.available().expect("file reported as unavailable");
+
+        let path = file.path().to_path_buf();
+        drop(file);
+        let backend = PersistentKernelModule::with_path(path);
+        assert!(matches!(
+            backend.call("ab 1"),
+            Err(Error::KernelModuleNotLoaded { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "remote")]
+    fn test_remote_backend() {
+        use crate::acpi_call::{Backend, CommandRunner, RemoteBackend};
+        use std::io;
+        use std::sync::Mutex;
+
+        /// Remembers every command, answering each with the same output.
+        #[derive(Default)]
+        struct FakeRunner(Mutex<Vec<String>>);
+
+        impl CommandRunner for FakeRunner {
+            fn run(&self, command: &str) -> io::Result<String> {
+                self.0.lock().unwrap().push(command.to_string());
+                Ok("0x1\0".to_string())
+            }
+        }
+
+        let backend = RemoteBackend::new(FakeRunner::default());
+        let output = backend
+            .call(&command(r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#, [3]))
+            .expect("failed to issue remote command");
+        backend
+            .available()
+            .expect("remote acpi_call reported as unavailable");
+
+        assert_eq!(output, "0x1\0");
+        assert_eq!(
+            *backend.runner.0.lock().unwrap(),
+            [
+                r#"printf '%s' '\_SB.PCI0.LPC0.EC0.VPC0.SBMC 3' > /proc/acpi/call && cat /proc/acpi/call"#,
+                "test -e /proc/acpi/call",
+            ]
+        );
+        assert_eq!(
+            RemoteBackend::<FakeRunner>::remote_command("it's"),
+            r#"printf '%s' 'it'\''s' > /proc/acpi/call && cat /proc/acpi/call"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serialize_error() {
+        assert_eq!(
+            serde_json::to_value(Error::MethodNotFound {
+                method: r#"\_SB.PCI0.LPC0.EC0.SPMO"#.into(),
+            })
+            .expect("failed to serialize error"),
+            json!({ "type": "MethodNotFound", "method": r#"\_SB.PCI0.LPC0.EC0.SPMO"# })
+        );
+        assert_eq!(
+            serde_json::to_value(Error::KernelModuleNotLoaded {
+                source: io::Error::new(io::ErrorKind::NotFound, "no such file or directory"),
+            })
+            .expect("failed to serialize error"),
+            json!({ "type": "KernelModuleNotLoaded", "source": "no such file or directory" })
+        );
     }
 }
@@ -0,0 +1,145 @@
+//! Recommendations for keeping the battery healthy. See
+//! [`Context::battery_advice`](crate::context::Context::battery_advice).
+//!
+//! The advice is based on a single reading of the power supplies and battery modes, so it can't
+//! tell for how long the battery has been in a state; it should be asked for periodically rather
+//! than acted upon blindly.
+
+use std::fmt;
+
+/// The charge level in percent from which the battery is considered full.
+pub const FULL_CAPACITY: u8 = 95;
+
+/// The temperature of the battery in degrees celsius from which it is considered hot.
+pub const HOT_TEMPERATURE: f32 = 40.0;
+
+/// A recommendation for keeping the battery healthy.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Advice {
+    /// The laptop is plugged in and the battery is at least [`FULL_CAPACITY`], but battery
+    /// conservation is disabled. Keeping the battery fully charged wears it out, which battery
+    /// conservation prevents.
+    EnableConservation {
+        /// The charge level of the battery in percent.
+        capacity: u8,
+    },
+
+    /// Rapid charge is enabled while the battery is at least [`HOT_TEMPERATURE`]. Charging a hot
+    /// battery quickly wears it out faster.
+    DisableRapidCharge {
+        /// The temperature of the battery in degrees celsius.
+        temperature: f32,
+    },
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EnableConservation { capacity } => write!(
+                f,
+                "battery is at {}% while plugged in with battery conservation disabled; consider \
+                 enabling battery conservation",
+                capacity
+            ),
+            Self::DisableRapidCharge { temperature } => write!(
+                f,
+                "battery is at {:.1}°C with rapid charge enabled; consider disabling rapid charge",
+                temperature
+            ),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use crate::advice::Advice;
+    use crate::mock::{MockBackend, MockPowerSupply};
+    use crate::power_supply::BatteryCapacity;
+    use crate::{Context, Profile};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_enable_conservation() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let full = MockPowerSupply {
+            capacities: vec![BatteryCapacity {
+                name: "BAT0".into(),
+                percent: 100,
+            }],
+            ..MockPowerSupply::default()
+        };
+
+        context.power_supply = Some(Arc::new(full.clone()));
+        assert_eq!(
+            context
+                .battery_advice()
+                .expect("failed to get battery advice"),
+            [Advice::EnableConservation { capacity: 100 }]
+        );
+
+        // not plugged in, so the battery will discharge anyway
+        context.power_supply = Some(Arc::new(MockPowerSupply {
+            ac_online: false,
+            ..full.clone()
+        }));
+        assert!(context
+            .battery_advice()
+            .expect("failed to get battery advice")
+            .is_empty());
+
+        context.power_supply = Some(Arc::new(full));
+        backend.set_battery_conservation(true);
+        assert!(context
+            .battery_advice()
+            .expect("failed to get battery advice")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_disable_rapid_charge() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_rapid_charge(true);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let hot = MockPowerSupply {
+            temperature: 45.0,
+            ..MockPowerSupply::default()
+        };
+
+        context.power_supply = Some(Arc::new(hot.clone()));
+        assert_eq!(
+            context
+                .battery_advice()
+                .expect("failed to get battery advice"),
+            [Advice::DisableRapidCharge { temperature: 45.0 }]
+        );
+
+        // the capacity isn't needed for this advice
+        context.power_supply = Some(Arc::new(MockPowerSupply {
+            capacities: Vec::new(),
+            ..hot
+        }));
+        assert_eq!(
+            context
+                .battery_advice()
+                .expect("failed to get battery advice"),
+            [Advice::DisableRapidCharge { temperature: 45.0 }]
+        );
+
+        context.power_supply = Some(Arc::new(MockPowerSupply::default()));
+        assert!(context
+            .battery_advice()
+            .expect("failed to get battery advice")
+            .is_empty());
+
+        backend.set_rapid_charge(false);
+        context.power_supply = Some(Arc::new(hot));
+        assert!(context
+            .battery_advice()
+            .expect("failed to get battery advice")
+            .is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 //! Abstractions for enabling a battery mode.
 
-use crate::battery::{BatteryController, BatteryEnableGuard};
+use crate::battery::{AppliedHandler, BatteryController, BatteryEnableGuard, ConflictResolver};
 use crate::context::Context;
 use crate::Handler;
 use std::marker::PhantomData;
@@ -79,6 +79,13 @@ where
         }
     }
 
+    /// Consume the builder, enabling the battery immediately and letting the resolver decide what
+    /// to do if the conflicting battery mode is enabled. Returns what was done about the
+    /// conflicting battery mode.
+    pub fn resolve_with(self, resolver: &dyn ConflictResolver) -> Result<AppliedHandler, C::Error> {
+        self.controller.enable_with_resolver(resolver)
+    }
+
     /// Pick the ignore handler, moving on to the next stage.
     pub fn ignore(self) -> EnableBuilder<'ctrl, 'ctx, Call, C, D, DD> {
         self.handler(Handler::Ignore)
@@ -113,12 +120,13 @@ where
     }
 
     /// Consume the builder, enabling the battery immediately with the handler that was specified
-    /// from the previous stage.
-    pub fn now(self) -> Result<(), C::Error> {
+    /// from the previous stage. Returns what the handler actually did, which depends on whether
+    /// the conflicting battery mode was enabled.
+    pub fn now(self) -> Result<AppliedHandler, C::Error> {
         match self.handler() {
-            Handler::Ignore => self.controller.enable_ignore().map_err(Into::into),
+            Handler::Ignore => self.controller.enable_ignore(),
             Handler::Error => self.controller.enable_error(),
-            Handler::Switch => self.controller.enable_switch().map_err(Into::into),
+            Handler::Switch => self.controller.enable_switch(),
         }
     }
 }
@@ -1,12 +1,114 @@
 //! Shared contents between [`crate::battery_conservation`] and [`crate::rapid_charge`].
+//!
+//! # Guards
+//! The guards which enable a mode for a scope, and the one which sets the system performance mode
+//! for a scope, undo their change when they are dropped. Like any other value, guards are dropped
+//! in reverse order of creation, so nested guards unwind from the innermost outwards.
 mod private;
 
+use crate::profile::{Battery, SharedBatteryConfiguration};
 use crate::{acpi_call, Handler};
 use std::error::Error;
 use try_drop::PureTryDrop;
 
 pub mod enable;
 
+#[cfg(all(feature = "battery_conservation", feature = "rapid_charge"))]
+pub mod selector;
+
+/// The battery modes, which conflict with each other.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BatteryMode {
+    /// Battery conservation mode.
+    Conservation,
+
+    /// Rapid charge mode.
+    RapidCharge,
+}
+
+impl BatteryMode {
+    /// Get the battery mode which conflicts with this one.
+    pub const fn opposite(self) -> Self {
+        match self {
+            Self::Conservation => Self::RapidCharge,
+            Self::RapidCharge => Self::Conservation,
+        }
+    }
+
+    /// Get the configuration of this battery mode from the battery configuration of a profile.
+    pub const fn configuration(self, battery: &Battery) -> &SharedBatteryConfiguration {
+        match self {
+            Self::Conservation => &battery.conservation,
+            Self::RapidCharge => &battery.rapid_charge,
+        }
+    }
+
+    /// Get the command and parameter which have to be issued through `acpi_call` to either enable
+    /// or disable this battery mode.
+    pub fn action(self, battery: &Battery, enabled: bool) -> (&str, u32) {
+        (
+            &battery.set_command,
+            self.configuration(battery).param_for(enabled),
+        )
+    }
+}
+
+/// What the handler (or [resolver](ConflictResolver)) actually did when enabling a battery mode,
+/// which is useful for audit trails.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AppliedHandler {
+    /// The conflicting battery mode was enabled, so it was disabled first.
+    SwitchedOff(BatteryMode),
+
+    /// The conflicting battery mode wasn't enabled, so there was nothing to handle.
+    NoConflict,
+
+    /// The conflicting battery mode was left as is. With [`Handler::Ignore`], it isn't even
+    /// checked whether it was enabled.
+    Ignored,
+}
+
+/// What to do when enabling a battery mode while the conflicting one is enabled.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Resolution {
+    /// Enable the battery mode anyway.
+    Proceed,
+
+    /// Don't enable the battery mode, returning an error.
+    Abort,
+
+    /// Disable the conflicting battery mode, then enable the battery mode.
+    SwitchOff,
+}
+
+/// Decides what to do when enabling a battery mode while the conflicting one is enabled.
+///
+/// This is a generalization of [`Handler`], which allows policies such as "only switch while on
+/// AC".
+pub trait ConflictResolver {
+    /// Resolve the conflict with the specified battery mode, which is currently enabled.
+    fn resolve(&self, conflicting: BatteryMode) -> Resolution;
+}
+
+impl ConflictResolver for Handler {
+    fn resolve(&self, _conflicting: BatteryMode) -> Resolution {
+        match self {
+            Self::Ignore => Resolution::Proceed,
+            Self::Error => Resolution::Abort,
+            Self::Switch => Resolution::SwitchOff,
+        }
+    }
+}
+
+impl<F: Fn(BatteryMode) -> Resolution> ConflictResolver for F {
+    fn resolve(&self, conflicting: BatteryMode) -> Resolution {
+        self(conflicting)
+    }
+}
+
 #[doc(hidden)]
 #[allow(drop_bounds)]
 pub trait BatteryEnableGuard<'ctrl, 'ctx: 'ctrl, C: BatteryController<'ctrl, 'ctx>>:
@@ -30,7 +132,228 @@ pub trait BatteryController<'this, 'ctx: 'this>: Sized + private::BatteryControl
     type EnableGuard: BatteryEnableGuard<'this, 'ctx, Self>;
     type Error: Error + From<acpi_call::Error>;
 
-    fn enable_ignore(&mut self) -> acpi_call::Result<()>;
-    fn enable_error(&mut self) -> Result<(), Self::Error>;
-    fn enable_switch(&mut self) -> acpi_call::Result<()>;
+    fn enable_ignore(&mut self) -> Result<AppliedHandler, Self::Error>;
+    fn enable_error(&mut self) -> Result<AppliedHandler, Self::Error>;
+    fn enable_switch(&mut self) -> Result<AppliedHandler, Self::Error>;
+    fn enable_with_resolver(
+        &mut self,
+        resolver: &dyn ConflictResolver,
+    ) -> Result<AppliedHandler, Self::Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::battery::BatteryMode;
+    use crate::Profile;
+
+    #[test]
+    fn test_action() {
+        let battery = &Profile::IDEAPAD_AMD
+            .battery
+            .expect("profile has no battery section");
+
+        assert_eq!(
+            BatteryMode::Conservation.action(battery, true),
+            (r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#, 0x03)
+        );
+        assert_eq!(
+            BatteryMode::Conservation.action(battery, false),
+            (r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#, 0x05)
+        );
+        assert_eq!(
+            BatteryMode::RapidCharge.action(battery, true),
+            (r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#, 0x07)
+        );
+        assert_eq!(
+            BatteryMode::RapidCharge.action(battery, false),
+            (r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#, 0x08)
+        );
+    }
+}
+
+#[cfg(all(test, feature = "core"))]
+mod mock_tests {
+    use crate::battery::{AppliedHandler, BatteryMode, ConflictResolver, Resolution};
+    use crate::mock::MockBackend;
+    use crate::{battery_conservation, rapid_charge, Context, Handler, Profile};
+    use std::cell::Cell;
+    use std::sync::{Arc, Mutex};
+
+    /// Always resolves to the same resolution, remembering the conflicting battery mode.
+    struct FixedResolver {
+        resolution: Resolution,
+        conflicting: Cell<Option<BatteryMode>>,
+    }
+
+    impl FixedResolver {
+        fn new(resolution: Resolution) -> Self {
+            Self {
+                resolution,
+                conflicting: Cell::new(None),
+            }
+        }
+    }
+
+    impl ConflictResolver for FixedResolver {
+        fn resolve(&self, conflicting: BatteryMode) -> Resolution {
+            self.conflicting.set(Some(conflicting));
+            self.resolution
+        }
+    }
+
+    /// Try to enable rapid charge while battery conservation is enabled.
+    fn enable_conflicting(
+        resolver: &FixedResolver,
+    ) -> (MockBackend, rapid_charge::Result<AppliedHandler>) {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let result = context
+            .controllers()
+            .rapid_charge()
+            .enable()
+            .resolve_with(resolver);
+
+        assert_eq!(resolver.conflicting.get(), Some(BatteryMode::Conservation));
+        (backend, result)
+    }
+
+    #[test]
+    fn test_resolver_proceed() {
+        let (backend, result) = enable_conflicting(&FixedResolver::new(Resolution::Proceed));
+
+        assert_eq!(
+            result.expect("failed to enable rapid charge"),
+            AppliedHandler::Ignored
+        );
+        assert!(backend.rapid_charge());
+        assert!(backend.battery_conservation());
+    }
+
+    #[test]
+    fn test_resolver_abort() {
+        let (backend, result) = enable_conflicting(&FixedResolver::new(Resolution::Abort));
+
+        assert!(matches!(
+            result,
+            Err(rapid_charge::Error::BatteryConservationEnabled)
+        ));
+        assert!(!backend.rapid_charge());
+        assert!(backend.battery_conservation());
+    }
+
+    #[test]
+    fn test_resolver_switch_off() {
+        let (backend, result) = enable_conflicting(&FixedResolver::new(Resolution::SwitchOff));
+
+        assert_eq!(
+            result.expect("failed to enable rapid charge"),
+            AppliedHandler::SwitchedOff(BatteryMode::Conservation)
+        );
+        assert!(backend.rapid_charge());
+        assert!(!backend.battery_conservation());
+    }
+
+    #[test]
+    fn test_resolver_closure() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_rapid_charge(true);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let error = context
+            .controllers()
+            .battery_conservation()
+            .enable()
+            .resolve_with(&|_| Resolution::Abort)
+            .expect_err("battery conservation enable succeeded");
+
+        assert!(matches!(
+            error,
+            battery_conservation::Error::RapidChargeEnabled
+        ));
+    }
+
+    #[test]
+    fn test_applied_handler() {
+        let enable = |conflicting, handler| {
+            let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+            backend.set_rapid_charge(conflicting);
+
+            let mut context = Context::new(Profile::IDEAPAD_AMD);
+            context.backend = Some(Arc::new(backend));
+            context
+                .controllers()
+                .battery_conservation()
+                .enable()
+                .handler(handler)
+                .now()
+        };
+
+        for conflicting in [false, true] {
+            assert_eq!(
+                enable(conflicting, Handler::Ignore)
+                    .expect("failed to enable battery conservation"),
+                AppliedHandler::Ignored
+            );
+        }
+
+        for handler in [Handler::Error, Handler::Switch] {
+            assert_eq!(
+                enable(false, handler).expect("failed to enable battery conservation"),
+                AppliedHandler::NoConflict
+            );
+        }
+
+        assert!(matches!(
+            enable(true, Handler::Error),
+            Err(battery_conservation::Error::RapidChargeEnabled)
+        ));
+        assert_eq!(
+            enable(true, Handler::Switch).expect("failed to enable battery conservation"),
+            AppliedHandler::SwitchedOff(BatteryMode::RapidCharge)
+        );
+    }
+
+    #[test]
+    fn test_on_conflict() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let disabled = Arc::new(Mutex::new(Vec::new()));
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.on_conflict = Some(Arc::new({
+            let disabled = Arc::clone(&disabled);
+            move |mode| disabled.lock().unwrap().push(mode)
+        }));
+        let controllers = context.controllers();
+
+        // nothing conflicts, so nothing is switched off
+        controllers
+            .rapid_charge()
+            .enable()
+            .switch()
+            .now()
+            .expect("failed to enable rapid charge");
+        assert!(disabled.lock().unwrap().is_empty());
+
+        // ignoring the conflict doesn't switch anything off either
+        controllers
+            .battery_conservation()
+            .enable()
+            .ignore()
+            .now()
+            .expect("failed to enable battery conservation");
+        assert!(disabled.lock().unwrap().is_empty());
+
+        controllers
+            .rapid_charge()
+            .enable()
+            .switch()
+            .now()
+            .expect("failed to enable rapid charge");
+        assert_eq!(*disabled.lock().unwrap(), [BatteryMode::Conservation]);
+        assert!(!backend.battery_conservation());
+    }
 }
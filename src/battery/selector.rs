@@ -0,0 +1,128 @@
+//! Select a single battery mode, so that both can't be enabled at the same time.
+
+use crate::battery::{AppliedHandler, BatteryMode};
+use crate::battery_conservation::BatteryConservationController;
+use crate::context::{self, Context};
+use crate::rapid_charge::RapidChargeController;
+use try_drop::prelude::*;
+use try_drop::{GlobalFallbackTryDropStrategyHandler, GlobalTryDropStrategyHandler};
+
+/// Owns the decision of which battery mode is enabled.
+///
+/// Battery conservation and rapid charge are mutually exclusive, so selecting one always switches
+/// the other off first. As there is no way to enable a battery mode without switching off the
+/// other one through this type, the state where both are enabled can't be reached through it.
+pub struct BatterySelector<
+    'ctx,
+    D = GlobalTryDropStrategyHandler,
+    DD = GlobalFallbackTryDropStrategyHandler,
+> where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    battery_conservation: BatteryConservationController<'ctx, D, DD>,
+    rapid_charge: RapidChargeController<'ctx, D, DD>,
+}
+
+impl<'ctx, D, DD> BatterySelector<'ctx, D, DD>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// Create a new battery selector.
+    pub fn new(context: &'ctx Context<D, DD>) -> Self {
+        Self {
+            battery_conservation: BatteryConservationController::new(context),
+            rapid_charge: RapidChargeController::new(context),
+        }
+    }
+
+    /// Enable the battery mode, switching off the other one if it is enabled. Returns
+    /// [`AppliedHandler::SwitchedOff`] if the other one was switched off, or
+    /// [`AppliedHandler::NoConflict`] if it wasn't enabled.
+    ///
+    /// Unlike enabling a battery mode through its controller, this doesn't take a
+    /// [`Handler`](crate::Handler). [`Handler::Switch`](crate::Handler::Switch) is always used, as
+    /// ignoring the other mode could leave both enabled, which is what this type rules out, and
+    /// erroring would just be a more roundabout [`Self::selected`] check.
+    pub fn select(&mut self, mode: BatteryMode) -> context::Result<AppliedHandler> {
+        Ok(match mode {
+            BatteryMode::Conservation => self.battery_conservation.enable().switch().now()?,
+            BatteryMode::RapidCharge => self.rapid_charge.enable().switch().now()?,
+        })
+    }
+
+    /// Disable both battery modes.
+    pub fn deselect(&mut self) -> context::Result<()> {
+        self.battery_conservation.disable()?;
+        self.rapid_charge.disable()?;
+
+        Ok(())
+    }
+
+    /// Get which battery mode is enabled, if any.
+    ///
+    /// # Errors
+    /// If both battery modes are enabled, which can happen if the firmware was poked by something
+    /// else, a [`context::Error::BothBatteryModesEnabled`] is returned.
+    pub fn selected(&self) -> context::Result<Option<BatteryMode>> {
+        match (self.battery_conservation.get()?, self.rapid_charge.get()?) {
+            (false, false) => Ok(None),
+            (true, false) => Ok(Some(BatteryMode::Conservation)),
+            (false, true) => Ok(Some(BatteryMode::RapidCharge)),
+            (true, true) => Err(context::Error::BothBatteryModesEnabled),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use crate::battery::{AppliedHandler, BatteryMode};
+    use crate::mock::MockBackend;
+    use crate::{Context, Profile};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_select() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_rapid_charge(true);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut selector = context.controllers().battery_selector();
+
+        assert_eq!(
+            selector
+                .select(BatteryMode::Conservation)
+                .expect("failed to select battery conservation"),
+            AppliedHandler::SwitchedOff(BatteryMode::RapidCharge)
+        );
+        assert!(backend.battery_conservation());
+        assert!(!backend.rapid_charge());
+
+        assert_eq!(
+            selector
+                .select(BatteryMode::RapidCharge)
+                .expect("failed to select rapid charge"),
+            AppliedHandler::SwitchedOff(BatteryMode::Conservation)
+        );
+        assert!(!backend.battery_conservation());
+        assert!(backend.rapid_charge());
+        assert_eq!(
+            selector.selected().expect("failed to get selected mode"),
+            Some(BatteryMode::RapidCharge)
+        );
+
+        selector.deselect().expect("failed to deselect");
+        assert_eq!(
+            selector.selected().expect("failed to get selected mode"),
+            None
+        );
+        assert_eq!(
+            selector
+                .select(BatteryMode::Conservation)
+                .expect("failed to select battery conservation"),
+            AppliedHandler::NoConflict
+        );
+    }
+}
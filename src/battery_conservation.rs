@@ -5,12 +5,18 @@
 //! disabled then enable it, the battery level will be capped at the level you enabled battery
 //! conservation mode at. For example, if you charge your battery to 80% and then enable battery
 //! conservation mode, the battery level will be capped at 80%.
-use crate::acpi_call::{self, acpi_call, acpi_call_expect_valid};
+use crate::acpi_call;
 use crate::battery::enable::EnableBuilder;
-use crate::battery::{BatteryController, BatteryEnableGuard};
+use crate::battery::{
+    AppliedHandler, BatteryController, BatteryEnableGuard, BatteryMode, ConflictResolver,
+    Resolution,
+};
 use crate::context::Context;
 // use crate::fallible_drop_strategy::{FallibleDropStrategies, FallibleDropStrategy};
 use crate::{battery_conservation, Handler};
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::{fs, io, mem};
 use thiserror::Error;
 use try_drop::prelude::*;
 use try_drop::{DropAdapter, GlobalFallbackTryDropStrategyHandler, GlobalTryDropStrategyHandler};
@@ -37,6 +43,171 @@ pub enum Error {
     /// enabled.
     #[error("rapid charge is enabled, disable it first before enabling battery conservation mode")]
     RapidChargeEnabled,
+
+    /// The battery didn't charge to the requested level before the
+    /// [timeout](Context::capacity_poll_timeout) elapsed.
+    #[error("timed out waiting for the battery to charge to {level}% (it is at {capacity}%)")]
+    Timeout {
+        /// The level which the battery was supposed to charge to.
+        level: u8,
+
+        /// The level of the battery when the timeout elapsed.
+        capacity: u8,
+    },
+
+    /// The battery was asked to charge to a level above 100%, which it could never reach.
+    #[error("can't wait for the battery to charge to {level}%, as it can't go above 100%")]
+    InvalidLevel {
+        /// The requested level.
+        level: u8,
+    },
+
+    /// The battery capacity couldn't be read.
+    #[error("failed to read battery capacity: {error}")]
+    PowerSupply {
+        /// The underlying IO error.
+        #[from]
+        error: io::Error,
+    },
+
+    /// Battery conservation still wasn't enabled after every attempt of
+    /// [`BatteryConservationController::enable_confirmed`].
+    #[error("battery conservation was still disabled after {attempts} attempt(s) to enable it")]
+    CouldNotConfirmEnable {
+        /// How many times enabling battery conservation was attempted.
+        attempts: u32,
+    },
+
+    /// The profile doesn't support setting the battery conservation threshold, only toggling
+    /// battery conservation.
+    #[error("the profile doesn't support setting the battery conservation threshold")]
+    ThresholdUnsupported,
+
+    /// The firmware doesn't accept the percentage as a battery conservation threshold.
+    #[error("{percent}% isn't a valid battery conservation threshold (allowed: {allowed:?})")]
+    InvalidThreshold {
+        /// The requested percentage.
+        percent: u8,
+
+        /// The percentages which the firmware accepts.
+        allowed: Vec<u8>,
+    },
+}
+
+/// Toggles battery conservation through the `conservation_mode` attribute of the in-tree
+/// `ideapad_acpi` driver instead of `acpi_call`, so the `acpi_call` kernel module isn't needed for
+/// battery conservation.
+///
+/// It is used when set as the [`Context::sysfs_battery_backend`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct SysfsBatteryBackend {
+    /// The path to the `conservation_mode` attribute.
+    pub path: PathBuf,
+}
+
+impl SysfsBatteryBackend {
+    /// Where the `ideapad_acpi` driver usually exposes the `conservation_mode` attribute.
+    pub const DEFAULT_PATH: &'static str =
+        "/sys/bus/platform/drivers/ideapad_acpi/VPC2004:00/conservation_mode";
+
+    /// Use the `conservation_mode` attribute at the specified path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Get whether battery conservation is enabled.
+    pub fn get(&self) -> io::Result<bool> {
+        match fs::read_to_string(&self.path)?.trim() {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            value => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid value '{}' in '{}'", value, self.path.display()),
+            )),
+        }
+    }
+
+    /// Enable or disable battery conservation.
+    pub fn set(&self, enabled: bool) -> io::Result<()> {
+        fs::write(&self.path, if enabled { "1" } else { "0" })
+    }
+}
+
+impl Default for SysfsBatteryBackend {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_PATH)
+    }
+}
+
+/// The battery conservation state read through `acpi_call` and through the sysfs interface of the
+/// `ideapad_acpi` driver. See [`BatteryConservationController::cross_check`].
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CrossCheck {
+    /// Whether battery conservation is enabled according to `acpi_call`.
+    pub acpi: bool,
+
+    /// Whether battery conservation is enabled according to sysfs, or [`None`] if the driver
+    /// isn't available.
+    pub sysfs: Option<bool>,
+}
+
+impl CrossCheck {
+    /// Check whether both states agree, or [`None`] if there is no sysfs state to compare with.
+    pub fn agree(&self) -> Option<bool> {
+        self.sysfs.map(|sysfs| sysfs == self.acpi)
+    }
+}
+
+/// The battery level in percent which battery conservation mode limits the battery to, unless it
+/// was already above it when battery conservation mode was enabled.
+pub const LIMIT: u8 = 60;
+
+/// Things worth telling the user about after enabling battery conservation, which aren't errors.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Warning {
+    /// The battery was above the [limit](LIMIT) when battery conservation was enabled, so it
+    /// will be capped at its current level instead of discharging to the limit.
+    CappedAbove60 {
+        /// The battery level which the battery is capped at.
+        at_percent: u8,
+    },
+}
+
+/// The state of battery conservation mode, as reported by the embedded controller.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Tristate {
+    /// Battery conservation mode is enabled.
+    Enabled,
+
+    /// Battery conservation mode is disabled.
+    Disabled,
+
+    /// The embedded controller reported a value which is neither enabled nor disabled.
+    Unknown(u32),
+}
+
+impl Tristate {
+    /// Get the state from the raw output of the get command.
+    pub const fn from_output(output: u32) -> Self {
+        match output {
+            0 => Self::Disabled,
+            1 => Self::Enabled,
+            output => Self::Unknown(output),
+        }
+    }
+}
+
+impl From<bool> for Tristate {
+    fn from(enabled: bool) -> Self {
+        if enabled {
+            Self::Enabled
+        } else {
+            Self::Disabled
+        }
+    }
 }
 
 /// Inner value for [`BatteryConservationEnableGuard`].
@@ -72,11 +243,19 @@ where
     }
 
     unsafe fn try_drop(&mut self) -> Result<(), Self::Error> {
-        self.controller.disable()
+        let context = self.controller.context;
+        context.record_drop_error(
+            "BatteryConservationController::disable",
+            self.controller.disable(),
+        )
     }
 }
 
 /// "Guarantees" that the battery conservation mode is enabled for the scope.
+///
+/// Battery conservation mode is disabled when the guard is dropped, see
+/// [nesting guards](crate::battery#guards). Use [`BatteryConservationEnableGuard::disarm`] to
+/// leave battery conservation mode enabled instead.
 #[must_use]
 pub struct BatteryConservationEnableGuard<'bc, 'ctx, D, DD>(
     DropAdapter<BatteryConservationEnableGuardInner<'bc, 'ctx, D, DD>>,
@@ -134,6 +313,18 @@ where
     }
 }
 
+impl<'bc, 'ctx, D, DD> BatteryConservationEnableGuard<'bc, 'ctx, D, DD>
+where
+    'ctx: 'bc,
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// Consume the guard without disabling battery conservation mode.
+    pub fn disarm(self) {
+        mem::forget(self)
+    }
+}
+
 impl<'bc, 'ctx, D, DD> BatteryEnableGuard<'bc, 'ctx, BatteryConservationController<'ctx, D, DD>>
     for BatteryConservationEnableGuard<'bc, 'ctx, D, DD>
 where
@@ -174,7 +365,11 @@ where
     }
 
     unsafe fn try_drop(&mut self) -> Result<(), Self::Error> {
-        self.controller.enable().handler(self.handler).now()
+        let context = self.controller.context;
+        context.record_drop_error(
+            "BatteryConservationController::enable",
+            self.controller.enable().handler(self.handler).now(),
+        )
     }
 }
 
@@ -210,28 +405,87 @@ where
     }
 
     /// Disable battery conservation.
+    ///
+    /// If a [`Context::sysfs_battery_backend`] is set, failures to write to it are returned as an
+    /// [`acpi_call::Error::Io`].
     pub fn disable(&mut self) -> acpi_call::Result<()> {
-        acpi_call(
-            self.context.profile.battery.set_command.to_string(),
-            [self.context.profile.battery.conservation.parameters.disable],
-        )?;
+        self.context.ensure_writable()?;
 
-        Ok(())
+        if let Some(sysfs) = self.context.sysfs_battery() {
+            return Ok(sysfs.set(false)?);
+        }
+
+        let battery = self.context.battery_profile()?;
+        let parameter = battery.conservation.param_for(false);
+        self.context.acpi_call(&battery.set_command, [parameter])?;
+
+        self.context
+            .verify_write(&battery.set_command, [parameter], false, || self.get())
     }
 
     /// Get the battery conservation status.
+    ///
+    /// If a [`Context::sysfs_battery_backend`] is set, failures to read from it are returned as an
+    /// [`acpi_call::Error::Io`].
     pub fn get(&self) -> acpi_call::Result<bool> {
-        let output = acpi_call_expect_valid(
-            self.context
-                .profile
-                .battery
-                .conservation
-                .get_command
-                .to_string(),
-            [],
-        )?;
+        if let Some(sysfs) = self.context.sysfs_battery() {
+            return Ok(sysfs.get()?);
+        }
+
+        self.get_acpi()
+    }
+
+    fn get_acpi(&self) -> acpi_call::Result<bool> {
+        let battery = self.context.battery_profile()?;
+        let output = self
+            .context
+            .acpi_call_expect_valid(&battery.conservation.get_command, [])?;
+
+        Ok(battery.conservation_enabled(output))
+    }
 
-        Ok(output != 0)
+    /// Read the battery conservation status through both `acpi_call` and the sysfs interface of
+    /// the `ideapad_acpi` driver, so that disagreements could be flagged, which usually means that
+    /// the profile is subtly wrong.
+    ///
+    /// Sysfs is read through the [`Context::sysfs_battery_backend`] if it is set, otherwise
+    /// through the [default path](SysfsBatteryBackend::DEFAULT_PATH). If the driver isn't
+    /// available, only the `acpi_call` state is returned.
+    pub fn cross_check(&self) -> acpi_call::Result<CrossCheck> {
+        let acpi = self.get_acpi()?;
+        let sysfs = match self.context.sysfs_battery() {
+            Some(sysfs) => sysfs.get(),
+            None => SysfsBatteryBackend::default().get(),
+        };
+        let sysfs = match sysfs {
+            Ok(enabled) => Some(enabled),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(CrossCheck { acpi, sysfs })
+    }
+
+    /// Get the battery conservation mode state, without treating unexpected values as enabled.
+    ///
+    /// Unlike [`BatteryConservationController::get`], this reports values other than 0 or 1 as
+    /// [`Tristate::Unknown`], which is useful for detecting anomalies. If the profile has a
+    /// [status mask](crate::profile::Battery::conservation_status_mask), the other bits are
+    /// expected to be set, so only the masked bit is looked at, like [`Self::get`] does.
+    pub fn get_tristate(&self) -> acpi_call::Result<Tristate> {
+        if let Some(sysfs) = self.context.sysfs_battery() {
+            return Ok(sysfs.get()?.into());
+        }
+
+        let battery = self.context.battery_profile()?;
+        let output = self
+            .context
+            .acpi_call_expect_valid(&battery.conservation.get_command, [])?;
+
+        Ok(match battery.conservation_status_mask {
+            Some(_) => battery.conservation_enabled(output).into(),
+            None => Tristate::from_output(output),
+        })
     }
 
     /// Check if battery conservation is enabled.
@@ -244,6 +498,175 @@ where
         self.get().map(|enabled| !enabled)
     }
 
+    /// Get the battery conservation status, or [`None`] if the model doesn't have the method it is
+    /// read through, so that an unavailable status could be told apart from a disabled one.
+    ///
+    /// Only an [`acpi_call::Error::MethodNotFound`] is treated as unavailable; every other error
+    /// is still returned.
+    pub fn try_status(&self) -> acpi_call::Result<Option<bool>> {
+        match self.get() {
+            Ok(enabled) => Ok(Some(enabled)),
+            Err(acpi_call::Error::MethodNotFound { .. }) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Get the handlers which make sense when enabling battery conservation with the profile of the
+    /// context, for example to populate a dropdown. Rapid charge is always configured in the same
+    /// section of the profile as battery conservation, so this is either every handler, or none if
+    /// the profile doesn't support battery conservation at all.
+    pub fn available_handlers(&self) -> Vec<Handler> {
+        if self.context.profile.capabilities().has_conservation {
+            Handler::ALL.to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Check whether enabling or disabling battery conservation would actually change anything,
+    /// without changing it.
+    pub fn would_change(&self, enabled: bool) -> acpi_call::Result<bool> {
+        self.get().map(|current| current != enabled)
+    }
+
+    /// Enable battery conservation with the specified handler, returning a
+    /// [`Warning::CappedAbove60`] if the battery is above the [limit](LIMIT), so that the user could
+    /// be told that it won't discharge to the limit.
+    ///
+    /// The warning is only advisory, so if the battery capacity couldn't be read, no warning is
+    /// returned.
+    pub fn enable_warning(&mut self, handler: Handler) -> Result<Option<Warning>> {
+        self.enable().handler(handler).now()?;
+
+        Ok(self
+            .context
+            .power_supply()
+            .battery_capacity()
+            .ok()
+            .filter(|&capacity| capacity > LIMIT)
+            .map(|at_percent| Warning::CappedAbove60 { at_percent }))
+    }
+
+    /// Set the battery conservation threshold to the specified percentage, for profiles which
+    /// declare a [`ConservationThreshold`](crate::profile::ConservationThreshold).
+    ///
+    /// # Errors
+    /// If the profile only supports toggling battery conservation, an
+    /// [`Error::ThresholdUnsupported`] is returned. If the percentage isn't one of the allowed
+    /// ones, an [`Error::InvalidThreshold`] is returned.
+    pub fn set_threshold(&mut self, percent: u8) -> Result<()> {
+        self.context.ensure_writable()?;
+
+        let threshold = self
+            .context
+            .battery_profile()?
+            .conservation_threshold
+            .as_ref()
+            .ok_or(Error::ThresholdUnsupported)?;
+
+        if !threshold.allows(percent) {
+            return Err(Error::InvalidThreshold {
+                percent,
+                allowed: threshold.allowed.to_vec(),
+            });
+        }
+
+        self.context
+            .acpi_call(&threshold.set_command, [percent.into()])?;
+
+        Ok(())
+    }
+
+    /// Enable battery conservation with the specified handler, then read it back, trying again up
+    /// to the specified number of attempts if the firmware left it disabled anyway. At least one
+    /// attempt is always made.
+    ///
+    /// A [write which wasn't applied](acpi_call::Error::WriteNotApplied) when
+    /// [`Context::verify_writes`] is set counts as a failed attempt rather than an error.
+    ///
+    /// # Errors
+    /// If battery conservation is still disabled after every attempt, an
+    /// [`Error::CouldNotConfirmEnable`] is returned. Any other error is returned immediately.
+    pub fn enable_confirmed(&mut self, handler: Handler, attempts: NonZeroU32) -> Result<()> {
+        let attempts = attempts.get();
+
+        for _ in 0..attempts {
+            match self.enable().handler(handler).now() {
+                Ok(_) => {}
+                Err(Error::AcpiCall {
+                    error: acpi_call::Error::WriteNotApplied { .. },
+                }) => continue,
+                Err(error) => return Err(error),
+            }
+
+            if self.get()? {
+                return Ok(());
+            }
+        }
+
+        Err(Error::CouldNotConfirmEnable { attempts })
+    }
+
+    /// Disable battery conservation until the battery charged to the specified level, after which
+    /// battery conservation is enabled again. This allows charging to a full battery once without
+    /// having to wait around for it.
+    ///
+    /// The battery capacity is read every [`Context::capacity_poll_interval`], and waiting gives up
+    /// after [`Context::capacity_poll_timeout`] if it is set. Battery conservation is enabled
+    /// again with the [switch](Handler::Switch) handler, even if waiting failed.
+    ///
+    /// # Errors
+    /// If the level is above 100%, an [`Error::InvalidLevel`] is returned without disabling
+    /// battery conservation. If the timeout elapsed, an [`Error::Timeout`] is returned. If the
+    /// battery capacity couldn't be read, an [`Error::PowerSupply`] is returned.
+    pub fn disable_until(&mut self, level: u8) -> Result<()> {
+        if level > 100 {
+            return Err(Error::InvalidLevel { level });
+        }
+
+        self.disable()?;
+        let waited = self.wait_for_capacity(level);
+        self.enable().switch().now()?;
+
+        waited
+    }
+
+    fn wait_for_capacity(&self, level: u8) -> Result<()> {
+        let clock = self.context.clock();
+        let started = clock.now();
+
+        loop {
+            let capacity = self.context.power_supply().battery_capacity()?;
+
+            if capacity >= level {
+                return Ok(());
+            }
+
+            if let Some(timeout) = self.context.capacity_poll_timeout {
+                if clock.now().saturating_duration_since(started) >= timeout {
+                    return Err(Error::Timeout { level, capacity });
+                }
+            }
+
+            clock.sleep(self.context.capacity_poll_interval);
+        }
+    }
+
+    fn enable_unchecked(&mut self) -> acpi_call::Result<()> {
+        self.context.ensure_writable()?;
+
+        if let Some(sysfs) = self.context.sysfs_battery() {
+            return sysfs.set(true).map_err(acpi_call::Error::from);
+        }
+
+        let battery = self.context.battery_profile()?;
+        let parameter = battery.conservation.param_for(true);
+        self.context.acpi_call(&battery.set_command, [parameter])?;
+
+        self.context
+            .verify_write(&battery.set_command, [parameter], true, || self.get())
+    }
+
     /// Ensures that the battery conservation mode is disabled for this scope.
     pub fn disable_guard<'bc>(
         &'bc mut self,
@@ -263,31 +686,42 @@ where
     type EnableGuard = BatteryConservationEnableGuard<'this, 'ctx, D, DD>;
     type Error = Error;
 
-    fn enable_ignore(&mut self) -> acpi_call::Result<()> {
-        acpi_call(
-            self.context.profile.battery.set_command.to_string(),
-            [self.context.profile.battery.conservation.parameters.enable],
-        )?;
+    fn enable_ignore(&mut self) -> Result<AppliedHandler, Self::Error> {
+        self.enable_unchecked()?;
 
-        Ok(())
+        Ok(AppliedHandler::Ignored)
     }
 
-    fn enable_error(&mut self) -> Result<(), Self::Error> {
-        if self.context.controllers().rapid_charge().enabled()? {
-            Err(Error::RapidChargeEnabled)
-        } else {
-            self.enable_ignore().map_err(Into::into)
-        }
+    fn enable_error(&mut self) -> Result<AppliedHandler, Self::Error> {
+        self.enable_with_resolver(&Handler::Error)
     }
 
-    fn enable_switch(&mut self) -> acpi_call::Result<()> {
+    fn enable_switch(&mut self) -> Result<AppliedHandler, Self::Error> {
+        self.enable_with_resolver(&Handler::Switch)
+    }
+
+    fn enable_with_resolver(
+        &mut self,
+        resolver: &dyn ConflictResolver,
+    ) -> Result<AppliedHandler, Self::Error> {
         let mut rapid_charge = self.context.controllers().rapid_charge();
+        let mut applied = AppliedHandler::NoConflict;
 
         if rapid_charge.enabled()? {
-            rapid_charge.disable()?;
+            match resolver.resolve(BatteryMode::RapidCharge) {
+                Resolution::Proceed => applied = AppliedHandler::Ignored,
+                Resolution::Abort => return Err(Error::RapidChargeEnabled),
+                Resolution::SwitchOff => {
+                    rapid_charge.disable()?;
+                    self.context.notify_conflict(BatteryMode::RapidCharge);
+                    applied = AppliedHandler::SwitchedOff(BatteryMode::RapidCharge);
+                }
+            }
         }
 
-        self.enable_ignore()
+        self.enable_unchecked()?;
+
+        Ok(applied)
     }
 }
 
@@ -304,6 +738,7 @@ where
         .enable()
         .switch()
         .now()
+        .map(|_| ())
 }
 
 /// Disable battery conservation.
@@ -489,3 +924,569 @@ mod tests {
         todo!()
     }
 }
+
+#[cfg(all(test, feature = "core"))]
+mod mock_tests {
+    use crate::battery_conservation::{CrossCheck, Error, SysfsBatteryBackend, Tristate, Warning};
+    use crate::clock::Clock;
+    use crate::mock::{MockBackend, MockClock, MockPowerSupply};
+    use crate::power_supply::{BatteryCapacity, PowerSupply};
+    use crate::profile::ConservationThreshold;
+    use crate::state::StateChange;
+    use crate::{acpi_call, Context, Handler, Profile};
+    use std::num::NonZeroU32;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use std::{fs, io};
+
+    #[test]
+    fn test_available_handlers() {
+        let context = Context::new(Profile::IDEAPAD_AMD);
+        assert_eq!(
+            context
+                .controllers()
+                .battery_conservation()
+                .available_handlers(),
+            Handler::ALL
+        );
+
+        let context = Context::new(Profile {
+            battery: None,
+            ..Profile::IDEAPAD_AMD
+        });
+        assert!(context
+            .controllers()
+            .battery_conservation()
+            .available_handlers()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_rapid_charge_clears_conservation() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_rapid_charge_clears_conservation(true);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let controllers = context.controllers();
+
+        controllers
+            .battery_conservation()
+            .enable()
+            .ignore()
+            .now()
+            .expect("failed to enable battery conservation");
+        let before = context.snapshot();
+
+        controllers
+            .rapid_charge()
+            .enable()
+            .ignore()
+            .now()
+            .expect("failed to enable rapid charge");
+        assert!(!backend.battery_conservation());
+
+        // the change is detected, even though battery conservation was never disabled
+        assert!(before
+            .changes(&context.snapshot())
+            .contains(&StateChange::BatteryConservation {
+                from: Some(true),
+                to: Some(false),
+            }));
+
+        // and corrected by enabling battery conservation again
+        controllers
+            .battery_conservation()
+            .enable()
+            .switch()
+            .now()
+            .expect("failed to enable battery conservation");
+        assert!(backend.battery_conservation());
+        assert!(!backend.rapid_charge());
+    }
+
+    /// A battery which charges by 10% every time its capacity is read.
+    struct ChargingPowerSupply(Mutex<u8>);
+
+    impl PowerSupply for ChargingPowerSupply {
+        fn battery_temperature(&self) -> io::Result<f32> {
+            Ok(30.0)
+        }
+
+        fn battery_capacities(&self) -> io::Result<Vec<BatteryCapacity>> {
+            let mut percent = self.0.lock().unwrap();
+            let capacity = BatteryCapacity {
+                name: "BAT0".into(),
+                percent: *percent,
+            };
+            *percent = (*percent + 10).min(100);
+
+            Ok(vec![capacity])
+        }
+
+        fn ac_online(&self) -> io::Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn charging_context(backend: &MockBackend, clock: &MockClock) -> Context {
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.clock = Some(Arc::new(clock.clone()));
+        context.power_supply = Some(Arc::new(ChargingPowerSupply(Mutex::new(60))));
+        context.capacity_poll_interval = Duration::from_secs(60);
+        context
+    }
+
+    #[test]
+    fn test_enable_warning() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let enable_at = |percent| {
+            let mut context = Context::new(Profile::IDEAPAD_AMD);
+            context.backend = Some(Arc::new(backend.clone()));
+            context.power_supply = Some(Arc::new(MockPowerSupply {
+                capacities: vec![BatteryCapacity {
+                    name: "BAT0".into(),
+                    percent,
+                }],
+                ..MockPowerSupply::default()
+            }));
+
+            context
+                .controllers()
+                .battery_conservation()
+                .enable_warning(Handler::Switch)
+                .expect("failed to enable battery conservation")
+        };
+
+        assert_eq!(enable_at(50), None);
+        assert_eq!(enable_at(60), None);
+        assert_eq!(
+            enable_at(80),
+            Some(Warning::CappedAbove60 { at_percent: 80 })
+        );
+        assert!(backend.battery_conservation());
+
+        // the handler is respected
+        backend.set_battery_conservation(false);
+        backend.set_rapid_charge(true);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        assert!(matches!(
+            context
+                .controllers()
+                .battery_conservation()
+                .enable_warning(Handler::Error),
+            Err(Error::RapidChargeEnabled)
+        ));
+        assert!(!backend.battery_conservation());
+    }
+
+    #[test]
+    fn test_disable_until() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+        let clock = MockClock::default();
+        let started = clock.now();
+        let context = charging_context(&backend, &clock);
+
+        context
+            .controllers()
+            .battery_conservation()
+            .disable_until(100)
+            .expect("failed to charge battery");
+
+        // 60, 70, 80, 90, 100
+        assert_eq!(clock.now() - started, Duration::from_secs(4 * 60));
+        assert!(backend.battery_conservation());
+        assert_eq!(
+            backend
+                .calls()
+                .iter()
+                .filter(|call| call.contains("SBMC"))
+                .collect::<Vec<_>>(),
+            [
+                r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 5"#,
+                r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 3"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disable_until_timeout() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+        let clock = MockClock::default();
+        let mut context = charging_context(&backend, &clock);
+        context.capacity_poll_timeout = Some(Duration::from_secs(2 * 60));
+
+        assert!(matches!(
+            context
+                .controllers()
+                .battery_conservation()
+                .disable_until(100),
+            Err(Error::Timeout {
+                level: 100,
+                capacity: 80,
+            })
+        ));
+        assert!(backend.battery_conservation());
+    }
+
+    #[test]
+    fn test_disable_until_invalid_level() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+        let clock = MockClock::default();
+        let context = charging_context(&backend, &clock);
+
+        assert!(matches!(
+            context
+                .controllers()
+                .battery_conservation()
+                .disable_until(101),
+            Err(Error::InvalidLevel { level: 101 })
+        ));
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn test_try_status() {
+        type Check = fn(&acpi_call::Result<Option<bool>>) -> bool;
+        let cases: [(fn(&MockBackend), Check); 3] = [
+            (
+                |backend| backend.set_battery_conservation(true),
+                |result| matches!(result, Ok(Some(true))),
+            ),
+            (
+                |backend| backend.remove_method(r#"\_SB.PCI0.LPC0.EC0.BTSM"#),
+                |result| matches!(result, Ok(None)),
+            ),
+            (
+                |backend| backend.respond(r#"\_SB.PCI0.LPC0.EC0.BTSM"#, "garbage"),
+                |result| matches!(result, Err(acpi_call::Error::UnknownValue { .. })),
+            ),
+        ];
+
+        for (index, (setup, check)) in cases.into_iter().enumerate() {
+            let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+            setup(&backend);
+            let mut context = Context::new(Profile::IDEAPAD_AMD);
+            context.backend = Some(Arc::new(backend));
+
+            let result = context.controllers().battery_conservation().try_status();
+            assert!(check(&result), "case {index}: {result:?}");
+        }
+    }
+
+    fn get_with_mask(output: &str, mask: Option<u32>) -> bool {
+        let mut profile = Profile::IDEAPAD_AMD;
+        profile.battery.as_mut().unwrap().conservation_status_mask = mask;
+        let backend = MockBackend::new(profile.clone());
+        backend.respond(r#"\_SB.PCI0.LPC0.EC0.BTSM"#, output);
+
+        let mut context = Context::new(profile);
+        context.backend = Some(Arc::new(backend));
+        context
+            .controllers()
+            .battery_conservation()
+            .get()
+            .expect("failed to get battery conservation")
+    }
+
+    #[test]
+    fn test_get_masked() {
+        assert!(get_with_mask("0x24", Some(0x20)));
+        assert!(!get_with_mask("0x24", Some(0x01)));
+        assert!(!get_with_mask("0x0", Some(0x20)));
+    }
+
+    #[test]
+    fn test_get_tristate_masked() {
+        let mut profile = Profile::IDEAPAD_AMD;
+        profile.battery.as_mut().unwrap().conservation_status_mask = Some(0x20);
+        let backend = MockBackend::new(profile.clone());
+        let mut context = Context::new(profile);
+        context.backend = Some(Arc::new(backend.clone()));
+        let battery_conservation = context.controllers().battery_conservation();
+
+        for (output, expected) in [("0x24", Tristate::Enabled), ("0x4", Tristate::Disabled)] {
+            backend.respond(r#"\_SB.PCI0.LPC0.EC0.BTSM"#, output);
+            assert_eq!(
+                battery_conservation
+                    .get_tristate()
+                    .expect("failed to get battery conservation"),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_unmasked() {
+        assert!(get_with_mask("0x24", None));
+        assert!(get_with_mask("0x1", None));
+        assert!(!get_with_mask("0x0", None));
+    }
+
+    #[test]
+    fn test_cross_check() {
+        let root = tempfile::tempdir().expect("failed to create fixture directory");
+        let path = root.path().join("conservation_mode");
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.sysfs_battery_backend = Some(SysfsBatteryBackend::new(&path));
+        let cross_check = || {
+            context
+                .controllers()
+                .battery_conservation()
+                .cross_check()
+                .expect("failed to cross check battery conservation")
+        };
+
+        fs::write(&path, "1\n").expect("failed to write conservation mode");
+        assert_eq!(
+            cross_check(),
+            CrossCheck {
+                acpi: true,
+                sysfs: Some(true)
+            }
+        );
+        assert_eq!(cross_check().agree(), Some(true));
+
+        fs::write(&path, "0\n").expect("failed to write conservation mode");
+        assert_eq!(cross_check().agree(), Some(false));
+
+        fs::remove_file(&path).expect("failed to remove conservation mode");
+        assert_eq!(
+            cross_check(),
+            CrossCheck {
+                acpi: true,
+                sysfs: None
+            }
+        );
+        assert_eq!(cross_check().agree(), None);
+    }
+
+    #[test]
+    fn test_sysfs_battery_backend() {
+        let root = tempfile::tempdir().expect("failed to create fixture directory");
+        let path = root.path().join("conservation_mode");
+        fs::write(&path, "0\n").expect("failed to write conservation mode");
+
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.sysfs_battery_backend = Some(SysfsBatteryBackend::new(&path));
+        let mut battery_conservation = context.controllers().battery_conservation();
+
+        assert!(!battery_conservation
+            .get()
+            .expect("failed to get battery conservation status"));
+
+        battery_conservation
+            .enable()
+            .ignore()
+            .now()
+            .expect("failed to enable battery conservation");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1");
+        assert!(battery_conservation
+            .get()
+            .expect("failed to get battery conservation status"));
+
+        battery_conservation
+            .disable()
+            .expect("failed to disable battery conservation");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0");
+
+        // the embedded controller was never touched
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn test_enable_guard_disarm() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut battery_conservation = context.controllers().battery_conservation();
+
+        battery_conservation
+            .enable()
+            .ignore()
+            .guard()
+            .expect("failed to enable battery conservation")
+            .disarm();
+
+        assert!(backend.battery_conservation());
+        assert_eq!(backend.calls(), [r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 3"#]);
+    }
+
+    #[test]
+    fn test_get_tristate() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let battery_conservation = context.controllers().battery_conservation();
+        let get_tristate = || {
+            battery_conservation
+                .get_tristate()
+                .expect("failed to get battery conservation state")
+        };
+
+        assert_eq!(get_tristate(), Tristate::Disabled);
+
+        backend.set_battery_conservation(true);
+        assert_eq!(get_tristate(), Tristate::Enabled);
+
+        let battery = context
+            .battery_profile()
+            .expect("profile has no battery section");
+        backend.respond(&battery.conservation.get_command, "0x2\0");
+        assert_eq!(get_tristate(), Tristate::Unknown(2));
+        assert!(battery_conservation
+            .get()
+            .expect("failed to get battery conservation status"));
+    }
+
+    #[test]
+    fn test_would_change() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let battery_conservation = context.controllers().battery_conservation();
+
+        assert!(!battery_conservation
+            .would_change(true)
+            .expect("failed to check battery conservation"));
+        assert!(battery_conservation
+            .would_change(false)
+            .expect("failed to check battery conservation"));
+        assert!(backend.battery_conservation());
+    }
+
+    fn threshold_profile() -> Profile {
+        let mut profile = Profile::IDEAPAD_AMD;
+        profile.battery = profile.battery.map(|battery| {
+            battery.with_conservation_threshold(ConservationThreshold::r#static(
+                r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMT"#,
+                &[60, 80, 100],
+            ))
+        });
+        profile
+    }
+
+    #[test]
+    fn test_set_threshold() {
+        let backend = MockBackend::new(threshold_profile());
+        let mut context = Context::new(threshold_profile());
+        context.backend = Some(Arc::new(backend.clone()));
+
+        context
+            .controllers()
+            .battery_conservation()
+            .set_threshold(80)
+            .expect("failed to set battery conservation threshold");
+        assert_eq!(backend.conservation_threshold(), Some(80));
+        assert_eq!(backend.calls(), [r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMT 80"#]);
+    }
+
+    #[test]
+    fn test_set_threshold_invalid() {
+        let backend = MockBackend::new(threshold_profile());
+        let mut context = Context::new(threshold_profile());
+        context.backend = Some(Arc::new(backend.clone()));
+
+        let error = context
+            .controllers()
+            .battery_conservation()
+            .set_threshold(70)
+            .expect_err("invalid battery conservation threshold was accepted");
+        assert!(matches!(
+            error,
+            Error::InvalidThreshold { percent: 70, ref allowed } if allowed == &[60, 80, 100]
+        ));
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn test_set_threshold_unsupported() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        assert!(matches!(
+            context
+                .controllers()
+                .battery_conservation()
+                .set_threshold(80),
+            Err(Error::ThresholdUnsupported)
+        ));
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn test_enable_confirmed() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_rapid_charge_clears_conservation(true);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let battery = context
+            .battery_profile()
+            .expect("profile has no battery section");
+        let enable =
+            acpi_call::command(&battery.set_command, [battery.conservation.param_for(true)]);
+        let enable_rapid_charge =
+            acpi_call::command(&battery.set_command, [battery.rapid_charge.param_for(true)]);
+
+        // rapid charge is enabled behind our back right after battery conservation is enabled,
+        // which leaves battery conservation disabled again
+        let interruptions = Arc::new(Mutex::new(1));
+        context.acpi_tap = Some(Arc::new({
+            let backend = backend.clone();
+            let enable = enable.clone();
+            let interruptions = Arc::clone(&interruptions);
+            move |event| {
+                let mut interruptions = interruptions.lock().unwrap();
+
+                if acpi_call::command(event.command, event.params.iter().copied()) == enable
+                    && *interruptions > 0
+                {
+                    *interruptions -= 1;
+                    acpi_call::Backend::call(&backend, &enable_rapid_charge)
+                        .expect("failed to enable rapid charge");
+                }
+            }
+        }));
+        let mut battery_conservation = context.controllers().battery_conservation();
+
+        battery_conservation
+            .enable_confirmed(Handler::Ignore, NonZeroU32::new(3).unwrap())
+            .expect("failed to enable battery conservation");
+        assert!(backend.battery_conservation());
+        assert_eq!(
+            backend
+                .calls()
+                .into_iter()
+                .filter(|call| *call == enable)
+                .count(),
+            2
+        );
+
+        backend.set_battery_conservation(false);
+        *interruptions.lock().unwrap() = 2;
+        assert!(matches!(
+            battery_conservation.enable_confirmed(Handler::Ignore, NonZeroU32::new(2).unwrap()),
+            Err(Error::CouldNotConfirmEnable { attempts: 2 })
+        ));
+        assert!(!backend.battery_conservation());
+
+        // a single attempt is always made
+        backend.set_rapid_charge(false);
+        battery_conservation
+            .enable_confirmed(Handler::Ignore, NonZeroU32::new(1).unwrap())
+            .expect("failed to enable battery conservation");
+        assert!(backend.battery_conservation());
+    }
+}
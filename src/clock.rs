@@ -0,0 +1,27 @@
+//! An abstraction over time, so that time dependent behaviour could be controlled.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    /// Get the current instant.
+    fn now(&self) -> Instant;
+
+    /// Block the current thread for the specified duration.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The system clock, which is the clock used by default.
+#[derive(Debug, Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
@@ -1,17 +1,239 @@
 //! Contains [`Context`], a structure which will be used by the majority of this crate.
 
+use crate::clock::{Clock, SystemClock};
+use crate::firmware::{self, FirmwareInfo};
+use crate::power_supply::{self, PowerSupply};
+use crate::preset::{PowerProfile, Preset, PresetReport};
+use crate::self_test::SelfTestReport;
+use crate::state::{DesiredState, DeviceState};
+use crate::thermal::{self, Thermal};
 use crate::{profile, Profile};
+use std::convert::Infallible;
+use std::io;
+use std::sync::{Arc, OnceLock};
+use thiserror::Error;
 use try_drop::prelude::*;
 use try_drop::{GlobalFallbackTryDropStrategyHandler, GlobalTryDropStrategyHandler};
 
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+use crate::{
+    acpi_call::{
+        self, AcpiEvent, AcpiTap, Backend, CommandRewriter, KernelModule, Output, PathStyle,
+        PreflightError,
+    },
+    self_test::ValidationReport,
+};
+
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+use crate::{battery::BatteryMode, profile::Battery};
+
+#[cfg(all(feature = "battery_conservation", feature = "rapid_charge"))]
+use crate::battery::selector::BatterySelector;
+
 #[cfg(feature = "battery_conservation")]
-use crate::battery_conservation::BatteryConservationController;
+use crate::advice::{self, Advice};
+
+#[cfg(feature = "battery_conservation")]
+use crate::battery_conservation::{self, BatteryConservationController, SysfsBatteryBackend};
 
 #[cfg(feature = "rapid_charge")]
-use crate::rapid_charge::RapidChargeController;
+use crate::rapid_charge::{self, RapidChargeController, TemperatureLimit};
 
 #[cfg(feature = "system_performance")]
-use crate::system_performance::SystemPerformanceController;
+use crate::{
+    profile::SystemPerformance,
+    system_performance::{self, SystemPerformanceController, SystemPerformanceMode},
+};
+
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    iter, mem,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Handy wrapper for [`enum@Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Bad things which could happen when using a [`Context`] directly.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// One or more settings couldn't be reset to their defaults. See
+    /// [`Context::reset_to_defaults`].
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    #[error("failed to reset {} setting(s) to their defaults", .errors.len())]
+    Reset {
+        /// The errors which occurred, in the order they occurred.
+        errors: Vec<Error>,
+    },
+
+    /// The battery mode couldn't be swapped because neither battery mode is enabled. See
+    /// [`Controllers::swap_battery_mode`].
+    #[cfg(all(feature = "battery_conservation", feature = "rapid_charge"))]
+    #[error(
+        "neither battery conservation nor rapid charge is enabled, so there is nothing to swap"
+    )]
+    NoBatteryModeEnabled,
+
+    /// The battery mode couldn't be swapped because both battery modes are enabled. See
+    /// [`Controllers::swap_battery_mode`] and [`Controllers::reconcile_battery`].
+    #[cfg(all(feature = "battery_conservation", feature = "rapid_charge"))]
+    #[error(
+        "both battery conservation and rapid charge are enabled, so it is unclear which to swap to"
+    )]
+    BothBatteryModesEnabled,
+
+    /// One or more settings couldn't be applied. See [`Context::with_state`].
+    #[error("failed to apply the state of the device")]
+    ApplyState {
+        /// What happened to each setting.
+        report: PresetReport,
+
+        /// What happened to each setting when restoring the state afterwards, if that failed as
+        /// well.
+        restore: Option<PresetReport>,
+    },
+
+    /// One or more settings couldn't be restored to what they were before. See
+    /// [`Context::with_state`].
+    #[error("failed to restore the state of the device")]
+    RestoreState {
+        /// What happened to each setting.
+        report: PresetReport,
+    },
+
+    /// An error occurred when calling `acpi_call`.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    #[error("{error}")]
+    AcpiCall {
+        /// The underlying error itself.
+        #[from]
+        error: acpi_call::Error,
+    },
+
+    /// The power supplies couldn't be read.
+    #[error("failed to read the power supplies: {error}")]
+    PowerSupply {
+        /// The underlying IO error.
+        error: io::Error,
+    },
+
+    /// An error occurred when enabling battery conservation.
+    #[cfg(feature = "battery_conservation")]
+    #[error("{error}")]
+    BatteryConservation {
+        /// The underlying error itself.
+        #[from]
+        error: battery_conservation::Error,
+    },
+
+    /// An error occurred when enabling rapid charge.
+    #[cfg(feature = "rapid_charge")]
+    #[error("{error}")]
+    RapidCharge {
+        /// The underlying error itself.
+        #[from]
+        error: rapid_charge::Error,
+    },
+
+    /// An error occurred when setting the system performance mode.
+    #[cfg(feature = "system_performance")]
+    #[error("{error}")]
+    SystemPerformance {
+        /// The underlying error itself.
+        #[from]
+        error: system_performance::Error,
+    },
+}
+
+/// A guard which failed to restore the state of the device when it was dropped. See
+/// [`Context::capture_drop_errors`].
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GuardDropError {
+    /// The controller method which the guard called to restore the state, for example
+    /// `RapidChargeController::disable`.
+    pub method: String,
+
+    /// The error which the method failed with.
+    pub error: String,
+}
+
+/// Restores the state of the device which it was created with when dropped, unless it was already
+/// restored. See [`Context::with_state`].
+struct RestoreGuard<'ctx, D, DD>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    context: &'ctx Context<D, DD>,
+    original: Option<Preset>,
+}
+
+impl<D, DD> RestoreGuard<'_, D, DD>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    fn restore(&mut self) -> Result<()> {
+        match self
+            .original
+            .take()
+            .map(|original| original.restore(self.context))
+        {
+            Some(report) if !report.succeeded() => Err(Error::RestoreState { report }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<D, DD> Drop for RestoreGuard<'_, D, DD>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    fn drop(&mut self) {
+        // this might be running while panicking, so the error can only be recorded
+        #[cfg(any(
+            feature = "battery_conservation",
+            feature = "rapid_charge",
+            feature = "system_performance"
+        ))]
+        let _ = self
+            .context
+            .record_drop_error("Context::with_state", self.restore());
+
+        #[cfg(not(any(
+            feature = "battery_conservation",
+            feature = "rapid_charge",
+            feature = "system_performance"
+        )))]
+        let _ = self.restore();
+    }
+}
 
 /// Creates controllers.
 #[derive(Copy, Clone)]
@@ -54,6 +276,61 @@ where
     pub fn system_performance(&self) -> SystemPerformanceController<'ctx, D, DD> {
         SystemPerformanceController::new(self.context)
     }
+
+    /// Creates a new [`BatterySelector`] instance.
+    #[cfg(all(feature = "battery_conservation", feature = "rapid_charge"))]
+    pub fn battery_selector(&self) -> BatterySelector<'ctx, D, DD> {
+        BatterySelector::new(self.context)
+    }
+
+    /// Repair the inconsistent state where both battery conservation and rapid charge are enabled,
+    /// which shouldn't be possible but can happen if the firmware was poked by something else.
+    ///
+    /// If both are enabled, the battery mode which isn't preferred is disabled. Returns whether a
+    /// correction was made.
+    #[cfg(all(feature = "battery_conservation", feature = "rapid_charge"))]
+    pub fn reconcile_battery(&self, prefer: BatteryMode) -> acpi_call::Result<bool> {
+        if !(self.battery_conservation().get()? && self.rapid_charge().get()?) {
+            return Ok(false);
+        }
+
+        match prefer.opposite() {
+            BatteryMode::Conservation => self.battery_conservation().disable()?,
+            BatteryMode::RapidCharge => self.rapid_charge().disable()?,
+        }
+
+        Ok(true)
+    }
+
+    /// Swap from whichever battery mode is enabled to the other one, returning the battery mode
+    /// which is now enabled.
+    ///
+    /// This isn't atomic; the enabled battery mode is disabled first, so if enabling the other one
+    /// fails, neither is enabled.
+    ///
+    /// # Errors
+    /// If neither or both battery modes are enabled, an [`Error::NoBatteryModeEnabled`] or
+    /// [`Error::BothBatteryModesEnabled`] is returned respectively.
+    #[cfg(all(feature = "battery_conservation", feature = "rapid_charge"))]
+    pub fn swap_battery_mode(&self) -> Result<BatteryMode> {
+        let mut battery_conservation = self.battery_conservation();
+        let mut rapid_charge = self.rapid_charge();
+
+        match (battery_conservation.get()?, rapid_charge.get()?) {
+            (true, false) => {
+                battery_conservation.disable()?;
+                rapid_charge.enable().ignore().now()?;
+                Ok(BatteryMode::RapidCharge)
+            }
+            (false, true) => {
+                rapid_charge.disable()?;
+                battery_conservation.enable().ignore().now()?;
+                Ok(BatteryMode::Conservation)
+            }
+            (false, false) => Err(Error::NoBatteryModeEnabled),
+            (true, true) => Err(Error::BothBatteryModesEnabled),
+        }
+    }
 }
 
 /// A context, which will be used by all controllers in this crate.
@@ -70,21 +347,457 @@ where
 
     /// The try drop strategy which will be run if the first try drop strategy fails.
     pub fallback_try_drop_strategy: DD,
+
+    /// If set, `acpi_call` commands are issued through this backend instead of the
+    /// [`KernelModule`]. See [`Self::backend()`].
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub backend: Option<Arc<dyn Backend>>,
+
+    /// If set, any two `acpi_call` operations issued through this context are spaced at least
+    /// this far apart, blocking until enough time has passed. This protects flaky embedded
+    /// controllers from being overwhelmed when several controllers poll at once.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub acpi_call_min_interval: Option<Duration>,
+
+    /// If set, every write is followed by reading the state back, failing with an
+    /// [`acpi_call::Error::WriteNotApplied`] if it didn't change as expected. Writes through the
+    /// [`Self::sysfs_battery_backend`] aren't verified.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub verify_writes: bool,
+
+    /// If set, a write which repeats the last value written to the same set command through this
+    /// context is skipped, returning the output of the last write instead. Any read forgets every
+    /// remembered write, so a write is never skipped if the state may have been changed
+    /// elsewhere. This protects the embedded controller from redundant writes, for example when a
+    /// misbehaving loop keeps setting the same system performance mode.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub coalesce_writes: bool,
+
+    /// If set, every write, such as setting the system performance mode or enabling battery
+    /// conservation, fails with an [`acpi_call::Error::ReadOnly`] without touching the embedded
+    /// controller. Reads work normally. This is meant for services which only monitor the device.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub read_only: bool,
+
+    /// The style which method paths are rewritten into before being issued, in case the
+    /// `acpi_call` kernel module doesn't accept the style of the profile. The methods of the
+    /// profile are used as is with the default [`PathStyle::Short`].
+    ///
+    /// It is [detected](acpi_call::detect_path_style_with) through the backend when the profile is
+    /// detected, through [`ContextBuilder::detect`] or [`Context::try_default`]. The style which
+    /// the [`KernelModule`] accepts is only detected the first time, like the profile, see
+    /// [`Profile::clear_profile_cache`]. Setting it overrides the detected style.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub path_style: PathStyle,
+
+    /// If set, this is called with every `acpi_call` operation issued through this context, reads
+    /// and writes alike, along with its result. This is meant for debugging, for example to dump a
+    /// trace of the interactions with the embedded controller to a file.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub acpi_tap: Option<AcpiTap>,
+
+    /// If set, this is called with the method and parameters of every `acpi_call` operation
+    /// issued through this context, after it was rewritten into the [path style](Self::path_style),
+    /// and whatever it returns is issued instead. This allows compatibility shims, such as
+    /// replacing a deprecated method with a new one without editing the profile.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub command_rewriter: Option<CommandRewriter>,
+
+    /// If set, battery conservation is toggled through the sysfs interface of the `ideapad_acpi`
+    /// driver instead of the [backend](Self::backend).
+    #[cfg(feature = "battery_conservation")]
+    pub sysfs_battery_backend: Option<SysfsBatteryBackend>,
+
+    /// If set, this is called with the conflicting battery mode whenever it was disabled in order
+    /// to enable the other one, for example to notify the user that rapid charge was turned off to
+    /// enable battery conservation.
+    #[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+    pub on_conflict: Option<Arc<dyn Fn(BatteryMode) + Send + Sync>>,
+
+    /// If set, guards which fail to restore the state of the device when they are dropped also
+    /// record the error in this context, in addition to passing it to the try drop strategy.
+    /// This lets a service find out that the device may be in an unexpected state, for example
+    /// after a panic. See [`Self::drained_drop_errors`].
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub capture_drop_errors: bool,
+
+    /// If set, power supply readings, such as the battery temperature, come from here instead of
+    /// sysfs. See [`Self::power_supply()`].
+    pub power_supply: Option<Arc<dyn PowerSupply>>,
+
+    /// If set, the CPU package temperature comes from here instead of sysfs. See
+    /// [`Self::thermal()`].
+    pub thermal: Option<Arc<dyn Thermal>>,
+
+    /// If set, rapid charge will refuse to be enabled when the battery is too hot.
+    #[cfg(feature = "rapid_charge")]
+    pub temperature_limit: Option<TemperatureLimit>,
+
+    /// If set, this clock is used for anything time dependent instead of the [`SystemClock`]. See
+    /// [`Self::clock()`].
+    pub clock: Option<Arc<dyn Clock>>,
+
+    /// How often the battery capacity is read when waiting for the battery to charge. See
+    /// [`BatteryConservationController::disable_until`].
+    #[cfg(feature = "battery_conservation")]
+    pub capacity_poll_interval: Duration,
+
+    /// If set, waiting for the battery to charge gives up after this much time, which is 8 hours by
+    /// default. See [`BatteryConservationController::disable_until`].
+    #[cfg(feature = "battery_conservation")]
+    pub capacity_poll_timeout: Option<Duration>,
+
+    /// If set, the system performance mode can't be set again until this much time has passed
+    /// since it was last set through this context.
+    #[cfg(feature = "system_performance")]
+    pub system_performance_cooldown: Option<Duration>,
+
+    /// If set, system performance modes which the firmware doesn't currently allow are refused.
+    /// See [`SystemPerformanceController::supported_modes`].
+    #[cfg(feature = "system_performance")]
+    pub reject_unsupported_system_performance_modes: bool,
+
+    /// If set, extreme performance is refused while on battery, since many models silently ignore
+    /// it. Whether AC power is online is read from [`Self::power_supply`]. Guards restoring extreme
+    /// performance aren't refused, as it was already in effect.
+    #[cfg(feature = "system_performance")]
+    pub extreme_performance_requires_ac: bool,
+
+    /// How often the system performance mode is read when waiting for it to settle. See
+    /// [`SystemPerformanceController::measure_settle_time`].
+    #[cfg(feature = "system_performance")]
+    pub settle_poll_interval: Duration,
+
+    /// If set, waiting for the system performance mode to settle gives up after this much time.
+    /// See [`SystemPerformanceController::measure_settle_time`].
+    #[cfg(feature = "system_performance")]
+    pub settle_timeout: Option<Duration>,
+
+    #[cfg(feature = "system_performance")]
+    pub(crate) last_system_performance_set: Mutex<Option<Instant>>,
+
+    #[cfg(feature = "system_performance")]
+    pub(crate) active_system_performance_guards: Mutex<usize>,
+
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub(crate) last_acpi_call: Mutex<Option<Instant>>,
+
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    drop_errors: Mutex<Vec<GuardDropError>>,
+
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    last_writes: Mutex<BTreeMap<String, (Vec<u32>, Output)>>,
+
+    profile_was_fallback: bool,
 }
 
-impl Context {
-    /// Creates a new context.
-    pub const fn new(profile: Profile) -> Self {
+/// Builds a [`Context`], for when more than the profile has to be chosen upfront.
+///
+/// `P` is where the profile comes from: either a [`Profile`] which was specified, or a [`Detect`]
+/// which detects it when the context is built. Only the latter can fail to build.
+pub struct ContextBuilder<
+    D = GlobalTryDropStrategyHandler,
+    DD = GlobalFallbackTryDropStrategyHandler,
+    P = Profile,
+> where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    profile: P,
+    fallible_try_drop_strategy: D,
+    fallback_try_drop_strategy: DD,
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    backend: Option<Arc<dyn Backend>>,
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    path_style: Option<PathStyle>,
+}
+
+/// The profile of a [`ContextBuilder`] which is detected when the context is built. See
+/// [`ContextBuilder::detect`].
+pub struct Detect {
+    fallback_profile: Option<Profile>,
+}
+
+impl ContextBuilder {
+    /// Start building a context with the specified profile.
+    pub fn new(profile: Profile) -> Self {
+        Self::with_profile(profile)
+    }
+}
+
+impl ContextBuilder<GlobalTryDropStrategyHandler, GlobalFallbackTryDropStrategyHandler, Detect> {
+    /// Start building a context with a profile which is detected when the context is built, like
+    /// [`Context::try_default`] does. See [`Self::try_build`].
+    pub fn detect() -> Self {
+        Self::with_profile(Detect {
+            fallback_profile: None,
+        })
+    }
+}
+
+impl<P> ContextBuilder<GlobalTryDropStrategyHandler, GlobalFallbackTryDropStrategyHandler, P> {
+    fn with_profile(profile: P) -> Self {
         Self {
             profile,
             fallible_try_drop_strategy: GlobalTryDropStrategyHandler,
             fallback_try_drop_strategy: GlobalFallbackTryDropStrategyHandler,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            backend: None,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            path_style: None,
+        }
+    }
+}
+
+impl<D, DD, P> ContextBuilder<D, DD, P>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// Use the specified try drop strategies instead of the global ones.
+    pub fn strategies<D2, DD2>(self, main: D2, fallback: DD2) -> ContextBuilder<D2, DD2, P>
+    where
+        D2: FallibleTryDropStrategy,
+        DD2: FallbackTryDropStrategy,
+    {
+        ContextBuilder {
+            profile: self.profile,
+            fallible_try_drop_strategy: main,
+            fallback_try_drop_strategy: fallback,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            backend: self.backend,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            path_style: self.path_style,
+        }
+    }
+
+    /// Issue `acpi_call` commands through the specified backend instead of the [`KernelModule`].
+    /// See [`Context::backend`].
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn backend(mut self, backend: impl Backend + 'static) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Rewrite method paths into the specified style instead of detecting it. See
+    /// [`Context::path_style`].
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn path_style(mut self, path_style: PathStyle) -> Self {
+        self.path_style = Some(path_style);
+        self
+    }
+
+    /// Build the context with the profile which `resolve` makes out of `P`, along with whether it
+    /// is the fallback profile.
+    fn build_from<E>(
+        self,
+        resolve: impl FnOnce(P) -> Result<(Profile, bool), E>,
+    ) -> Result<Context<D, DD>, E> {
+        let (profile, profile_was_fallback) = resolve(self.profile)?;
+        let mut context = Context::new_with_strategies(
+            profile,
+            self.fallible_try_drop_strategy,
+            self.fallback_try_drop_strategy,
+        );
+        context.profile_was_fallback = profile_was_fallback;
+
+        #[cfg(any(
+            feature = "battery_conservation",
+            feature = "rapid_charge",
+            feature = "system_performance"
+        ))]
+        if let Some(backend) = self.backend {
+            context.backend = Some(backend);
+        }
+
+        #[cfg(any(
+            feature = "battery_conservation",
+            feature = "rapid_charge",
+            feature = "system_performance"
+        ))]
+        if let Some(path_style) = self.path_style {
+            context.path_style = path_style;
+        }
+
+        Ok(context)
+    }
+}
+
+impl<D, DD> ContextBuilder<D, DD>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// Build the context. Everything which wasn't chosen is the same as [`Context::new`].
+    pub fn build(self) -> Context<D, DD> {
+        match self.build_from(|profile| Ok::<_, Infallible>((profile, false))) {
+            Ok(context) => context,
+            Err(never) => match never {},
         }
     }
+}
+
+impl<D, DD> ContextBuilder<D, DD, Detect>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// If no profile matches this laptop, use the specified profile instead of failing. This keeps
+    /// a service running on near-compatible hardware; whether it happened can be checked through
+    /// [`Context::profile_was_fallback`].
+    ///
+    /// Only a [`profile::Error::NoValidProfileInSearchPath`] falls back to the profile, as every
+    /// other error means that detection itself didn't work.
+    pub fn fallback_profile(mut self, profile: Profile) -> Self {
+        self.profile.fallback_profile = Some(profile);
+        self
+    }
+
+    /// Detect the profile, then build the context. Unless it was chosen, the
+    /// [path style](Context::path_style) is detected as well, falling back to the default if that
+    /// fails, as the error would show up again on the first command anyway. Everything else which
+    /// wasn't chosen is the same as [`Context::new`].
+    ///
+    /// # Errors
+    /// The same errors as [`Profile::find_cached`], unless a
+    /// [fallback profile](Self::fallback_profile) is used instead.
+    pub fn try_build(self) -> profile::Result<Context<D, DD>> {
+        self.try_build_with(Profile::find_cached)
+    }
+
+    fn try_build_with(
+        self,
+        find: impl FnOnce() -> profile::Result<Profile>,
+    ) -> profile::Result<Context<D, DD>> {
+        #[cfg(any(
+            feature = "battery_conservation",
+            feature = "rapid_charge",
+            feature = "system_performance"
+        ))]
+        let path_style = self.path_style;
+        let context = self.build_from(|detect| match (find(), detect.fallback_profile) {
+            (Ok(profile), _) => Ok((profile, false)),
+            (Err(profile::Error::NoValidProfileInSearchPath { .. }), Some(fallback)) => {
+                Ok((fallback, true))
+            }
+            (Err(error), _) => Err(error),
+        })?;
+
+        #[cfg(any(
+            feature = "battery_conservation",
+            feature = "rapid_charge",
+            feature = "system_performance"
+        ))]
+        let context = match path_style {
+            Some(_) => context,
+            None => context.with_detected_path_style(),
+        };
+
+        Ok(context)
+    }
+}
 
-    /// Try and create a new context by trying to find a profile.
+impl Context {
+    /// Start building a context with the specified profile. See [`ContextBuilder`].
+    pub fn builder(profile: Profile) -> ContextBuilder {
+        ContextBuilder::new(profile)
+    }
+
+    /// Creates a new context.
+    pub const fn new(profile: Profile) -> Self {
+        Self::new_with_strategies(
+            profile,
+            GlobalTryDropStrategyHandler,
+            GlobalFallbackTryDropStrategyHandler,
+        )
+    }
+
+    /// Try and create a new context by trying to find a profile, detecting the
+    /// [path style](Self::path_style) as well. See [`ContextBuilder::try_build`].
+    ///
+    /// The profile is only detected the first time, see [`Profile::find_cached`].
     pub fn try_default() -> profile::Result<Self> {
-        Ok(Self::new(Profile::find()?))
+        ContextBuilder::detect().try_build()
     }
 }
 
@@ -94,21 +807,1627 @@ where
     DD: FallbackTryDropStrategy,
 {
     /// Creates a new context with the specified try drop strategies.
-    pub fn new_with_strategies(profile: Profile, main: D, fallback: DD) -> Self {
+    pub const fn new_with_strategies(profile: Profile, main: D, fallback: DD) -> Self {
         Self {
             profile,
             fallible_try_drop_strategy: main,
             fallback_try_drop_strategy: fallback,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            backend: None,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            acpi_call_min_interval: None,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            verify_writes: false,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            coalesce_writes: false,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            read_only: false,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            path_style: PathStyle::Short,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            acpi_tap: None,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            command_rewriter: None,
+            #[cfg(feature = "battery_conservation")]
+            sysfs_battery_backend: None,
+            #[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+            on_conflict: None,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            capture_drop_errors: false,
+            power_supply: None,
+            thermal: None,
+            #[cfg(feature = "rapid_charge")]
+            temperature_limit: None,
+            clock: None,
+            #[cfg(feature = "battery_conservation")]
+            capacity_poll_interval: Duration::from_secs(60),
+            #[cfg(feature = "battery_conservation")]
+            capacity_poll_timeout: Some(Duration::from_secs(8 * 60 * 60)),
+            #[cfg(feature = "system_performance")]
+            system_performance_cooldown: None,
+            #[cfg(feature = "system_performance")]
+            reject_unsupported_system_performance_modes: false,
+            #[cfg(feature = "system_performance")]
+            extreme_performance_requires_ac: false,
+            #[cfg(feature = "system_performance")]
+            settle_poll_interval: Duration::from_millis(50),
+            #[cfg(feature = "system_performance")]
+            settle_timeout: Some(Duration::from_secs(10)),
+            #[cfg(feature = "system_performance")]
+            last_system_performance_set: Mutex::new(None),
+            #[cfg(feature = "system_performance")]
+            active_system_performance_guards: Mutex::new(0),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            last_acpi_call: Mutex::new(None),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            drop_errors: Mutex::new(Vec::new()),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            last_writes: Mutex::new(BTreeMap::new()),
+            profile_was_fallback: false,
         }
     }
 
-    /// Try and create a new context by trying to find a profile.
+    /// Try and create a new context by trying to find a profile, detecting the
+    /// [path style](Self::path_style) as well. See [`ContextBuilder::try_build`].
+    ///
+    /// The profile is only detected the first time, see [`Profile::find_cached`].
     pub fn try_default_with_strategies(main: D, fallback: DD) -> profile::Result<Self> {
-        Ok(Self::new_with_strategies(Profile::find()?, main, fallback))
+        ContextBuilder::detect()
+            .strategies(main, fallback)
+            .try_build()
     }
 
     /// Create a controller creator.
     pub fn controllers(&self) -> Controllers<D, DD> {
         Controllers::new(self)
     }
+
+    /// Create a new context which shares everything with this one, except for the try drop
+    /// strategies, which are replaced with the specified ones.
+    ///
+    /// The profile is cloned, while the backend, power supply and clock are shared with this
+    /// context. The time the system performance mode was last set is carried over, so a cooldown
+    /// can't be bypassed by overriding the strategies.
+    pub fn with_strategy_override<D2, DD2>(&self, main: D2, fallback: DD2) -> Context<D2, DD2>
+    where
+        D2: FallibleTryDropStrategy,
+        DD2: FallbackTryDropStrategy,
+    {
+        Context {
+            profile: self.profile.clone(),
+            fallible_try_drop_strategy: main,
+            fallback_try_drop_strategy: fallback,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            backend: self.backend.clone(),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            acpi_call_min_interval: self.acpi_call_min_interval,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            verify_writes: self.verify_writes,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            coalesce_writes: self.coalesce_writes,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            read_only: self.read_only,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            path_style: self.path_style,
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            acpi_tap: self.acpi_tap.clone(),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            command_rewriter: self.command_rewriter.clone(),
+            #[cfg(feature = "battery_conservation")]
+            sysfs_battery_backend: self.sysfs_battery_backend.clone(),
+            #[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+            on_conflict: self.on_conflict.clone(),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            capture_drop_errors: self.capture_drop_errors,
+            power_supply: self.power_supply.clone(),
+            thermal: self.thermal.clone(),
+            #[cfg(feature = "rapid_charge")]
+            temperature_limit: self.temperature_limit,
+            clock: self.clock.clone(),
+            #[cfg(feature = "battery_conservation")]
+            capacity_poll_interval: self.capacity_poll_interval,
+            #[cfg(feature = "battery_conservation")]
+            capacity_poll_timeout: self.capacity_poll_timeout,
+            #[cfg(feature = "system_performance")]
+            system_performance_cooldown: self.system_performance_cooldown,
+            #[cfg(feature = "system_performance")]
+            reject_unsupported_system_performance_modes: self
+                .reject_unsupported_system_performance_modes,
+            #[cfg(feature = "system_performance")]
+            extreme_performance_requires_ac: self.extreme_performance_requires_ac,
+            #[cfg(feature = "system_performance")]
+            settle_poll_interval: self.settle_poll_interval,
+            #[cfg(feature = "system_performance")]
+            settle_timeout: self.settle_timeout,
+            #[cfg(feature = "system_performance")]
+            last_system_performance_set: Mutex::new(
+                *self
+                    .last_system_performance_set
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+            ),
+            #[cfg(feature = "system_performance")]
+            active_system_performance_guards: Mutex::new(0),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            last_acpi_call: Mutex::new(
+                *self
+                    .last_acpi_call
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+            ),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            drop_errors: Mutex::new(Vec::new()),
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            last_writes: Mutex::new(BTreeMap::new()),
+            profile_was_fallback: self.profile_was_fallback,
+        }
+    }
+
+    /// Get the backend which `acpi_call` commands are issued through, which is the
+    /// [`KernelModule`] unless [`Self::backend`](field@Self::backend) is set.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn backend(&self) -> &dyn Backend {
+        self.backend.as_deref().unwrap_or(&KernelModule)
+    }
+
+    /// Get where power supply readings come from, which is [sysfs](power_supply::Sysfs) unless
+    /// [`Self::power_supply`](field@Self::power_supply) is set.
+    pub fn power_supply(&self) -> &dyn PowerSupply {
+        static SYSFS: OnceLock<power_supply::Sysfs> = OnceLock::new();
+
+        match &self.power_supply {
+            Some(power_supply) => &**power_supply,
+            None => SYSFS.get_or_init(power_supply::Sysfs::default),
+        }
+    }
+
+    /// Get where the CPU package temperature comes from, which is [sysfs](thermal::Sysfs) unless
+    /// [`Self::thermal`](field@Self::thermal) is set.
+    pub fn thermal(&self) -> &dyn Thermal {
+        static SYSFS: OnceLock<thermal::Sysfs> = OnceLock::new();
+
+        match &self.thermal {
+            Some(thermal) => &**thermal,
+            None => SYSFS.get_or_init(thermal::Sysfs::default),
+        }
+    }
+
+    /// Get the clock which is used for anything time dependent, which is the [`SystemClock`]
+    /// unless [`Self::clock`](field@Self::clock) is set.
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_deref().unwrap_or(&SystemClock)
+    }
+
+    /// Check whether the profile is the [fallback profile](ContextBuilder::fallback_profile),
+    /// because no profile matched this laptop. If so, the profile might not fully fit the
+    /// hardware.
+    pub fn profile_was_fallback(&self) -> bool {
+        self.profile_was_fallback
+    }
+
+    /// Take a snapshot of the state of the device. See [`DeviceState::read`].
+    pub fn snapshot(&self) -> DeviceState {
+        DeviceState::read(self)
+    }
+
+    /// Take a snapshot of the state of the device, reading each feature on its own thread. See
+    /// [`DeviceState::read_parallel`].
+    pub fn snapshot_parallel(&self) -> DeviceState
+    where
+        D: Sync,
+        DD: Sync,
+    {
+        DeviceState::read_parallel(self)
+    }
+
+    /// Read the versions of the firmware from [`firmware::DMI_PATH`]. See [`FirmwareInfo::read`].
+    pub fn firmware_info(&self) -> io::Result<FirmwareInfo> {
+        FirmwareInfo::read(firmware::DMI_PATH)
+    }
+
+    /// Apply every setting of the preset, skipping settings whose feature isn't compiled in. See
+    /// [`Preset::apply`].
+    pub fn apply_preset(&self, preset: &Preset) -> PresetReport {
+        preset.apply(self)
+    }
+
+    /// Apply the settings of the power profile. See [`PowerProfile::preset`].
+    pub fn set_power_profile(&self, profile: PowerProfile) -> PresetReport {
+        self.apply_preset(&profile.preset())
+    }
+
+    /// Apply the desired state, run the closure, then restore every setting to what it was before.
+    /// The state is restored even if the closure panics, although errors can only be reported if
+    /// it returns.
+    ///
+    /// Settings which couldn't be read beforehand aren't restored.
+    ///
+    /// # Errors
+    /// If any setting of the desired state couldn't be applied, an [`Error::ApplyState`] is
+    /// returned without running the closure, after restoring the state; if that fails too, its
+    /// report is included as well. If restoring the state after running the closure fails, an
+    /// [`Error::RestoreState`] is returned.
+    pub fn with_state<F, R>(&self, desired: DesiredState, f: F) -> Result<R>
+    where
+        F: FnOnce() -> R,
+    {
+        let mut guard = RestoreGuard {
+            context: self,
+            original: Some(Preset::from(&self.snapshot())),
+        };
+        let report = desired.apply(self);
+
+        if !report.succeeded() {
+            let restore = match guard.restore() {
+                Err(Error::RestoreState { report }) => Some(report),
+                _ => None,
+            };
+
+            return Err(Error::ApplyState { report, restore });
+        }
+
+        let value = f();
+        guard.restore()?;
+
+        Ok(value)
+    }
+
+    /// Get the power profile which is the closest to the current state of the device. See
+    /// [`PowerProfile::closest`].
+    pub fn current_power_profile(&self) -> Option<PowerProfile> {
+        PowerProfile::closest(&self.snapshot())
+    }
+
+    /// Get recommendations for keeping the battery healthy, based on the
+    /// [power supplies](Self::power_supply) and the battery modes. The rules are:
+    ///
+    ///  1. If the laptop is plugged in, the battery is at least [`advice::FULL_CAPACITY`] and
+    ///     battery conservation is disabled, [`Advice::EnableConservation`].
+    ///  2. If rapid charge is enabled and the battery is at least [`advice::HOT_TEMPERATURE`],
+    ///     [`Advice::DisableRapidCharge`].
+    ///
+    /// The advice is returned in the order above. If no battery is found, the first rule is
+    /// skipped. This only ever reads from the device.
+    #[cfg(feature = "battery_conservation")]
+    pub fn battery_advice(&self) -> Result<Vec<Advice>> {
+        let power_supply_error = |error| Error::PowerSupply { error };
+        let controllers = self.controllers();
+        let mut advice = Vec::new();
+
+        if self
+            .power_supply()
+            .ac_online()
+            .map_err(power_supply_error)?
+        {
+            let capacities = self
+                .power_supply()
+                .battery_capacities()
+                .map_err(power_supply_error)?;
+
+            if let Some(capacity) = power_supply::aggregate_capacity(&capacities)
+                .filter(|&capacity| capacity >= advice::FULL_CAPACITY)
+            {
+                if !controllers.battery_conservation().get()? {
+                    advice.push(Advice::EnableConservation { capacity });
+                }
+            }
+        }
+
+        #[cfg(feature = "rapid_charge")]
+        if controllers.rapid_charge().get()? {
+            let temperature = self
+                .power_supply()
+                .battery_temperature()
+                .map_err(power_supply_error)?;
+
+            if temperature >= advice::HOT_TEMPERATURE {
+                advice.push(Advice::DisableRapidCharge { temperature });
+            }
+        }
+
+        Ok(advice)
+    }
+
+    /// Run a read-only self test of everything this crate needs. See [`SelfTestReport::run`].
+    pub fn self_test(&self) -> SelfTestReport {
+        SelfTestReport::run(self)
+    }
+
+    /// Probe the get methods of every bundled profile through the backend of this context, which
+    /// shows which profiles fit this machine even if detecting the profile failed. This only ever
+    /// reads from the device. See [`ValidationReport`].
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn try_each_profile(&self) -> Vec<(Profile, ValidationReport)> {
+        Profile::SEARCH_PATH
+            .iter()
+            .map(|profile| (profile.clone(), ValidationReport::run(self, profile)))
+            .collect()
+    }
+
+    /// Reset the device to its default state, which is:
+    ///
+    ///  1. The system performance mode set to [`SystemPerformanceMode::IntelligentCooling`].
+    ///  2. Battery conservation disabled.
+    ///  3. Rapid charge disabled.
+    ///
+    /// These are applied in the order above. This is best-effort; every step is attempted even if
+    /// a previous one failed, and features which aren't compiled in are skipped.
+    ///
+    /// # Errors
+    /// If any of the steps failed, an [`Error::Reset`] containing every error is returned.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn reset_to_defaults(&self) -> Result<()> {
+        let controllers = self.controllers();
+        let mut errors = Vec::new();
+
+        #[cfg(feature = "system_performance")]
+        if let Err(error) = controllers
+            .system_performance()
+            .set(SystemPerformanceMode::IntelligentCooling)
+        {
+            errors.push(error.into())
+        }
+
+        #[cfg(feature = "battery_conservation")]
+        if let Err(error) = controllers.battery_conservation().disable() {
+            errors.push(error.into())
+        }
+
+        #[cfg(feature = "rapid_charge")]
+        if let Err(error) = controllers.rapid_charge().disable() {
+            errors.push(error.into())
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Reset { errors })
+        }
+    }
+
+    /// Issue an `acpi_call` command through the backend of this context.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub(crate) fn acpi_call(
+        &self,
+        method: &str,
+        parameters: impl IntoIterator<Item = u32>,
+    ) -> acpi_call::Result<Output> {
+        let write = self.coalesce_writes && self.is_set_command(method);
+        let (method, parameters) = self.rewrite_call(method, parameters);
+        acpi_call::check_parameters(&method, &parameters, self.profile.parameter_width)?;
+
+        if self.coalesce_writes {
+            let mut last_writes = self
+                .last_writes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            if !write {
+                last_writes.clear();
+            } else if let Some((_, output)) = last_writes
+                .get(&*method)
+                .filter(|(last_parameters, _)| *last_parameters == parameters)
+            {
+                return Ok(output.clone());
+            }
+        }
+
+        let _last_call = self.throttle();
+        let result = acpi_call::acpi_call(
+            self.backend(),
+            &method,
+            parameters.iter().copied(),
+            self.profile.output_radix,
+        );
+
+        if write {
+            let mut last_writes = self
+                .last_writes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            match &result {
+                Ok(output) => {
+                    last_writes.insert(method.to_string(), (parameters.clone(), output.clone()));
+                }
+                Err(_) => {
+                    last_writes.remove(&*method);
+                }
+            }
+        }
+
+        if let Some(acpi_tap) = &self.acpi_tap {
+            acpi_tap(&AcpiEvent {
+                command: &method,
+                params: &parameters,
+                result: &result,
+            })
+        }
+
+        result
+    }
+
+    /// Issue an `acpi_call` command through the backend of this context, expecting a valid output.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub(crate) fn acpi_call_expect_valid(
+        &self,
+        method: &str,
+        parameters: impl IntoIterator<Item = u32>,
+    ) -> acpi_call::Result<u32> {
+        match self.acpi_call(method, parameters)? {
+            Output::Valid(value) => Ok(value),
+            Output::Invalid(value) => Err(acpi_call::Error::UnknownValue { value }),
+        }
+    }
+
+    /// Issue an `acpi_call` command through the backend of this context, falling back to the
+    /// specified default if the output isn't valid.
+    ///
+    /// This is useful for status reads on quirky firmware, where `0` (disabled) is a safe default.
+    /// Actual errors, such as [`acpi_call::Error::MethodNotFound`], are still returned.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn acpi_call_expect_valid_or(
+        &self,
+        method: &str,
+        parameters: impl IntoIterator<Item = u32>,
+        default: u32,
+    ) -> acpi_call::Result<u32> {
+        match self.acpi_call(method, parameters)? {
+            Output::Valid(value) => Ok(value),
+            Output::Invalid(_) => Ok(default),
+        }
+    }
+
+    /// Check whether the method is one of the set commands of the profile, which are the only
+    /// methods which write to the device.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    fn is_set_command(&self, method: &str) -> bool {
+        let system_performance = self
+            .profile
+            .system_performance
+            .iter()
+            .map(|system_performance| &system_performance.commands.set);
+        let battery = self.profile.battery.iter().flat_map(|battery| {
+            iter::once(&battery.set_command).chain(
+                battery
+                    .conservation_threshold
+                    .iter()
+                    .map(|threshold| &threshold.set_command),
+            )
+        });
+
+        system_performance
+            .chain(battery)
+            .any(|command| command.as_ref() == method)
+    }
+
+    /// Forget the writes which were [coalesced](Self::coalesce_writes), because the device was
+    /// accessed outside of `acpi_call`.
+    #[cfg(feature = "battery_conservation")]
+    fn forget_writes(&self) {
+        self.last_writes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Get the [sysfs battery backend](Self::sysfs_battery_backend) to read from or write to it.
+    /// Coalesced writes can't see through it, so they are forgotten if it is set.
+    #[cfg(feature = "battery_conservation")]
+    pub(crate) fn sysfs_battery(&self) -> Option<&SysfsBatteryBackend> {
+        let sysfs = self.sysfs_battery_backend.as_ref();
+
+        if sysfs.is_some() {
+            self.forget_writes();
+        }
+
+        sysfs
+    }
+
+    /// Rewrite a call into what is actually issued: the method into the
+    /// [path style](Self::path_style), then both the method and the parameters through the
+    /// [command rewriter](Self::command_rewriter) if one is set.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub(crate) fn rewrite_call<'a>(
+        &self,
+        method: &'a str,
+        parameters: impl IntoIterator<Item = u32>,
+    ) -> (Cow<'a, str>, Vec<u32>) {
+        let method = self.styled_method(method);
+
+        match &self.command_rewriter {
+            Some(command_rewriter) => {
+                let parameters = parameters.into_iter().collect::<Vec<_>>();
+                let (method, parameters) = command_rewriter(&method, &parameters);
+                (Cow::Owned(method), parameters)
+            }
+            None => (method, parameters.into_iter().collect()),
+        }
+    }
+
+    /// Detect the [path style](Self::path_style) by issuing the probe methods through this
+    /// context, keeping the default if that fails. The style of the [`KernelModule`] is cached
+    /// along with the profile, see [`profile::find_cached_path_style`].
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    fn with_detected_path_style(mut self) -> Self {
+        let detect = || acpi_call::detect_path_style_through(|method| self.acpi_call(method, []));
+        let detected = match self.backend {
+            Some(_) => detect(),
+            None => profile::find_cached_path_style(detect),
+        };
+
+        self.path_style = detected.unwrap_or_default();
+        self
+    }
+
+    /// Rewrite the method into the [path style](Self::path_style), unless it is the default.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    fn styled_method<'a>(&self, method: &'a str) -> Cow<'a, str> {
+        match self.path_style {
+            PathStyle::Short => Cow::Borrowed(method),
+            style => Cow::Owned(acpi_call::normalize_method(method, style)),
+        }
+    }
+
+    /// If [`Self::verify_writes`] is set, check that the state read back after issuing a command
+    /// is what was expected.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub(crate) fn verify_write<T: PartialEq, E: From<acpi_call::Error>>(
+        &self,
+        method: &str,
+        parameters: impl IntoIterator<Item = u32>,
+        expected: T,
+        read_back: impl FnOnce() -> std::result::Result<T, E>,
+    ) -> std::result::Result<(), E> {
+        if self.verify_writes && read_back()? != expected {
+            return Err(acpi_call::Error::WriteNotApplied {
+                command: acpi_call::command(method, parameters),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Get the battery section of the profile, failing with an
+    /// [`acpi_call::Error::FeatureUnavailableInProfile`] if it is missing.
+    #[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+    pub(crate) fn battery_profile(&self) -> acpi_call::Result<&Battery> {
+        self.profile
+            .battery
+            .as_ref()
+            .ok_or(acpi_call::Error::FeatureUnavailableInProfile { section: "battery" })
+    }
+
+    /// Get the system performance section of the profile, failing with an
+    /// [`acpi_call::Error::FeatureUnavailableInProfile`] if it is missing.
+    #[cfg(feature = "system_performance")]
+    pub(crate) fn system_performance_profile(&self) -> acpi_call::Result<&SystemPerformance> {
+        self.profile.system_performance.as_ref().ok_or(
+            acpi_call::Error::FeatureUnavailableInProfile {
+                section: "system_performance",
+            },
+        )
+    }
+
+    /// Call [`Self::on_conflict`], if set, with the battery mode which was disabled.
+    #[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+    pub(crate) fn notify_conflict(&self, disabled: BatteryMode) {
+        if let Some(on_conflict) = &self.on_conflict {
+            on_conflict(disabled)
+        }
+    }
+
+    /// Take every guard drop error which was recorded since the last call, oldest first. Nothing is
+    /// recorded unless [`Self::capture_drop_errors`] is set.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn drained_drop_errors(&self) -> Vec<GuardDropError> {
+        mem::take(
+            &mut *self
+                .drop_errors
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+
+    /// Pass through the result of a guard restoring the state when it was dropped. If
+    /// [`Self::capture_drop_errors`] is set and the guard failed to restore the state, the error is
+    /// recorded along with the method it was restoring it with.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub(crate) fn record_drop_error<T, E: ToString>(
+        &self,
+        method: &str,
+        result: std::result::Result<T, E>,
+    ) -> std::result::Result<(), E> {
+        if let (true, Err(error)) = (self.capture_drop_errors, &result) {
+            self.drop_errors
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(GuardDropError {
+                    method: method.to_string(),
+                    error: error.to_string(),
+                })
+        }
+
+        result.map(|_| ())
+    }
+
+    /// Check that `acpi_call` commands could be issued through the [backend](Self::backend) at all,
+    /// so that an application could fail fast with a clear error at startup instead of on the
+    /// first operation. See [`acpi_call::Backend::preflight`].
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn preflight(&self) -> std::result::Result<(), PreflightError> {
+        self.backend().preflight()
+    }
+
+    /// Fail with an [`acpi_call::Error::ReadOnly`] if this context is [read only](Self::read_only).
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub(crate) fn ensure_writable(&self) -> acpi_call::Result<()> {
+        if self.read_only {
+            return Err(acpi_call::Error::ReadOnly);
+        }
+
+        Ok(())
+    }
+
+    /// Block until [`Self::acpi_call_min_interval`] has passed since the last `acpi_call`
+    /// operation, then record the current one.
+    ///
+    /// The returned guard has to be held while the operation is issued, so that operations issued
+    /// from multiple threads don't interleave.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    fn throttle(&self) -> MutexGuard<'_, Option<Instant>> {
+        let mut last_call = self
+            .last_acpi_call
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let (Some(min_interval), Some(last_call)) = (self.acpi_call_min_interval, *last_call) {
+            let elapsed = self.clock().now().saturating_duration_since(last_call);
+
+            if elapsed < min_interval {
+                self.clock().sleep(min_interval - elapsed);
+            }
+        }
+
+        *last_call = Some(self.clock().now());
+        last_call
+    }
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use crate::acpi_call::{self, PathStyle};
+    use crate::battery::BatteryMode;
+    use crate::battery_conservation::SysfsBatteryBackend;
+    use crate::clock::Clock;
+    use crate::context::{ContextBuilder, Error};
+    use crate::mock::{MockBackend, MockClock};
+    use crate::preset::PresetPerformance;
+    use crate::state::DesiredState;
+    use crate::system_performance::{self, SystemPerformanceMode};
+    use crate::{profile, Context, Profile};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use try_drop::{GlobalFallbackTryDropStrategyHandler, TryDropStrategy};
+
+    /// Counts the errors it handles. Clones share the same count.
+    #[derive(Clone, Default)]
+    struct CountingStrategy(Arc<Mutex<usize>>);
+
+    impl TryDropStrategy for CountingStrategy {
+        fn handle_error(&self, _error: try_drop::Error) {
+            *self.0.lock().unwrap() += 1
+        }
+    }
+
+    #[test]
+    fn test_reset_to_defaults() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_system_performance_mode(SystemPerformanceMode::ExtremePerformance);
+        backend.set_battery_conservation(true);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context
+            .reset_to_defaults()
+            .expect("failed to reset to defaults");
+
+        assert_eq!(
+            backend.calls(),
+            [
+                r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC 1028097"#,
+                r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 5"#,
+                r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 8"#,
+            ]
+        );
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+        assert!(!backend.battery_conservation());
+        assert!(!backend.rapid_charge());
+
+        backend.set_system_performance_mode(SystemPerformanceMode::ExtremePerformance);
+        backend.remove_method(r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC"#);
+        let error = context
+            .reset_to_defaults()
+            .expect_err("reset succeeded without a set command");
+        assert!(matches!(
+            error,
+            Error::Reset { ref errors } if matches!(
+                errors[..],
+                [Error::SystemPerformance {
+                    error: system_performance::Error::AcpiCall {
+                        error: acpi_call::Error::MethodNotFound { .. }
+                    }
+                }]
+            )
+        ));
+    }
+
+    #[test]
+    fn test_acpi_call_min_interval() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let clock = MockClock::default();
+        let started = clock.now();
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.clock = Some(Arc::new(clock.clone()));
+        context.acpi_call_min_interval = Some(Duration::from_millis(500));
+        let system_performance = context.controllers().system_performance();
+
+        // every get issues two calls, and only the very first call doesn't have to wait
+        system_performance
+            .get()
+            .expect("failed to get system performance mode");
+        system_performance
+            .get()
+            .expect("failed to get system performance mode");
+        assert_eq!(backend.calls().len(), 4);
+        assert_eq!(clock.now() - started, Duration::from_millis(1500));
+
+        // enough time has passed since the last call, so the next one goes through immediately
+        clock.advance(Duration::from_secs(2));
+        system_performance
+            .get()
+            .expect("failed to get system performance mode");
+        assert_eq!(clock.now() - started, Duration::from_millis(4000));
+    }
+
+    #[test]
+    fn test_verify_writes() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        context
+            .controllers()
+            .battery_conservation()
+            .disable()
+            .expect("failed to disable battery conservation");
+        assert_eq!(backend.calls(), [r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 5"#]);
+
+        context.verify_writes = true;
+        context
+            .controllers()
+            .battery_conservation()
+            .disable()
+            .expect("failed to disable battery conservation");
+        assert_eq!(
+            backend.calls()[1..],
+            [
+                r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 5"#,
+                r#"\_SB.PCI0.LPC0.EC0.BTSM"#,
+            ]
+        );
+
+        // the embedded controller ignores writes to the system performance mode
+        let commands = &context
+            .system_performance_profile()
+            .expect("profile has no system performance section")
+            .commands;
+        backend.respond(&commands.set, "0x0");
+        let error = context
+            .controllers()
+            .system_performance()
+            .set(SystemPerformanceMode::ExtremePerformance)
+            .expect_err("unapplied write was verified");
+        assert!(matches!(
+            error,
+            system_performance::Error::AcpiCall {
+                error: acpi_call::Error::WriteNotApplied { command }
+            } if command == r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC 1224705"#
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_parallel() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_system_performance_mode(SystemPerformanceMode::BatterySaving);
+        backend.set_battery_conservation(true);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let state = context.snapshot_parallel();
+
+        assert_eq!(
+            state.system_performance,
+            Ok(SystemPerformanceMode::BatterySaving)
+        );
+        assert_eq!(state.battery_conservation, Ok(true));
+        assert_eq!(state.rapid_charge, Ok(false));
+        assert_eq!(state, context.snapshot());
+    }
+
+    #[test]
+    fn test_path_style() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.path_style = PathStyle::Padded;
+        let _ = context.controllers().rapid_charge().get();
+
+        assert_eq!(backend.calls(), [r#"\_SB_.PCI0.LPC0.EC0_.QCHO"#]);
+    }
+
+    #[test]
+    fn test_acpi_tap() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.acpi_tap = Some(Arc::new({
+            let events = Arc::clone(&events);
+            move |event| {
+                events.lock().unwrap().push((
+                    event.command.to_string(),
+                    event.params.to_vec(),
+                    event.result.is_ok(),
+                ))
+            }
+        }));
+        let controllers = context.controllers();
+
+        controllers
+            .rapid_charge()
+            .enable()
+            .ignore()
+            .now()
+            .expect("failed to enable rapid charge");
+        assert!(controllers
+            .rapid_charge()
+            .get()
+            .expect("failed to get rapid charge"));
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (
+                    r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#.to_string(),
+                    vec![0x07],
+                    true
+                ),
+                (r#"\_SB.PCI0.LPC0.EC0.QCHO"#.to_string(), vec![], true),
+            ]
+        );
+    }
+
+    fn with_state(backend: &MockBackend, f: impl FnOnce()) -> Result<(), Error> {
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let desired = DesiredState {
+            system_performance: Some(PresetPerformance::ExtremePerformance),
+            rapid_charge: Some(true),
+            ..Default::default()
+        };
+
+        context.with_state(desired, || {
+            assert_eq!(
+                backend.system_performance_mode(),
+                SystemPerformanceMode::ExtremePerformance
+            );
+            assert!(backend.rapid_charge());
+            assert!(!backend.battery_conservation());
+            f()
+        })
+    }
+
+    #[test]
+    fn test_with_state() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_system_performance_mode(SystemPerformanceMode::BatterySaving);
+        backend.set_battery_conservation(true);
+
+        // changes made by the closure are undone as well
+        with_state(&backend, || {
+            backend.set_system_performance_mode(SystemPerformanceMode::IntelligentCooling)
+        })
+        .expect("failed to run with state");
+
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::BatterySaving
+        );
+        assert!(backend.battery_conservation());
+        assert!(!backend.rapid_charge());
+    }
+
+    #[test]
+    fn test_with_state_panic() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_system_performance_mode(SystemPerformanceMode::BatterySaving);
+        backend.set_battery_conservation(true);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            with_state(&backend, || panic!("closure panicked"))
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::BatterySaving
+        );
+        assert!(backend.battery_conservation());
+        assert!(!backend.rapid_charge());
+    }
+
+    #[test]
+    fn test_with_state_apply_failed() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+        backend.respond(
+            &Profile::IDEAPAD_AMD.battery.unwrap().set_command,
+            "Error: AE_NOT_FOUND",
+        );
+
+        let mut ran = false;
+        let result = with_state(&backend, || ran = true);
+
+        assert!(!ran);
+        match result {
+            Err(Error::ApplyState {
+                report,
+                restore: Some(restore),
+            }) => {
+                assert!(!report.succeeded());
+                assert!(!restore.succeeded());
+            }
+            _ => panic!("expected both the apply and restore reports"),
+        }
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+    }
+
+    #[test]
+    fn test_with_state_cooldown() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.clock = Some(Arc::new(MockClock::default()));
+        context.system_performance_cooldown = Some(Duration::from_secs(60));
+        let desired = DesiredState {
+            system_performance: Some(PresetPerformance::ExtremePerformance),
+            ..Default::default()
+        };
+
+        context
+            .with_state(desired, || {})
+            .expect("failed to restore the state within the cooldown");
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+    }
+
+    #[test]
+    fn test_command_rewriter() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.command_rewriter = Some(Arc::new(|method, parameters| {
+            (method.replace("SBMC", "SBMD"), parameters.to_vec())
+        }));
+
+        let _ = context.controllers().rapid_charge().disable();
+        assert_eq!(backend.calls(), [r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMD 8"#]);
+    }
+
+    #[test]
+    fn test_acpi_call_expect_valid_or() {
+        let method = r#"\_SB.PCI0.LPC0.EC0.VPC0.GBMD"#;
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        backend.respond(method, "0x10");
+        assert_eq!(
+            context.acpi_call_expect_valid_or(method, [], 0).unwrap(),
+            0x10
+        );
+
+        backend.respond(method, "garbage");
+        assert_eq!(context.acpi_call_expect_valid_or(method, [], 0).unwrap(), 0);
+        assert!(matches!(
+            context.acpi_call_expect_valid(method, []),
+            Err(acpi_call::Error::UnknownValue { .. })
+        ));
+
+        backend.remove_method(method);
+        assert!(matches!(
+            context.acpi_call_expect_valid_or(method, [], 0),
+            Err(acpi_call::Error::MethodNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_coalesce_writes() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.coalesce_writes = true;
+        let mut system_performance = context.controllers().system_performance();
+        let set = |system_performance: &mut system_performance::SystemPerformanceController| {
+            system_performance
+                .set(SystemPerformanceMode::IntelligentCooling)
+                .expect("failed to set system performance mode")
+        };
+        let writes = || {
+            backend
+                .calls()
+                .iter()
+                .filter(|call| call.contains(' '))
+                .count()
+        };
+
+        set(&mut system_performance);
+        set(&mut system_performance);
+        assert_eq!(writes(), 1);
+
+        system_performance
+            .get()
+            .expect("failed to get system performance mode");
+        set(&mut system_performance);
+        assert_eq!(writes(), 2);
+
+        // battery conservation is read through sysfs, which acpi_call can't see
+        let root = tempfile::tempdir().expect("failed to create fixture directory");
+        let path = root.path().join("conservation_mode");
+        std::fs::write(&path, "0").expect("failed to write fixture");
+        context.sysfs_battery_backend = Some(SysfsBatteryBackend::new(&path));
+        let mut system_performance = context.controllers().system_performance();
+
+        set(&mut system_performance);
+        assert_eq!(writes(), 2);
+        context
+            .controllers()
+            .battery_conservation()
+            .get()
+            .expect("failed to get battery conservation");
+        set(&mut system_performance);
+        assert_eq!(writes(), 3);
+    }
+
+    #[test]
+    fn test_output_radix() {
+        let method = r#"\_SB.PCI0.LPC0.EC0.VPC0.GBMD"#;
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.respond(method, "AB");
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        assert!(matches!(
+            context.acpi_call_expect_valid(method, []),
+            Err(acpi_call::Error::UnknownValue { value }) if value == "AB"
+        ));
+
+        context.profile = Profile::IDEAPAD_AMD.with_output_radix(profile::OutputRadix::Hex);
+        assert_eq!(context.acpi_call_expect_valid(method, []).unwrap(), 0xAB);
+    }
+
+    #[test]
+    fn test_static_context() {
+        static CONTEXT: Context = Context::new(Profile::IDEAPAD_AMD);
+
+        assert_eq!(CONTEXT.profile, Profile::IDEAPAD_AMD);
+        assert!(CONTEXT.backend.is_none());
+        assert!(CONTEXT.clock().now() <= Instant::now());
+    }
+
+    #[test]
+    fn test_preflight() {
+        let root = tempfile::tempdir().expect("failed to create fixture directory");
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(MockBackend::new(Profile::IDEAPAD_AMD)));
+        assert!(context.preflight().is_ok());
+
+        context.backend = Some(Arc::new(acpi_call::PersistentKernelModule::with_path(
+            root.path().join("call"),
+        )));
+        assert!(matches!(
+            context.preflight(),
+            Err(acpi_call::PreflightError::KernelModuleNotLoaded { path })
+                if path == root.path().join("call")
+        ));
+    }
+
+    #[test]
+    fn test_read_only() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.read_only = true;
+        let controllers = context.controllers();
+
+        assert!(matches!(
+            controllers
+                .system_performance()
+                .set(SystemPerformanceMode::ExtremePerformance),
+            Err(system_performance::Error::AcpiCall {
+                error: acpi_call::Error::ReadOnly
+            })
+        ));
+        assert!(matches!(
+            controllers.rapid_charge().disable(),
+            Err(acpi_call::Error::ReadOnly)
+        ));
+        assert!(backend.calls().is_empty());
+
+        assert_eq!(
+            controllers
+                .system_performance()
+                .get()
+                .expect("failed to get system performance mode"),
+            SystemPerformanceMode::IntelligentCooling
+        );
+    }
+
+    #[test]
+    fn test_parameter_width() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD.with_parameter_width(8));
+        context.backend = Some(Arc::new(backend.clone()));
+
+        context
+            .controllers()
+            .battery_conservation()
+            .disable()
+            .expect("in range parameter was rejected");
+        assert!(matches!(
+            context
+                .controllers()
+                .system_performance()
+                .set(SystemPerformanceMode::BatterySaving),
+            Err(system_performance::Error::AcpiCall {
+                error: acpi_call::Error::ParameterOutOfRange {
+                    parameter: 0x0013B001,
+                    width: 8,
+                    ..
+                }
+            })
+        ));
+        assert_eq!(backend.calls(), [r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 5"#]);
+    }
+
+    #[test]
+    fn test_builder_backend() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let context = Context::builder(Profile::IDEAPAD_AMD)
+            .strategies(
+                CountingStrategy::default(),
+                GlobalFallbackTryDropStrategyHandler,
+            )
+            .backend(backend.clone())
+            .build();
+
+        context
+            .controllers()
+            .rapid_charge()
+            .disable()
+            .expect("failed to disable rapid charge");
+        assert_eq!(backend.calls(), [r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 8"#]);
+    }
+
+    #[test]
+    fn test_builder_profile() {
+        let fallback = Profile {
+            name: "FALLBACK".into(),
+            ..Profile::IDEAPAD_AMD
+        };
+
+        for (detected, fallback, expected) in [
+            (
+                Some(Profile::IDEAPAD_AMD),
+                Some(fallback.clone()),
+                Some((Profile::IDEAPAD_AMD, false)),
+            ),
+            (None, Some(fallback.clone()), Some((fallback.clone(), true))),
+            // without a fallback, the error is returned as is
+            (None, None, None),
+        ] {
+            let builder = match fallback {
+                Some(fallback) => ContextBuilder::detect().fallback_profile(fallback),
+                None => ContextBuilder::detect(),
+            };
+            let result = builder.try_build_with(|| {
+                detected.ok_or_else(|| profile::Error::NoValidProfileInSearchPath {
+                    detected_product: "81ZZ".to_string(),
+                    considered: vec![Profile::IDEAPAD_AMD.name.to_string()],
+                })
+            });
+
+            match expected {
+                Some((profile, was_fallback)) => {
+                    let context = result.expect("failed to build context");
+                    assert_eq!(context.profile, profile);
+                    assert_eq!(context.profile_was_fallback(), was_fallback);
+                }
+                None => assert!(matches!(
+                    result,
+                    Err(profile::Error::NoValidProfileInSearchPath { .. })
+                )),
+            }
+        }
+    }
+
+    #[test]
+    fn test_builder_detected_path_style() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.respond(PathStyle::PADDED_PROBE_METHOD, "0x0");
+        let context = ContextBuilder::detect()
+            .backend(backend.clone())
+            .try_build_with(|| Ok(Profile::IDEAPAD_AMD))
+            .expect("failed to build context");
+        assert_eq!(context.path_style, PathStyle::Padded);
+
+        // a chosen path style isn't detected
+        let calls = backend.calls().len();
+        let context = ContextBuilder::detect()
+            .backend(backend.clone())
+            .path_style(PathStyle::Short)
+            .try_build_with(|| Ok(Profile::IDEAPAD_AMD))
+            .expect("failed to build context");
+        assert_eq!(context.path_style, PathStyle::Short);
+        assert_eq!(backend.calls().len(), calls);
+
+        // neither style is found, so the default is kept
+        let context = ContextBuilder::detect()
+            .backend(MockBackend::new(Profile::IDEAPAD_AMD))
+            .try_build_with(|| Ok(Profile::IDEAPAD_AMD))
+            .expect("failed to build context");
+        assert_eq!(context.path_style, PathStyle::Short);
+
+        // the probes are issued through the context like any other command
+        let probes = Arc::new(Mutex::new(Vec::new()));
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.acpi_tap = Some(Arc::new({
+            let probes = Arc::clone(&probes);
+            move |event| probes.lock().unwrap().push(event.command.to_string())
+        }));
+        let context = context.with_detected_path_style();
+        assert_eq!(context.path_style, PathStyle::Padded);
+        assert_eq!(
+            *probes.lock().unwrap(),
+            [PathStyle::PROBE_METHOD, PathStyle::PADDED_PROBE_METHOD]
+        );
+    }
+
+    #[test]
+    fn test_with_strategy_override() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        let strategy = CountingStrategy::default();
+        let overridden =
+            context.with_strategy_override(strategy.clone(), GlobalFallbackTryDropStrategyHandler);
+        assert_eq!(overridden.profile, context.profile);
+
+        // restoring the mode when the guard is dropped fails because the set command is gone,
+        // which has to be handled by the overridden strategy instead of the global one
+        let mut controller = overridden.controllers().system_performance();
+        let guard = controller
+            .guard_for_this_scope(SystemPerformanceMode::ExtremePerformance)
+            .expect("failed to set system performance mode");
+        backend.remove_method(r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC"#);
+        drop(guard);
+
+        assert_eq!(*strategy.0.lock().unwrap(), 1);
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::ExtremePerformance
+        );
+    }
+
+    #[test]
+    fn test_capture_drop_errors() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let strategy = CountingStrategy::default();
+        let mut context = Context::builder(Profile::IDEAPAD_AMD)
+            .strategies(strategy.clone(), GlobalFallbackTryDropStrategyHandler)
+            .backend(backend.clone())
+            .build();
+        context.capture_drop_errors = true;
+
+        // restoring the mode fails because the set command is gone
+        let mut controller = context.controllers().system_performance();
+        let guard = controller
+            .guard_for_this_scope(SystemPerformanceMode::ExtremePerformance)
+            .expect("failed to set system performance mode");
+        backend.remove_method(r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC"#);
+        drop(guard);
+
+        assert_eq!(*strategy.0.lock().unwrap(), 1);
+
+        let errors = context.drained_drop_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].method, "SystemPerformanceController::set");
+        assert!(errors[0].error.contains("DYTC"));
+        assert!(context.drained_drop_errors().is_empty());
+    }
+
+    #[test]
+    fn test_capture_drop_errors_after_panic() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.capture_drop_errors = true;
+        let desired = DesiredState {
+            system_performance: Some(PresetPerformance::ExtremePerformance),
+            ..Default::default()
+        };
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            context.with_state(desired, || {
+                backend.remove_method(r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC"#);
+                panic!("closure panicked")
+            })
+        }));
+
+        assert!(result.is_err());
+        let errors = context.drained_drop_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].method, "Context::with_state");
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::ExtremePerformance
+        );
+    }
+
+    #[test]
+    fn test_reconcile_battery_both_enabled() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+        backend.set_rapid_charge(true);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        assert!(context
+            .controllers()
+            .reconcile_battery(BatteryMode::Conservation)
+            .expect("failed to reconcile battery"));
+        assert!(backend.battery_conservation());
+        assert!(!backend.rapid_charge());
+    }
+
+    #[test]
+    fn test_reconcile_battery_consistent() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_rapid_charge(true);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        assert!(!context
+            .controllers()
+            .reconcile_battery(BatteryMode::Conservation)
+            .expect("failed to reconcile battery"));
+        assert!(backend.rapid_charge());
+        assert!(backend.calls().iter().all(|call| !call.contains("SBMC")));
+    }
+
+    fn swap(backend: &MockBackend) -> crate::context::Result<BatteryMode> {
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.controllers().swap_battery_mode()
+    }
+
+    #[test]
+    fn test_swap_battery_mode_from_conservation() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+
+        assert_eq!(
+            swap(&backend).expect("failed to swap battery mode"),
+            BatteryMode::RapidCharge
+        );
+        assert!(!backend.battery_conservation());
+        assert!(backend.rapid_charge());
+    }
+
+    #[test]
+    fn test_swap_battery_mode_from_rapid_charge() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_rapid_charge(true);
+
+        assert_eq!(
+            swap(&backend).expect("failed to swap battery mode"),
+            BatteryMode::Conservation
+        );
+        assert!(backend.battery_conservation());
+        assert!(!backend.rapid_charge());
+    }
+
+    #[test]
+    fn test_swap_battery_mode_neither() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+
+        assert!(matches!(swap(&backend), Err(Error::NoBatteryModeEnabled)));
+        assert!(!backend.battery_conservation());
+        assert!(!backend.rapid_charge());
+    }
 }
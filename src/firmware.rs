@@ -0,0 +1,78 @@
+//! Read information about the firmware of this laptop, which helps to tell apart near-identical
+//! models and is useful to include in bug reports.
+
+use std::path::Path;
+use std::{fs, io};
+
+/// The default path where the kernel exposes the DMI (SMBIOS) information.
+pub const DMI_PATH: &str = "/sys/class/dmi/id";
+
+/// Versions of the firmware. Fields which the firmware doesn't report are [`None`].
+#[derive(Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FirmwareInfo {
+    /// The BIOS version, for example `EUCN41WW`.
+    pub bios_version: Option<String>,
+
+    /// The release date of the BIOS, usually in the `MM/DD/YYYY` format.
+    pub bios_date: Option<String>,
+
+    /// The version of the embedded controller firmware, for example `1.41`.
+    pub ec_version: Option<String>,
+}
+
+impl FirmwareInfo {
+    /// Read the firmware information from the DMI directory, usually [`DMI_PATH`].
+    ///
+    /// # Errors
+    /// If the DMI directory itself couldn't be read, the underlying [`io::Error`] is returned.
+    /// Missing or empty attributes aren't errors.
+    pub fn read(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        fs::read_dir(root)?;
+
+        Ok(Self {
+            bios_version: read_attribute(&root.join("bios_version"))?,
+            bios_date: read_attribute(&root.join("bios_date"))?,
+            ec_version: read_attribute(&root.join("ec_firmware_release"))?,
+        })
+    }
+}
+
+fn read_attribute(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value.trim().to_string())),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::firmware::FirmwareInfo;
+    use std::fs;
+
+    #[test]
+    fn test_read() {
+        let root = tempfile::tempdir().expect("failed to create fixture directory");
+        fs::write(root.path().join("bios_version"), "EUCN41WW\n").expect("failed to write");
+        fs::write(root.path().join("bios_date"), "09/27/2021\n").expect("failed to write");
+
+        assert_eq!(
+            FirmwareInfo::read(root.path()).expect("failed to read firmware info"),
+            FirmwareInfo {
+                bios_version: Some("EUCN41WW".into()),
+                bios_date: Some("09/27/2021".into()),
+                ec_version: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_missing_directory() {
+        let root = tempfile::tempdir().expect("failed to create fixture directory");
+
+        assert!(FirmwareInfo::read(root.path().join("missing")).is_err());
+    }
+}
@@ -9,8 +9,15 @@ extern crate serial_test;
 #[macro_use]
 extern crate serde;
 
+#[cfg(feature = "include_profiles")]
+extern crate self as ideapad;
+
 #[macro_use]
-#[cfg(any(feature = "borrowed_cow_vec", feature = "borrowed_cow_array"))]
+#[cfg(any(
+    feature = "borrowed_cow_vec",
+    feature = "borrowed_cow_array",
+    feature = "include_profiles"
+))]
 pub mod macros;
 
 #[cfg(any(
@@ -20,25 +27,48 @@ pub mod macros;
 ))]
 pub mod acpi_call;
 
+#[cfg(feature = "battery_conservation")]
+pub mod advice;
+
 #[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
 pub mod battery;
 
 #[cfg(feature = "battery_conservation")]
 pub mod battery_conservation;
 
+pub mod clock;
 pub mod context;
+pub mod firmware;
+#[cfg(all(test, feature = "core"))]
+mod mock;
+pub mod power_supply;
 pub mod prelude;
+pub mod preset;
 pub mod profile;
 
 #[cfg(feature = "rapid_charge")]
 pub mod rapid_charge;
 
+pub mod self_test;
+pub mod state;
+
 #[cfg(feature = "system_performance")]
 pub mod system_performance;
+pub mod thermal;
+
+#[cfg(feature = "udev")]
+pub mod udev_watcher;
+pub mod watcher;
 
 use crate::context::Context;
 pub use prelude::*;
 
+#[cfg(feature = "include_profiles")]
+pub use macros::include_profiles;
+
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+use std::{fmt, str::FromStr};
+
 #[cfg(not(target_os = "linux"))]
 compile_error!(
     "this crate only works on linux systems due to its dependency on the `acpi_call` kernel module"
@@ -49,6 +79,11 @@ pub fn context() -> profile::Result<Context> {
     Context::try_default()
 }
 
+/// Detect the profile of this laptop, without creating a context. See [`Profile::find`].
+pub fn detect_profile() -> profile::Result<Profile> {
+    Profile::find()
+}
+
 /// Handlers which determine what to do when battery conservation and rapid charge modes conflict.
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -63,3 +98,75 @@ pub enum Handler {
     /// Switch the conflicting mode to disabled then try again.
     Switch,
 }
+
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+impl Handler {
+    /// Every handler.
+    pub const ALL: [Self; 3] = [Self::Ignore, Self::Error, Self::Switch];
+
+    /// Get the name of this handler, which is what [`Display`](fmt::Display) shows and what
+    /// [`FromStr`] accepts.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Ignore => "ignore",
+            Self::Error => "error",
+            Self::Switch => "switch",
+        }
+    }
+}
+
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+impl fmt::Display for Handler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+impl FromStr for Handler {
+    type Err = ParseHandlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "error" => Ok(Self::Error),
+            "switch" => Ok(Self::Switch),
+            _ => Err(ParseHandlerError {
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// The string which was parsed wasn't the name of a [`Handler`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, thiserror::Error)]
+#[error("unknown handler '{value}', expected one of 'ignore', 'error' or 'switch'")]
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+pub struct ParseHandlerError {
+    /// The string which was parsed.
+    pub value: String,
+}
+
+#[cfg(all(test, any(feature = "battery_conservation", feature = "rapid_charge")))]
+mod tests {
+    use crate::{Handler, ParseHandlerError};
+
+    #[test]
+    fn test_handler_round_trip() {
+        for handler in [Handler::Ignore, Handler::Error, Handler::Switch] {
+            assert_eq!(handler.to_string().parse::<Handler>(), Ok(handler));
+        }
+
+        assert_eq!("switch".parse::<Handler>(), Ok(Handler::Switch));
+    }
+
+    #[test]
+    fn test_handler_parse_unknown() {
+        assert_eq!(
+            "disable".parse::<Handler>(),
+            Err(ParseHandlerError {
+                value: "disable".into(),
+            })
+        );
+    }
+}
@@ -23,3 +23,44 @@ macro_rules! borrowed_cow_vec {
         vec![$(::std::borrow::Cow::Borrowed($item)),+]
     }
 }
+
+/// Embed every `*.toml` profile of a directory into the binary at compile time, parsing them into
+/// a [`Vec`] of [`Profile`](crate::profile::Profile)s, sorted by file name.
+///
+/// The directory is relative to the root of the invoking crate, which is where its
+/// `Cargo.toml` is. A missing directory, invalid TOML, or a profile without a `name` or
+/// `expected_product_names` is a compile error. Adding a file to the directory doesn't cause a
+/// rebuild by itself, but changing one of the included files does.
+///
+/// # Panics
+/// If one of the files doesn't follow the profile schema otherwise, this panics with the path of
+/// the file and the reason, the first time the profiles are parsed.
+///
+/// # Examples
+/// ```ignore
+/// for profile in ideapad::include_profiles!("profiles") {
+///     ideapad::Profile::register(profile);
+/// }
+/// ```
+#[cfg(feature = "include_profiles")]
+pub use ideapad_macros::include_profiles;
+
+#[cfg(all(test, feature = "include_profiles"))]
+mod tests {
+    use crate::macros::include_profiles;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_include_profiles() {
+        let profiles = include_profiles!("tests/fixtures/profiles");
+
+        assert_eq!(
+            profiles
+                .iter()
+                .map(|profile| profile.name.clone())
+                .collect::<Vec<_>>(),
+            [Cow::Borrowed("FIRST"), Cow::Borrowed("SECOND")]
+        );
+        assert_eq!(profiles[1].expected_product_names.as_ref(), ["82XY"]);
+    }
+}
@@ -0,0 +1,267 @@
+//! Fakes for tests, such as an `acpi_call` backend which simulates the embedded controller of a
+//! profile.
+#![allow(dead_code)]
+
+use crate::acpi_call::{self, Backend};
+use crate::clock::Clock;
+use crate::power_supply::{BatteryCapacity, PowerSupply};
+use crate::profile::{Battery, Profile, SystemPerformance};
+use crate::system_performance::SystemPerformanceMode;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+struct State {
+    profile: Profile,
+    calls: Vec<String>,
+    responses: HashMap<String, String>,
+    battery_conservation: bool,
+    rapid_charge: bool,
+    rapid_charge_clears_conservation: bool,
+    conservation_threshold: Option<u8>,
+    system_performance_mode: SystemPerformanceMode,
+}
+
+/// Simulates the embedded controller of a profile, recording every command issued to it.
+///
+/// Clones share the same state, so a clone could be given to a context while the original is kept
+/// around to inspect it.
+#[derive(Clone)]
+pub(crate) struct MockBackend(Arc<Mutex<State>>);
+
+impl MockBackend {
+    /// Create a new backend simulating the profile, with battery conservation and rapid charge
+    /// disabled and the system performance mode set to intelligent cooling.
+    pub fn new(profile: Profile) -> Self {
+        Self(Arc::new(Mutex::new(State {
+            profile,
+            calls: Vec::new(),
+            responses: HashMap::new(),
+            battery_conservation: false,
+            rapid_charge: false,
+            rapid_charge_clears_conservation: false,
+            conservation_threshold: None,
+            system_performance_mode: SystemPerformanceMode::IntelligentCooling,
+        })))
+    }
+
+    fn state(&self) -> MutexGuard<State> {
+        self.0.lock().expect("mock backend state poisoned")
+    }
+
+    /// Every command issued so far, in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.state().calls.clone()
+    }
+
+    /// Always respond to the method with the specified raw output.
+    pub fn respond(&self, method: &str, output: &str) {
+        self.state()
+            .responses
+            .insert(method.to_string(), output.to_string());
+    }
+
+    /// Make the method respond as if it wasn't found in the acpi table.
+    pub fn remove_method(&self, method: &str) {
+        self.respond(method, "Error: AE_NOT_FOUND");
+    }
+
+    pub fn battery_conservation(&self) -> bool {
+        self.state().battery_conservation
+    }
+
+    pub fn set_battery_conservation(&self, enabled: bool) {
+        self.state().battery_conservation = enabled
+    }
+
+    pub fn rapid_charge(&self) -> bool {
+        self.state().rapid_charge
+    }
+
+    pub fn set_rapid_charge(&self, enabled: bool) {
+        self.state().rapid_charge = enabled
+    }
+
+    /// Replicate the firmware quirk where enabling rapid charge silently disables battery
+    /// conservation, even if the conflict is ignored.
+    pub fn set_rapid_charge_clears_conservation(&self, enabled: bool) {
+        self.state().rapid_charge_clears_conservation = enabled
+    }
+
+    /// The last battery conservation threshold which was set, if any.
+    pub fn conservation_threshold(&self) -> Option<u8> {
+        self.state().conservation_threshold
+    }
+
+    pub fn system_performance_mode(&self) -> SystemPerformanceMode {
+        self.state().system_performance_mode
+    }
+
+    pub fn set_system_performance_mode(&self, mode: SystemPerformanceMode) {
+        self.state().system_performance_mode = mode
+    }
+}
+
+impl Backend for MockBackend {
+    fn call(&self, command: &str) -> acpi_call::Result<String> {
+        let mut state = self.state();
+        state.calls.push(command.to_string());
+
+        let mut parts = command.split(' ');
+        let method = parts.next().unwrap_or_default();
+        let parameter = parts
+            .next()
+            .and_then(|parameter| parameter.parse::<u32>().ok());
+
+        if let Some(output) = state.responses.get(method) {
+            return Ok(output.clone());
+        }
+
+        let battery = state.profile.battery.clone();
+        let system_performance = state.profile.system_performance.clone();
+        let output = battery
+            .and_then(|battery| state.call_battery(&battery, method, parameter))
+            .or_else(|| {
+                system_performance.and_then(|system_performance| {
+                    state.call_system_performance(&system_performance, method, parameter)
+                })
+            });
+
+        match output {
+            Some(output) => Ok(format!("{:#x}", output)),
+            None => Ok("Error: AE_NOT_FOUND".to_string()),
+        }
+    }
+}
+
+impl State {
+    fn call_battery(
+        &mut self,
+        battery: &Battery,
+        method: &str,
+        parameter: Option<u32>,
+    ) -> Option<u32> {
+        match parameter {
+            Some(parameter) if method == battery.set_command => {
+                if parameter == battery.conservation.parameters.enable {
+                    self.battery_conservation = true
+                } else if parameter == battery.conservation.parameters.disable {
+                    self.battery_conservation = false
+                } else if parameter == battery.rapid_charge.parameters.enable {
+                    self.rapid_charge = true;
+
+                    if self.rapid_charge_clears_conservation {
+                        self.battery_conservation = false
+                    }
+                } else if parameter == battery.rapid_charge.parameters.disable {
+                    self.rapid_charge = false
+                }
+
+                Some(0)
+            }
+            Some(parameter)
+                if battery
+                    .conservation_threshold
+                    .as_ref()
+                    .is_some_and(|threshold| threshold.set_command == method) =>
+            {
+                self.conservation_threshold = u8::try_from(parameter).ok();
+                Some(0)
+            }
+            None if method == battery.conservation.get_command => {
+                Some(self.battery_conservation as u32)
+            }
+            None if method == battery.rapid_charge.get_command => Some(self.rapid_charge as u32),
+            _ => None,
+        }
+    }
+
+    fn call_system_performance(
+        &mut self,
+        system_performance: &SystemPerformance,
+        method: &str,
+        parameter: Option<u32>,
+    ) -> Option<u32> {
+        match parameter {
+            Some(parameter) if method == system_performance.commands.set => {
+                if let Some(mode) = SystemPerformanceMode::from_u32_setter(
+                    &system_performance.parameters,
+                    parameter,
+                ) {
+                    self.system_performance_mode = mode
+                }
+
+                Some(0)
+            }
+            None if method == system_performance.commands.get_spmo_bit => {
+                Some(self.system_performance_mode.spmo(&system_performance.bits))
+            }
+            None if method == system_performance.commands.get_fcmo_bit => {
+                Some(self.system_performance_mode.fcmo(&system_performance.bits))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Fixed power supply readings.
+#[derive(Debug, Clone)]
+pub(crate) struct MockPowerSupply {
+    pub temperature: f32,
+    pub capacities: Vec<BatteryCapacity>,
+    pub ac_online: bool,
+}
+
+impl Default for MockPowerSupply {
+    fn default() -> Self {
+        Self {
+            temperature: 30.0,
+            capacities: vec![BatteryCapacity {
+                name: "BAT0".into(),
+                percent: 50,
+            }],
+            ac_online: true,
+        }
+    }
+}
+
+impl PowerSupply for MockPowerSupply {
+    fn battery_temperature(&self) -> io::Result<f32> {
+        Ok(self.temperature)
+    }
+
+    fn battery_capacities(&self) -> io::Result<Vec<BatteryCapacity>> {
+        Ok(self.capacities.clone())
+    }
+
+    fn ac_online(&self) -> io::Result<bool> {
+        Ok(self.ac_online)
+    }
+}
+
+/// A clock which only moves forward when told to. Clones share the same time.
+#[derive(Debug, Clone)]
+pub(crate) struct MockClock(Arc<Mutex<Instant>>);
+
+impl MockClock {
+    pub fn advance(&self, duration: Duration) {
+        *self.0.lock().expect("mock clock poisoned") += duration
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().expect("mock clock poisoned")
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration)
+    }
+}
@@ -0,0 +1,220 @@
+//! Read information about the power supplies of this laptop.
+//!
+//! Unlike the rest of this crate, these readings don't go through `acpi_call`, but through the
+//! power supply class the kernel exposes in `/sys/class/power_supply`.
+
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// The default path where the kernel exposes the power supplies.
+pub const SYSFS_PATH: &str = "/sys/class/power_supply";
+
+/// The charge level of a single battery.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BatteryCapacity {
+    /// The name of the battery, for example `BAT0`.
+    pub name: String,
+
+    /// The charge level of the battery in percent.
+    pub percent: u8,
+}
+
+/// Aggregate the charge levels of multiple batteries into one, which is the average of all of
+/// them. Returns [`None`] if there are no batteries.
+pub fn aggregate_capacity(capacities: &[BatteryCapacity]) -> Option<u8> {
+    if capacities.is_empty() {
+        return None;
+    }
+
+    let total = capacities
+        .iter()
+        .map(|capacity| u32::from(capacity.percent))
+        .sum::<u32>();
+    let count = capacities.len() as u32;
+
+    Some(((total + count / 2) / count) as u8)
+}
+
+/// A source of power supply readings.
+pub trait PowerSupply: Send + Sync {
+    /// Get the temperature of the battery in degrees celsius.
+    fn battery_temperature(&self) -> io::Result<f32>;
+
+    /// Get the charge level of every battery.
+    fn battery_capacities(&self) -> io::Result<Vec<BatteryCapacity>>;
+
+    /// Get whether the laptop is plugged in.
+    fn ac_online(&self) -> io::Result<bool>;
+
+    /// Get the charge level of all batteries combined in percent. See [`aggregate_capacity`].
+    fn battery_capacity(&self) -> io::Result<u8> {
+        aggregate_capacity(&self.battery_capacities()?)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no batteries found"))
+    }
+}
+
+/// Reads power supply information from sysfs.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Sysfs {
+    /// The directory which contains the power supplies, usually [`SYSFS_PATH`].
+    pub root: PathBuf,
+}
+
+impl Sysfs {
+    /// Read power supplies from the specified directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Get the paths of all batteries (`BAT0`, `BAT1`, ...), sorted by name.
+    pub fn batteries(&self) -> io::Result<Vec<PathBuf>> {
+        let mut batteries = fs::read_dir(&self.root)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("BAT"))
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        batteries.sort();
+
+        Ok(batteries)
+    }
+
+    /// Get the paths of all AC adapters, which are the power supplies of the `Mains` type, sorted
+    /// by name.
+    pub fn ac_adapters(&self) -> io::Result<Vec<PathBuf>> {
+        let mut adapters = fs::read_dir(&self.root)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                fs::read_to_string(path.join("type")).is_ok_and(|r#type| r#type.trim() == "Mains")
+            })
+            .collect::<Vec<_>>();
+        adapters.sort();
+
+        Ok(adapters)
+    }
+
+    fn first_battery(&self) -> io::Result<PathBuf> {
+        self.batteries()?.into_iter().next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no batteries found in '{}'", self.root.display()),
+            )
+        })
+    }
+}
+
+impl Default for Sysfs {
+    fn default() -> Self {
+        Self::new(SYSFS_PATH)
+    }
+}
+
+impl PowerSupply for Sysfs {
+    fn battery_temperature(&self) -> io::Result<f32> {
+        // the kernel reports the temperature in tenths of a degree celsius
+        let tenths: i32 = read_attribute(&self.first_battery()?.join("temp"))?;
+
+        Ok(tenths as f32 / 10.0)
+    }
+
+    fn battery_capacities(&self) -> io::Result<Vec<BatteryCapacity>> {
+        self.batteries()?
+            .into_iter()
+            .map(|battery| {
+                Ok(BatteryCapacity {
+                    name: battery
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    percent: read_attribute(&battery.join("capacity"))?,
+                })
+            })
+            .collect()
+    }
+
+    fn ac_online(&self) -> io::Result<bool> {
+        let adapters = self.ac_adapters()?;
+
+        if adapters.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no ac adapters found in '{}'", self.root.display()),
+            ));
+        }
+
+        for adapter in adapters {
+            if read_attribute::<u8>(&adapter.join("online"))? != 0 {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+pub(crate) fn read_attribute<T: std::str::FromStr>(path: &Path) -> io::Result<T> {
+    let contents = fs::read_to_string(path)?;
+
+    contents.trim().parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "invalid value '{}' in '{}'",
+                contents.trim(),
+                path.display()
+            ),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::power_supply::{aggregate_capacity, BatteryCapacity, PowerSupply, Sysfs};
+    use std::fs;
+    use std::path::Path;
+
+    fn battery(root: &Path, name: &str, capacity: u8) {
+        fs::create_dir(root.join(name)).expect("failed to create battery");
+        fs::write(root.join(name).join("capacity"), format!("{}\n", capacity))
+            .expect("failed to write capacity");
+    }
+
+    #[test]
+    fn test_battery_capacities() {
+        let root = tempfile::tempdir().expect("failed to create fixture directory");
+        battery(root.path(), "BAT1", 40);
+        battery(root.path(), "BAT0", 81);
+        fs::create_dir(root.path().join("AC")).expect("failed to create ac adapter");
+        fs::write(root.path().join("AC").join("type"), "Mains\n").expect("failed to write type");
+        fs::write(root.path().join("AC").join("online"), "1\n").expect("failed to write online");
+
+        let sysfs = Sysfs::new(root.path());
+        assert!(sysfs.ac_online().expect("failed to read ac adapter"));
+
+        assert_eq!(
+            sysfs
+                .battery_capacities()
+                .expect("failed to read capacities"),
+            [
+                BatteryCapacity {
+                    name: "BAT0".into(),
+                    percent: 81,
+                },
+                BatteryCapacity {
+                    name: "BAT1".into(),
+                    percent: 40,
+                },
+            ]
+        );
+        assert_eq!(
+            sysfs.battery_capacity().expect("failed to read capacity"),
+            61
+        );
+    }
+
+    #[test]
+    fn test_aggregate_capacity_no_batteries() {
+        assert_eq!(aggregate_capacity(&[]), None);
+    }
+}
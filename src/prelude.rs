@@ -1,14 +1,26 @@
 //! Most commonly used types.
 
 pub use crate::{
-    context::Context,
-    profile::{Error as ProfileError, Profile, Result as ProfileResult},
+    context::{Context, ContextBuilder, Error as ContextError, Result as ContextResult},
+    firmware::FirmwareInfo,
+    preset::{PowerProfile, Preset, PresetPerformance, PresetReport, SettingOutcome},
+    profile::{
+        Capabilities, Error as ProfileError, OutputRadix, Profile, ProfileFieldDiff, ProfileIssue,
+        Result as ProfileResult,
+    },
+    self_test::{MethodProbe, SelfTestItem, SelfTestReport, SelfTestStatus, ValidationReport},
+    state::{DesiredState, DeviceState, StateChange, StateHistory, Subsystem},
 };
 
+#[cfg(feature = "battery_conservation")]
+pub use crate::advice::Advice;
+
 #[cfg(feature = "battery_conservation")]
 pub use crate::battery_conservation::{
-    BatteryConservationController, Error as BatteryConservationModeError,
-    Result as BatteryConservationModeResult,
+    BatteryConservationController, CrossCheck as BatteryConservationCrossCheck,
+    Error as BatteryConservationModeError, Result as BatteryConservationModeResult,
+    SysfsBatteryBackend, Tristate as BatteryConservationState,
+    Warning as BatteryConservationWarning,
 };
 
 #[cfg(feature = "rapid_charge")]
@@ -18,8 +30,9 @@ pub use crate::rapid_charge::{
 
 #[cfg(feature = "system_performance")]
 pub use crate::system_performance::{
-    Error as SystemPerformanceModeError, Result as SystemPerformanceModeResult,
-    SystemPerformanceController, SystemPerformanceMode,
+    CalibrationReport, Error as SystemPerformanceModeError, Result as SystemPerformanceModeResult,
+    SetResult as SystemPerformanceSetResult, SystemPerformanceController, SystemPerformanceMode,
+    SystemPerformanceStatus,
 };
 
 #[cfg(any(
@@ -27,7 +40,29 @@ pub use crate::system_performance::{
     feature = "rapid_charge",
     feature = "system_performance"
 ))]
-pub use crate::acpi_call::{Error as AcpiCallError, Result as AcpiCallResult};
+pub use crate::{
+    acpi_call::{
+        AcpiEvent, AcpiTap, Backend as AcpiBackend, CommandRewriter, Error as AcpiCallError,
+        Output as AcpiOutput, PathStyle, PreflightError, Result as AcpiCallResult,
+    },
+    context::GuardDropError,
+};
+
+#[cfg(all(
+    feature = "remote",
+    any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    )
+))]
+pub use crate::acpi_call::{CommandRunner, RemoteBackend, Ssh};
+
+#[cfg(all(feature = "battery_conservation", feature = "rapid_charge"))]
+pub use crate::battery::selector::BatterySelector;
 
 #[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
-pub use crate::Handler;
+pub use crate::{
+    battery::{AppliedHandler, BatteryMode, ConflictResolver, Resolution},
+    Handler, ParseHandlerError,
+};
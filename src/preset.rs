@@ -0,0 +1,653 @@
+//! Presets, which apply multiple settings at once.
+//!
+//! Presets can always be constructed, regardless of which features are compiled in. Settings
+//! whose feature isn't compiled in are skipped when the preset is applied, which is recorded in
+//! the [`PresetReport`].
+
+use crate::context::Context;
+use crate::state::DeviceState;
+use try_drop::prelude::*;
+
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+use crate::battery::BatteryMode;
+
+#[cfg(feature = "system_performance")]
+use crate::system_performance::SystemPerformanceMode;
+
+/// A system performance mode in a preset. Unlike
+/// [`SystemPerformanceMode`](crate::system_performance::SystemPerformanceMode), this is available
+/// even if the `system_performance` feature isn't enabled.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PresetPerformance {
+    /// See [`SystemPerformanceMode::IntelligentCooling`].
+    ///
+    /// [`SystemPerformanceMode::IntelligentCooling`]: crate::system_performance::SystemPerformanceMode::IntelligentCooling
+    IntelligentCooling,
+
+    /// See [`SystemPerformanceMode::ExtremePerformance`].
+    ///
+    /// [`SystemPerformanceMode::ExtremePerformance`]: crate::system_performance::SystemPerformanceMode::ExtremePerformance
+    ExtremePerformance,
+
+    /// See [`SystemPerformanceMode::BatterySaving`].
+    ///
+    /// [`SystemPerformanceMode::BatterySaving`]: crate::system_performance::SystemPerformanceMode::BatterySaving
+    BatterySaving,
+}
+
+#[cfg(feature = "system_performance")]
+impl From<PresetPerformance> for SystemPerformanceMode {
+    fn from(performance: PresetPerformance) -> Self {
+        match performance {
+            PresetPerformance::IntelligentCooling => Self::IntelligentCooling,
+            PresetPerformance::ExtremePerformance => Self::ExtremePerformance,
+            PresetPerformance::BatterySaving => Self::BatterySaving,
+        }
+    }
+}
+
+#[cfg(feature = "system_performance")]
+impl From<SystemPerformanceMode> for PresetPerformance {
+    fn from(mode: SystemPerformanceMode) -> Self {
+        match mode {
+            SystemPerformanceMode::IntelligentCooling => Self::IntelligentCooling,
+            SystemPerformanceMode::ExtremePerformance => Self::ExtremePerformance,
+            SystemPerformanceMode::BatterySaving => Self::BatterySaving,
+        }
+    }
+}
+
+/// Multiple settings which are applied at once. Settings which are [`None`] are left alone.
+#[derive(Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Preset {
+    /// The system performance mode.
+    pub system_performance: Option<PresetPerformance>,
+
+    /// Whether battery conservation is enabled. If enabled, rapid charge is switched off.
+    pub battery_conservation: Option<bool>,
+
+    /// Whether rapid charge is enabled. If enabled, battery conservation is switched off.
+    pub rapid_charge: Option<bool>,
+}
+
+/// Every setting of the state which could be read, so that applying the preset brings the device
+/// back to that state.
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+impl From<&DeviceState> for Preset {
+    fn from(state: &DeviceState) -> Self {
+        Self {
+            #[cfg(feature = "system_performance")]
+            system_performance: state.system_performance.clone().ok().map(Into::into),
+            #[cfg(not(feature = "system_performance"))]
+            system_performance: None,
+            #[cfg(feature = "battery_conservation")]
+            battery_conservation: state.battery_conservation.clone().ok(),
+            #[cfg(not(feature = "battery_conservation"))]
+            battery_conservation: None,
+            #[cfg(feature = "rapid_charge")]
+            rapid_charge: state.rapid_charge.clone().ok(),
+            #[cfg(not(feature = "rapid_charge"))]
+            rapid_charge: None,
+        }
+    }
+}
+
+/// Without any features, no setting of the state could be read.
+#[cfg(not(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+)))]
+impl From<&DeviceState> for Preset {
+    fn from(_: &DeviceState) -> Self {
+        Self::default()
+    }
+}
+
+/// What happened to a single setting when a preset was applied.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SettingOutcome {
+    /// The setting was applied.
+    Applied,
+
+    /// The setting wasn't applied, for example because its feature isn't compiled in.
+    Skipped {
+        /// Why the setting was skipped.
+        reason: String,
+    },
+
+    /// Applying the setting failed.
+    Failed {
+        /// A description of the error.
+        error: String,
+    },
+}
+
+/// What happened to every setting of a preset which wasn't [`None`], in the order they were
+/// applied. See [`Context::apply_preset`].
+#[derive(Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PresetReport {
+    /// The name of each setting and its outcome.
+    pub settings: Vec<(String, SettingOutcome)>,
+}
+
+impl PresetReport {
+    /// Check whether no setting failed. Skipped settings don't count as failures.
+    pub fn succeeded(&self) -> bool {
+        self.settings
+            .iter()
+            .all(|(_, outcome)| !matches!(outcome, SettingOutcome::Failed { .. }))
+    }
+
+    /// Get the outcome of the setting with the specified name.
+    pub fn outcome(&self, name: &str) -> Option<&SettingOutcome> {
+        self.settings
+            .iter()
+            .find(|(setting, _)| setting == name)
+            .map(|(_, outcome)| outcome)
+    }
+}
+
+impl SettingOutcome {
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    fn from_result<T, E: ToString>(result: Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Self::Applied,
+            Err(error) => Self::Failed {
+                error: error.to_string(),
+            },
+        }
+    }
+
+    #[cfg(not(all(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    )))]
+    fn skipped(feature: &str) -> Self {
+        Self::Skipped {
+            reason: format!("unsupported, `{}` feature is disabled", feature),
+        }
+    }
+}
+
+/// A setting of a [`Preset`] which isn't [`None`]. Unlike [`Subsystem`](crate::state::Subsystem),
+/// every setting exists regardless of which features are compiled in, so that skipped settings
+/// can be reported.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Change {
+    SystemPerformance(PresetPerformance),
+    BatteryConservation(bool),
+    RapidCharge(bool),
+}
+
+impl Change {
+    /// Get the name of this setting in a [`PresetReport`], which is also the name of its feature.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::SystemPerformance(_) => "system_performance",
+            Self::BatteryConservation(_) => "battery_conservation",
+            Self::RapidCharge(_) => "rapid_charge",
+        }
+    }
+
+    fn apply<D, DD>(self, context: &Context<D, DD>, restoring: bool) -> SettingOutcome
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        match self {
+            Self::SystemPerformance(performance) => {
+                apply_system_performance(context, performance, restoring)
+            }
+            Self::BatteryConservation(enabled) => apply_battery_conservation(context, enabled),
+            Self::RapidCharge(enabled) => apply_rapid_charge(context, enabled),
+        }
+    }
+}
+
+#[cfg(feature = "system_performance")]
+fn apply_system_performance<D, DD>(
+    context: &Context<D, DD>,
+    performance: PresetPerformance,
+    restoring: bool,
+) -> SettingOutcome
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    let mut controller = context.controllers().system_performance();
+    let mode = performance.into();
+
+    SettingOutcome::from_result(if restoring {
+        controller.set_unchecked(mode)
+    } else {
+        controller.set(mode)
+    })
+}
+
+#[cfg(not(feature = "system_performance"))]
+fn apply_system_performance<D, DD>(
+    _: &Context<D, DD>,
+    _: PresetPerformance,
+    _: bool,
+) -> SettingOutcome
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    SettingOutcome::skipped("system_performance")
+}
+
+#[cfg(feature = "battery_conservation")]
+fn apply_battery_conservation<D, DD>(context: &Context<D, DD>, enabled: bool) -> SettingOutcome
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    let mut controller = context.controllers().battery_conservation();
+
+    if enabled {
+        SettingOutcome::from_result(controller.enable().switch().now())
+    } else {
+        SettingOutcome::from_result(controller.disable())
+    }
+}
+
+#[cfg(not(feature = "battery_conservation"))]
+fn apply_battery_conservation<D, DD>(_: &Context<D, DD>, _: bool) -> SettingOutcome
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    SettingOutcome::skipped("battery_conservation")
+}
+
+#[cfg(feature = "rapid_charge")]
+fn apply_rapid_charge<D, DD>(context: &Context<D, DD>, enabled: bool) -> SettingOutcome
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    let mut controller = context.controllers().rapid_charge();
+
+    if enabled {
+        SettingOutcome::from_result(controller.enable().switch().now())
+    } else {
+        SettingOutcome::from_result(controller.disable())
+    }
+}
+
+#[cfg(not(feature = "rapid_charge"))]
+fn apply_rapid_charge<D, DD>(_: &Context<D, DD>, _: bool) -> SettingOutcome
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    SettingOutcome::skipped("rapid_charge")
+}
+
+impl Preset {
+    /// Apply every setting of this preset which isn't [`None`]. This is best-effort; every setting
+    /// is attempted even if a previous one failed.
+    ///
+    /// The system performance mode is applied first, then whichever battery mode is disabled,
+    /// then whichever battery mode is enabled, so that enabling one doesn't get undone.
+    pub fn apply<D, DD>(&self, context: &Context<D, DD>) -> PresetReport
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        self.apply_with(context, false)
+    }
+
+    /// Apply this preset to bring the device back to a state it was in before, like
+    /// [`Self::apply`], except that setting the system performance mode isn't subject to the
+    /// [cooldown](Context::system_performance_cooldown) or the other checks of
+    /// [`SystemPerformanceController::set`].
+    ///
+    /// [`SystemPerformanceController::set`]: crate::system_performance::SystemPerformanceController::set
+    pub(crate) fn restore<D, DD>(&self, context: &Context<D, DD>) -> PresetReport
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        self.apply_with(context, true)
+    }
+
+    fn apply_with<D, DD>(&self, context: &Context<D, DD>, restoring: bool) -> PresetReport
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        PresetReport {
+            settings: self
+                .changes()
+                .into_iter()
+                .map(|change| (change.name().to_string(), change.apply(context, restoring)))
+                .collect(),
+        }
+    }
+
+    /// Get the `acpi_call` commands which [`Self::apply`] would issue to change the settings, in
+    /// order, as each method with its parameters. This is computed from the profile of the
+    /// context, without touching the embedded controller. Like any other command, each one is
+    /// rewritten into the [path style](Context::path_style) and through the
+    /// [command rewriter](Context::command_rewriter) of the context.
+    ///
+    /// Settings are left out of the plan if their feature isn't compiled in or the profile doesn't
+    /// have their section. Switching off the conflicting battery mode isn't part of the plan
+    /// either, since whether that happens depends on the state of the embedded controller.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn plan<D, DD>(&self, context: &Context<D, DD>) -> Vec<(String, Vec<u32>)>
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        let profile = &context.profile;
+
+        self.changes()
+            .into_iter()
+            .filter_map(|change| match change {
+                #[cfg(feature = "system_performance")]
+                Change::SystemPerformance(performance) => {
+                    profile
+                        .system_performance
+                        .as_ref()
+                        .map(|system_performance| {
+                            SystemPerformanceMode::from(performance).action(system_performance)
+                        })
+                }
+                #[cfg(feature = "battery_conservation")]
+                Change::BatteryConservation(enabled) => profile
+                    .battery
+                    .as_ref()
+                    .map(|battery| BatteryMode::Conservation.action(battery, enabled)),
+                #[cfg(feature = "rapid_charge")]
+                Change::RapidCharge(enabled) => profile
+                    .battery
+                    .as_ref()
+                    .map(|battery| BatteryMode::RapidCharge.action(battery, enabled)),
+                #[cfg(not(feature = "system_performance"))]
+                Change::SystemPerformance(_) => None,
+                #[cfg(not(feature = "battery_conservation"))]
+                Change::BatteryConservation(_) => None,
+                #[cfg(not(feature = "rapid_charge"))]
+                Change::RapidCharge(_) => None,
+            })
+            .map(|(method, parameter)| {
+                let (method, parameters) = context.rewrite_call(method, [parameter]);
+                (method.into_owned(), parameters)
+            })
+            .collect()
+    }
+
+    /// Get the settings which aren't [`None`] in the order they are applied: the system
+    /// performance mode first, then whichever battery mode is disabled, then whichever battery
+    /// mode is enabled, so that enabling one doesn't get undone.
+    fn changes(&self) -> Vec<Change> {
+        let mut battery = [
+            self.battery_conservation
+                .map(|enabled| (enabled, Change::BatteryConservation(enabled))),
+            self.rapid_charge
+                .map(|enabled| (enabled, Change::RapidCharge(enabled))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        // `false` sorts first, so disabling happens before enabling
+        battery.sort_by_key(|&(enabled, _)| enabled);
+
+        self.system_performance
+            .map(Change::SystemPerformance)
+            .into_iter()
+            .chain(battery.into_iter().map(|(_, change)| change))
+            .collect()
+    }
+}
+
+/// A named power profile, which is a friendlier way to pick a system performance mode along with a
+/// battery policy than setting each of them. See [`Self::preset`] for what each one does.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PowerProfile {
+    /// Battery saving, with battery conservation enabled to preserve the battery.
+    Quiet,
+
+    /// Intelligent cooling, with both battery modes disabled.
+    Balanced,
+
+    /// Extreme performance, with rapid charge enabled.
+    Performance,
+}
+
+impl PowerProfile {
+    /// Every power profile.
+    pub const ALL: [Self; 3] = [Self::Quiet, Self::Balanced, Self::Performance];
+
+    /// Get the settings which this power profile applies.
+    pub const fn preset(self) -> Preset {
+        let (performance, battery_conservation, rapid_charge) = match self {
+            Self::Quiet => (PresetPerformance::BatterySaving, true, false),
+            Self::Balanced => (PresetPerformance::IntelligentCooling, false, false),
+            Self::Performance => (PresetPerformance::ExtremePerformance, false, true),
+        };
+
+        Preset {
+            system_performance: Some(performance),
+            battery_conservation: Some(battery_conservation),
+            rapid_charge: Some(rapid_charge),
+        }
+    }
+
+    /// Get the power profile which matches the most settings of the state, preferring the
+    /// earlier one in [`Self::ALL`] on ties. Settings which couldn't be read or whose feature
+    /// isn't compiled in are ignored, and if there are none, [`None`] is returned.
+    pub fn closest(state: &DeviceState) -> Option<Self> {
+        let settings = Preset::from(state);
+
+        if settings == Preset::default() {
+            return None;
+        }
+
+        let mut closest = None;
+
+        for profile in Self::ALL {
+            let preset = profile.preset();
+            let matches = [
+                settings.system_performance.is_some()
+                    && settings.system_performance == preset.system_performance,
+                settings.battery_conservation.is_some()
+                    && settings.battery_conservation == preset.battery_conservation,
+                settings.rapid_charge.is_some() && settings.rapid_charge == preset.rapid_charge,
+            ]
+            .into_iter()
+            .filter(|&matches| matches)
+            .count();
+
+            if !matches!(closest, Some((_, most)) if most >= matches) {
+                closest = Some((profile, matches));
+            }
+        }
+
+        closest.map(|(profile, _)| profile)
+    }
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use crate::acpi_call::PathStyle;
+    use crate::mock::MockBackend;
+    use crate::preset::{PowerProfile, Preset, PresetPerformance, SettingOutcome};
+    use crate::state::DeviceState;
+    use crate::system_performance::SystemPerformanceMode;
+    use crate::{Context, Profile};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_apply() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_battery_conservation(true);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let report = context.apply_preset(&Preset {
+            system_performance: Some(PresetPerformance::ExtremePerformance),
+            battery_conservation: Some(false),
+            rapid_charge: Some(true),
+        });
+
+        assert!(report.succeeded());
+        assert_eq!(
+            report.outcome("rapid_charge"),
+            Some(&SettingOutcome::Applied)
+        );
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::ExtremePerformance
+        );
+        assert!(!backend.battery_conservation());
+        assert!(backend.rapid_charge());
+    }
+
+    #[test]
+    fn test_set_power_profile() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        for (profile, mode, battery_conservation, rapid_charge) in [
+            (
+                PowerProfile::Performance,
+                SystemPerformanceMode::ExtremePerformance,
+                false,
+                true,
+            ),
+            (
+                PowerProfile::Quiet,
+                SystemPerformanceMode::BatterySaving,
+                true,
+                false,
+            ),
+            (
+                PowerProfile::Balanced,
+                SystemPerformanceMode::IntelligentCooling,
+                false,
+                false,
+            ),
+        ] {
+            assert!(context.set_power_profile(profile).succeeded());
+            assert_eq!(backend.system_performance_mode(), mode);
+            assert_eq!(backend.battery_conservation(), battery_conservation);
+            assert_eq!(backend.rapid_charge(), rapid_charge);
+            assert_eq!(context.current_power_profile(), Some(profile));
+        }
+    }
+
+    #[test]
+    fn test_closest_power_profile() {
+        let state = DeviceState {
+            profile: "IDEAPAD_AMD".to_string(),
+            system_performance: Ok(SystemPerformanceMode::ExtremePerformance),
+            battery_conservation: Ok(true),
+            rapid_charge: Err("method not found".to_string()),
+        };
+
+        // performance matches the system performance mode and quiet matches battery conservation,
+        // so the earlier one wins the tie
+        assert_eq!(PowerProfile::closest(&state), Some(PowerProfile::Quiet));
+
+        let state = DeviceState {
+            battery_conservation: Ok(false),
+            ..state
+        };
+        assert_eq!(
+            PowerProfile::closest(&state),
+            Some(PowerProfile::Performance)
+        );
+
+        let state = DeviceState {
+            system_performance: Err("method not found".to_string()),
+            battery_conservation: Err("method not found".to_string()),
+            ..state
+        };
+        assert_eq!(PowerProfile::closest(&state), None);
+    }
+
+    #[test]
+    fn test_plan() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let plan = Preset {
+            system_performance: Some(PresetPerformance::BatterySaving),
+            battery_conservation: Some(true),
+            rapid_charge: Some(false),
+        }
+        .plan(&context);
+
+        assert_eq!(
+            plan,
+            [
+                (
+                    r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC"#.to_string(),
+                    vec![0x0013B001]
+                ),
+                (r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#.to_string(), vec![0x08]),
+                (r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#.to_string(), vec![0x03]),
+            ]
+        );
+
+        // the plan is issued like any other command
+        context.path_style = PathStyle::Padded;
+        context.command_rewriter = Some(Arc::new(|method, parameters| {
+            (method.replace("SBMC", "SBMD"), parameters.to_vec())
+        }));
+        assert_eq!(
+            Preset {
+                battery_conservation: Some(true),
+                ..Preset::default()
+            }
+            .plan(&context),
+            [(r#"\_SB_.PCI0.LPC0.EC0_.VPC0.SBMD"#.to_string(), vec![0x03])]
+        );
+        assert!(backend.calls().is_empty());
+    }
+}
+
+#[cfg(all(test, feature = "ideapad_amd", not(feature = "system_performance")))]
+mod degraded_tests {
+    use crate::preset::{Preset, PresetPerformance, SettingOutcome};
+    use crate::{Context, Profile};
+
+    #[test]
+    fn test_apply_skips_disabled_features() {
+        let context = Context::new(Profile::IDEAPAD_AMD);
+        let report = context.apply_preset(&Preset {
+            system_performance: Some(PresetPerformance::BatterySaving),
+            ..Default::default()
+        });
+
+        assert!(report.succeeded());
+        assert!(matches!(
+            report.outcome("system_performance"),
+            Some(SettingOutcome::Skipped { reason }) if reason.contains("system_performance")
+        ));
+    }
+}
@@ -1,10 +1,28 @@
 //! An abstraction which allows this crate to be used on multiple Ideapad models.
 
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+use crate::acpi_call::{self, PathStyle};
 use smbioslib::SMBiosSystemInformation;
 use std::borrow::Cow;
-use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use std::{env, io, thread};
 use thiserror::Error;
 
+static REGISTERED: Mutex<Vec<Arc<Profile>>> = Mutex::new(Vec::new());
+static CACHED: Mutex<Option<Profile>> = Mutex::new(None);
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+static CACHED_PATH_STYLE: Mutex<Option<PathStyle>> = Mutex::new(None);
+
 /// Handy wrapper for [`enum@Error`].
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -24,31 +42,134 @@ pub enum Error {
     UnableToFindSystemInformation,
 
     /// No valid profile was found in the specified search path.
-    #[error("no valid profiles were found in the search path")]
-    NoValidProfileInSearchPath,
+    #[error(
+        "detected product name '{detected_product}', but none of the profiles [{}] support it",
+        considered.join(", ")
+    )]
+    NoValidProfileInSearchPath {
+        /// The product name of this laptop's model.
+        detected_product: String,
+
+        /// The names of the profiles in the search path.
+        considered: Vec<String>,
+    },
+
+    /// A command of a profile which came from untrusted input was empty.
+    #[error("the `{field}` command of the profile is empty")]
+    EmptyCommand {
+        /// The path of the empty command in the profile, for example `battery.set_command`.
+        field: &'static str,
+    },
+
+    /// The profile couldn't be parsed as TOML.
+    #[cfg(feature = "toml")]
+    #[error("{error}")]
+    Toml {
+        /// The underlying TOML error.
+        #[from]
+        error: toml::de::Error,
+    },
+
+    /// The profile forced through the [`Profile::ENV_OVERRIDE`] environment variable isn't in the
+    /// search path.
+    #[error("profile '{name}' was forced through the environment, but isn't in the search path")]
+    UnknownProfile {
+        /// The name of the forced profile.
+        name: String,
+    },
+
+    /// The profile was written for a newer version of the profile schema than this crate supports.
+    #[error("profile schema version {version} is newer than the supported version {supported}")]
+    UnsupportedProfileVersion {
+        /// The schema version of the profile.
+        version: u32,
+
+        /// The newest schema version which is supported, which is [`Profile::SCHEMA_VERSION`].
+        supported: u32,
+    },
+
+    /// Getting the system information from the SMBIOS took too long.
+    #[error("getting system information from smbios took longer than {timeout:?}")]
+    DetectionTimedOut {
+        /// How long detection was allowed to take.
+        timeout: Duration,
+    },
+}
+
+/// Deserializes the [`u32`] parameters and bits of profiles from either integers or strings, so
+/// that values could be written as they usually are in hexadecimal, for example `"0x03"`.
+#[cfg(feature = "serde")]
+mod hex {
+    use serde::de::{self, Deserializer, Unexpected, Visitor};
+    use std::fmt;
+
+    struct U32Visitor;
+
+    impl<'de> Visitor<'de> for U32Visitor {
+        type Value = u32;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an integer or a hexadecimal string such as \"0x03\"")
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+            u32::try_from(value).map_err(|_| E::invalid_value(Unexpected::Unsigned(value), &self))
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+            u32::try_from(value).map_err(|_| E::invalid_value(Unexpected::Signed(value), &self))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            let parsed = match value
+                .strip_prefix("0x")
+                .or_else(|| value.strip_prefix("0X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => value.parse(),
+            };
+
+            parsed.map_err(|_| E::invalid_value(Unexpected::Str(value), &self))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        deserializer.deserialize_any(U32Visitor)
+    }
 }
 
 /// Actual values of [`Bit`]. It is not guaranteed that [`Self::Different`] would actually be
 /// different values; this is why [`Bit`] wraps this type.
+///
+/// When (de)serialized, same bits are represented as a plain integer (`0`), while different bits
+/// are represented as a map (`{ spmo = 0, fcmo = 1 }`).
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(untagged))]
 pub enum BitInner {
     /// Same bits.
-    Same(u32),
+    Same(#[cfg_attr(feature = "serde", serde(deserialize_with = "hex::deserialize"))] u32),
 
     /// (not guaranteed to be) different bits.
     Different {
         /// The SPMO bit.
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "hex::deserialize"))]
         spmo: u32,
 
         /// The FCMO bit.
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "hex::deserialize"))]
         fcmo: u32,
     },
 }
 
 /// Represents an spmo and fcmo bit.
+///
+/// This is (de)serialized the same way as [`BitInner`].
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "BitInner", into = "BitInner")
+)]
 pub struct Bit(BitInner);
 
 impl Bit {
@@ -93,6 +214,18 @@ impl Bit {
     }
 }
 
+impl From<BitInner> for Bit {
+    fn from(inner: BitInner) -> Self {
+        Self::from_inner(inner)
+    }
+}
+
+impl From<Bit> for BitInner {
+    fn from(bit: Bit) -> Self {
+        bit.inner()
+    }
+}
+
 /// Variety of commands which could be used to for system performance.
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -105,6 +238,15 @@ pub struct SystemPerformanceCommands {
 
     /// Get SPMO bit command.
     pub get_spmo_bit: Cow<'static, str>,
+
+    /// Command which queries the system performance modes the firmware currently allows, if the
+    /// firmware has one. It returns a bitmask where bit `n` is set if the mode with the fcmo bit
+    /// `n` is allowed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub capabilities: Option<Cow<'static, str>>,
 }
 
 impl SystemPerformanceCommands {
@@ -119,6 +261,7 @@ impl SystemPerformanceCommands {
             set: Cow::Borrowed(set),
             get_fcmo_bit: Cow::Borrowed(get_fcmo_bit),
             get_spmo_bit: Cow::Borrowed(get_spmo_bit),
+            capabilities: None,
         }
     }
 
@@ -129,6 +272,7 @@ impl SystemPerformanceCommands {
             set: Cow::Owned(set),
             get_fcmo_bit: Cow::Owned(get_fcmo_bit),
             get_spmo_bit: Cow::Owned(get_spmo_bit),
+            capabilities: None,
         }
     }
 
@@ -143,8 +287,16 @@ impl SystemPerformanceCommands {
             set: set.into(),
             get_fcmo_bit: get_fcmo_bit.into(),
             get_spmo_bit: get_spmo_bit.into(),
+            capabilities: None,
         }
     }
+
+    /// Use the specified command to query the system performance modes the firmware currently
+    /// allows. See [`Self::capabilities`].
+    pub fn with_capabilities(mut self, capabilities: impl Into<Cow<'static, str>>) -> Self {
+        self.capabilities = Some(capabilities.into());
+        self
+    }
 }
 
 /// System performance parameters which are passed as arguments to `acpi_call`.
@@ -152,12 +304,15 @@ impl SystemPerformanceCommands {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SystemPerformanceParameters {
     /// Parameter which is used to set the current system performance to intelligent cooling.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "hex::deserialize"))]
     pub intelligent_cooling: u32,
 
     /// Parameter which is used to set the current system performance to extreme performance.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "hex::deserialize"))]
     pub extreme_performance: u32,
 
     /// Parameter which is used to set the current system performance to battery saving.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "hex::deserialize"))]
     pub battery_saving: u32,
 }
 
@@ -181,6 +336,15 @@ impl SystemPerformanceParameters {
             battery_saving,
         }
     }
+
+    /// Get the parameter which sets the system performance mode. This is the same as
+    /// [`SystemPerformanceMode::setter`].
+    ///
+    /// [`SystemPerformanceMode::setter`]: crate::system_performance::SystemPerformanceMode::setter
+    #[cfg(feature = "system_performance")]
+    pub const fn value_of(&self, mode: crate::system_performance::SystemPerformanceMode) -> u32 {
+        mode.setter(self)
+    }
 }
 
 /// System performance bits which are used to disambiguate between the different types of system
@@ -261,6 +425,23 @@ pub struct Battery {
 
     /// Rapid charge configuration.
     pub rapid_charge: SharedBatteryConfiguration,
+
+    /// If set, the output of the battery conservation get command is a bitmask, and battery
+    /// conservation is enabled if any of these bits are set. Otherwise, any output other than `0`
+    /// means that battery conservation is enabled.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub conservation_status_mask: Option<u32>,
+
+    /// If set, the battery conservation threshold can be set to one of several percentages
+    /// rather than battery conservation only being toggled. Few models support this.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub conservation_threshold: Option<ConservationThreshold>,
 }
 
 impl Battery {
@@ -275,6 +456,8 @@ impl Battery {
             set_command: Cow::Borrowed(set_command),
             conservation,
             rapid_charge,
+            conservation_status_mask: None,
+            conservation_threshold: None,
         }
     }
 
@@ -289,6 +472,8 @@ impl Battery {
             set_command: Cow::Owned(set_command),
             conservation,
             rapid_charge,
+            conservation_status_mask: None,
+            conservation_threshold: None,
         }
     }
 
@@ -303,18 +488,83 @@ impl Battery {
             set_command: set_command.into(),
             conservation,
             rapid_charge,
+            conservation_status_mask: None,
+            conservation_threshold: None,
+        }
+    }
+
+    /// Read the battery conservation status as a bitmask. See [`Self::conservation_status_mask`].
+    pub const fn with_conservation_status_mask(mut self, mask: u32) -> Self {
+        self.conservation_status_mask = Some(mask);
+        self
+    }
+
+    /// Allow setting the battery conservation threshold. See [`Self::conservation_threshold`].
+    pub fn with_conservation_threshold(mut self, threshold: ConservationThreshold) -> Self {
+        self.conservation_threshold = Some(threshold);
+        self
+    }
+
+    /// Interpret the output of the battery conservation get command, applying the
+    /// [mask](Self::conservation_status_mask) if there is one.
+    pub const fn conservation_enabled(&self, output: u32) -> bool {
+        match self.conservation_status_mask {
+            Some(mask) => output & mask != 0,
+            None => output != 0,
         }
     }
 }
 
+/// Configuration for setting the battery conservation threshold, for models whose firmware
+/// supports more than toggling battery conservation.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConservationThreshold {
+    /// The command which sets the threshold, taking the percentage as its parameter.
+    pub set_command: Cow<'static, str>,
+
+    /// The percentages which the firmware accepts as a threshold.
+    pub allowed: Cow<'static, [u8]>,
+}
+
+impl ConservationThreshold {
+    /// Create a new threshold configuration which uses stack allocated types which can be
+    /// constructed at compile time.
+    pub const fn r#static(set_command: &'static str, allowed: &'static [u8]) -> Self {
+        Self {
+            set_command: Cow::Borrowed(set_command),
+            allowed: Cow::Borrowed(allowed),
+        }
+    }
+
+    /// Create a new threshold configuration. Although more flexible than [`Self::static`], this
+    /// can only be used at runtime.
+    pub fn new(
+        set_command: impl Into<Cow<'static, str>>,
+        allowed: impl Into<Cow<'static, [u8]>>,
+    ) -> Self {
+        Self {
+            set_command: set_command.into(),
+            allowed: allowed.into(),
+        }
+    }
+
+    /// Check whether the firmware accepts the percentage as a threshold.
+    pub fn allows(&self, percent: u8) -> bool {
+        self.allowed.contains(&percent)
+    }
+}
+
 /// Parameters for [`SharedBatteryConfiguration`].
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SharedBatteryConfigurationParameters {
     /// Enable either battery conservation or rapid charge.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "hex::deserialize"))]
     pub enable: u32,
 
     /// Disable either battery conservation or rapid charge.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "hex::deserialize"))]
     pub disable: u32,
 }
 
@@ -337,6 +587,18 @@ impl SharedBatteryConfigurationParameters {
     pub const fn new(enable: u32, disable: u32) -> Self {
         Self { enable, disable }
     }
+
+    /// Describe what the parameter does, which is either `"enable"`, `"disable"` or `"unknown"`.
+    /// This makes logs of raw `acpi_call` commands readable.
+    pub const fn describe(&self, value: u32) -> &'static str {
+        if value == self.enable {
+            "enable"
+        } else if value == self.disable {
+            "disable"
+        } else {
+            "unknown"
+        }
+    }
 }
 
 /// Battery configuration which is shared between battery conservation and rapid charge.
@@ -386,11 +648,118 @@ impl SharedBatteryConfiguration {
             parameters,
         }
     }
+
+    /// Get the parameter which either enables or disables the battery mode.
+    pub const fn param_for(&self, enable: bool) -> u32 {
+        if enable {
+            self.parameters.enable
+        } else {
+            self.parameters.disable
+        }
+    }
+}
+
+/// What a model can do, according to which sections its [`Profile`] has.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Capabilities {
+    /// Whether battery conservation is supported.
+    pub has_conservation: bool,
+
+    /// Whether rapid charge is supported.
+    pub has_rapid_charge: bool,
+
+    /// Whether system performance modes are supported.
+    pub has_system_performance: bool,
+}
+
+/// A field which differs between two profiles. See [`Profile::diff`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ProfileFieldDiff {
+    /// The path of the field, for example `battery.set_command`.
+    pub field: &'static str,
+
+    /// The value of the field in the first profile, or [`None`] if its section is missing.
+    pub left: Option<String>,
+
+    /// The value of the field in the second profile, or [`None`] if its section is missing.
+    pub right: Option<String>,
+}
+
+/// A problem with the structure of a profile. See [`Profile::validate_structure`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Error)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ProfileIssue {
+    /// A command of the profile is empty.
+    #[error("the `{field}` command is empty")]
+    EmptyCommand {
+        /// The path of the empty command, for example `battery.set_command`.
+        field: &'static str,
+    },
+
+    /// The enable and disable parameters of a battery mode are the same, so it can't be turned
+    /// off once turned on (or vice versa).
+    #[error("the enable and disable parameters of `{field}` are both {parameter:#x}")]
+    SameEnableDisable {
+        /// The path of the parameters, for example `battery.conservation.parameters`.
+        field: &'static str,
+
+        /// The parameter which is used for both enabling and disabling.
+        parameter: u32,
+    },
+
+    /// Two system performance modes are set with the same parameter.
+    #[error("`{first}` and `{second}` are both {parameter:#x}")]
+    DuplicatePerformanceParameter {
+        /// The path of the first parameter, for example
+        /// `system_performance.parameters.intelligent_cooling`.
+        first: &'static str,
+
+        /// The path of the second parameter.
+        second: &'static str,
+
+        /// The parameter which is shared by both system performance modes.
+        parameter: u32,
+    },
+}
+
+/// The radix which the embedded controller writes outputs without a `0x` prefix in.
+#[derive(Debug, Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OutputRadix {
+    /// Outputs without a `0x` prefix are decimal, for example `171`. This is what most versions
+    /// of the `acpi_call` kernel module return.
+    #[default]
+    Decimal,
+
+    /// Outputs without a `0x` prefix are hexadecimal, for example `AB`, for versions of the
+    /// `acpi_call` kernel module which leave out the prefix.
+    Hex,
+}
+
+impl OutputRadix {
+    /// Whether this is [`Self::Decimal`].
+    pub const fn is_decimal(&self) -> bool {
+        matches!(self, Self::Decimal)
+    }
+
+    /// The radix as a number, which can be passed to [`u32::from_str_radix`].
+    pub const fn radix(self) -> u32 {
+        match self {
+            Self::Decimal => 10,
+            Self::Hex => 16,
+        }
+    }
 }
 
 /// A configuration which allows this crate to be used in different Ideapad models.
+///
+/// Fields are added as profiles learn to describe more models, so profiles have to be created
+/// through [`Self::new`], [`Self::static`], [`Self::dynamic`] or by deserializing them.
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub struct Profile {
     /// The name of this profile.
     pub name: Cow<'static, str>,
@@ -398,11 +767,46 @@ pub struct Profile {
     /// The product names which this profile supports.
     pub expected_product_names: Cow<'static, [Cow<'static, str>]>,
 
-    /// System performance.
-    pub system_performance: SystemPerformance,
-
-    /// Battery.
-    pub battery: Battery,
+    /// System performance, if the model supports it.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub system_performance: Option<SystemPerformance>,
+
+    /// Battery, if the model supports battery conservation and rapid charge.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub battery: Option<Battery>,
+
+    /// If set, how many bits wide the parameters which the embedded controller accepts are.
+    /// Parameters which don't fit are rejected with an
+    /// [`acpi_call::Error::ParameterOutOfRange`](crate::acpi_call::Error::ParameterOutOfRange)
+    /// instead of being silently truncated.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub parameter_width: Option<u32>,
+
+    /// The radix which outputs without a `0x` prefix are parsed in. Outputs with a `0x` prefix
+    /// are always parsed as hexadecimal.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "OutputRadix::is_decimal")
+    )]
+    pub output_radix: OutputRadix,
+
+    /// The version of the profile schema which this profile was written for. Profiles without one
+    /// are assumed to be of the first version.
+    ///
+    /// Unknown fields are ignored when deserializing, so that profiles with fields from newer
+    /// versions still load, but profiles of a newer version than [`Self::SCHEMA_VERSION`] are
+    /// rejected by [`Self::validate`].
+    #[cfg_attr(feature = "serde", serde(default = "Profile::first_schema_version"))]
+    pub schema_version: u32,
 }
 
 impl Profile {
@@ -496,8 +900,11 @@ impl Profile {
         Self {
             name: Cow::Borrowed(name),
             expected_product_names: Cow::Borrowed(expected_product_names),
-            system_performance,
-            battery,
+            system_performance: Some(system_performance),
+            battery: Some(battery),
+            parameter_width: None,
+            output_radix: OutputRadix::Decimal,
+            schema_version: Self::SCHEMA_VERSION,
         }
     }
 
@@ -512,8 +919,11 @@ impl Profile {
         Self {
             name: Cow::Owned(name),
             expected_product_names: Cow::Owned(expected_product_names),
-            system_performance,
-            battery,
+            system_performance: Some(system_performance),
+            battery: Some(battery),
+            parameter_width: None,
+            output_radix: OutputRadix::Decimal,
+            schema_version: Self::SCHEMA_VERSION,
         }
     }
 
@@ -533,11 +943,375 @@ impl Profile {
                     .map(|x| x.into())
                     .collect(),
             ),
-            system_performance,
-            battery,
+            system_performance: Some(system_performance),
+            battery: Some(battery),
+            parameter_width: None,
+            output_radix: OutputRadix::Decimal,
+            schema_version: Self::SCHEMA_VERSION,
+        }
+    }
+
+    /// The newest version of the profile schema which is supported.
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    #[cfg(feature = "serde")]
+    const fn first_schema_version() -> u32 {
+        1
+    }
+
+    /// Reject parameters which are wider than the specified amount of bits. See
+    /// [`Self::parameter_width`].
+    pub const fn with_parameter_width(mut self, width: u32) -> Self {
+        self.parameter_width = Some(width);
+        self
+    }
+
+    /// Parse outputs without a `0x` prefix in the specified radix. See [`Self::output_radix`].
+    pub const fn with_output_radix(mut self, radix: OutputRadix) -> Self {
+        self.output_radix = radix;
+        self
+    }
+
+    /// Parse a profile from TOML, validating it afterwards. See [`Self::validate`].
+    ///
+    /// The schema version is checked before anything else, so that a profile of a newer version
+    /// is reported as such, instead of failing to parse because of a changed field.
+    ///
+    /// # Errors
+    /// If the profile is of a newer schema version than supported, an
+    /// [`Error::UnsupportedProfileVersion`] is returned. If the TOML is invalid or doesn't describe
+    /// a profile, an [`Error::Toml`] is returned. If a command is empty, an
+    /// [`Error::EmptyCommand`] is returned.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Version {
+            #[serde(default = "Profile::first_schema_version")]
+            schema_version: u32,
+        }
+
+        Self::check_schema_version(toml::from_str::<Version>(toml)?.schema_version)?;
+        let profile: Self = toml::from_str(toml)?;
+        profile.validate()?;
+
+        Ok(profile)
+    }
+
+    /// Check that this profile is usable. This should be done for profiles which came from
+    /// untrusted input; the bundled profiles are always valid.
+    ///
+    /// # Errors
+    /// If the profile is of a newer schema version than supported, an
+    /// [`Error::UnsupportedProfileVersion`] is returned.
+    ///
+    /// If a command is empty, which would result in only the parameters being written to
+    /// `acpi_call`, an [`Error::EmptyCommand`] is returned.
+    pub fn validate(&self) -> Result<()> {
+        Self::check_schema_version(self.schema_version)?;
+
+        match self.empty_commands().next() {
+            Some(field) => Err(Error::EmptyCommand { field }),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the invariants of this profile which deserialization can't, such as the enable and
+    /// disable parameters of a battery mode being distinct. This catches copy-paste errors in
+    /// user-authored profiles before they reach the embedded controller.
+    ///
+    /// # Errors
+    /// Every issue which was found is returned, in the order the fields appear in a profile.
+    pub fn validate_structure(&self) -> std::result::Result<(), Vec<ProfileIssue>> {
+        let mut issues = self
+            .empty_commands()
+            .map(|field| ProfileIssue::EmptyCommand { field })
+            .collect::<Vec<_>>();
+
+        if let Some(system_performance) = &self.system_performance {
+            let parameters = &system_performance.parameters;
+            let parameters = [
+                (
+                    "system_performance.parameters.intelligent_cooling",
+                    parameters.intelligent_cooling,
+                ),
+                (
+                    "system_performance.parameters.extreme_performance",
+                    parameters.extreme_performance,
+                ),
+                (
+                    "system_performance.parameters.battery_saving",
+                    parameters.battery_saving,
+                ),
+            ];
+
+            for (index, &(first, parameter)) in parameters.iter().enumerate() {
+                issues.extend(
+                    parameters[index + 1..]
+                        .iter()
+                        .filter(|(_, other)| *other == parameter)
+                        .map(|&(second, _)| ProfileIssue::DuplicatePerformanceParameter {
+                            first,
+                            second,
+                            parameter,
+                        }),
+                );
+            }
+        }
+
+        if let Some(battery) = &self.battery {
+            issues.extend(
+                [
+                    ("battery.conservation.parameters", &battery.conservation),
+                    ("battery.rapid_charge.parameters", &battery.rapid_charge),
+                ]
+                .into_iter()
+                .filter(|(_, configuration)| {
+                    configuration.parameters.enable == configuration.parameters.disable
+                })
+                .map(|(field, configuration)| ProfileIssue::SameEnableDisable {
+                    field,
+                    parameter: configuration.parameters.enable,
+                }),
+            );
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    fn check_schema_version(version: u32) -> Result<()> {
+        if version > Self::SCHEMA_VERSION {
+            return Err(Error::UnsupportedProfileVersion {
+                version,
+                supported: Self::SCHEMA_VERSION,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Get what the model can do, according to which sections this profile has. This is
+    /// independent of which features of this crate are compiled in.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            has_conservation: self.battery.is_some(),
+            has_rapid_charge: self.battery.is_some(),
+            has_system_performance: self.system_performance.is_some(),
+        }
+    }
+
+    /// Get which field of this profile refers to the `acpi_call` method, for example
+    /// `battery.conservation.get_command`. This is useful for finding out which part of a profile
+    /// is wrong when a method isn't found.
+    pub fn field_of(&self, method: &str) -> Option<&'static str> {
+        self.commands()
+            .into_iter()
+            .find(|(_, command)| command.as_ref() == method)
+            .map(|(field, _)| field)
+    }
+
+    /// Get every command of this profile along with the path of its field.
+    fn commands(&self) -> Vec<(&'static str, &Cow<'static, str>)> {
+        let mut fields = Vec::new();
+
+        if let Some(system_performance) = &self.system_performance {
+            let commands = &system_performance.commands;
+            fields.extend([
+                ("system_performance.commands.set", &commands.set),
+                (
+                    "system_performance.commands.get_fcmo_bit",
+                    &commands.get_fcmo_bit,
+                ),
+                (
+                    "system_performance.commands.get_spmo_bit",
+                    &commands.get_spmo_bit,
+                ),
+            ]);
+
+            if let Some(capabilities) = &commands.capabilities {
+                fields.push(("system_performance.commands.capabilities", capabilities));
+            }
+        }
+
+        if let Some(battery) = &self.battery {
+            fields.extend([
+                ("battery.set_command", &battery.set_command),
+                (
+                    "battery.conservation.get_command",
+                    &battery.conservation.get_command,
+                ),
+                (
+                    "battery.rapid_charge.get_command",
+                    &battery.rapid_charge.get_command,
+                ),
+            ]);
+
+            if let Some(threshold) = &battery.conservation_threshold {
+                fields.push((
+                    "battery.conservation_threshold.set_command",
+                    &threshold.set_command,
+                ));
+            }
+        }
+
+        fields
+    }
+
+    /// Get the fields of the commands which are empty, in the order they appear in a profile.
+    fn empty_commands(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.commands()
+            .into_iter()
+            .filter(|(_, command)| command.trim().is_empty())
+            .map(|(field, _)| field)
+    }
+
+    /// Get which command, bit and parameter fields differ between this profile and the other one,
+    /// in the order they appear in a profile. This helps with deriving a profile for a new model
+    /// from a similar existing one.
+    pub fn diff(&self, other: &Profile) -> Vec<ProfileFieldDiff> {
+        let left = self.fields();
+        let right = other.fields();
+        fn value_of(fields: &[(&'static str, String)], field: &str) -> Option<String> {
+            fields
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map(|(_, value)| value.clone())
+        }
+
+        left.iter()
+            .chain(
+                right
+                    .iter()
+                    .filter(|(field, _)| value_of(&left, field).is_none()),
+            )
+            .map(|&(field, _)| ProfileFieldDiff {
+                field,
+                left: value_of(&left, field),
+                right: value_of(&right, field),
+            })
+            .filter(|diff| diff.left != diff.right)
+            .collect()
+    }
+
+    /// Get every command, bit and parameter of this profile along with the path of its field.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = self
+            .commands()
+            .into_iter()
+            .map(|(field, command)| (field, command.to_string()))
+            .collect::<Vec<_>>();
+
+        if let Some(system_performance) = &self.system_performance {
+            let bits = &system_performance.bits;
+            let parameters = &system_performance.parameters;
+            fields.extend([
+                (
+                    "system_performance.bits.intelligent_cooling",
+                    format_bit(bits.intelligent_cooling),
+                ),
+                (
+                    "system_performance.bits.extreme_performance",
+                    format_bit(bits.extreme_performance),
+                ),
+                (
+                    "system_performance.bits.battery_saving",
+                    format_bit(bits.battery_saving),
+                ),
+                (
+                    "system_performance.parameters.intelligent_cooling",
+                    format!("{:#x}", parameters.intelligent_cooling),
+                ),
+                (
+                    "system_performance.parameters.extreme_performance",
+                    format!("{:#x}", parameters.extreme_performance),
+                ),
+                (
+                    "system_performance.parameters.battery_saving",
+                    format!("{:#x}", parameters.battery_saving),
+                ),
+            ]);
         }
+
+        if let Some(battery) = &self.battery {
+            let conservation = &battery.conservation.parameters;
+            let rapid_charge = &battery.rapid_charge.parameters;
+            fields.extend([
+                (
+                    "battery.conservation.parameters.enable",
+                    format!("{:#x}", conservation.enable),
+                ),
+                (
+                    "battery.conservation.parameters.disable",
+                    format!("{:#x}", conservation.disable),
+                ),
+                (
+                    "battery.rapid_charge.parameters.enable",
+                    format!("{:#x}", rapid_charge.enable),
+                ),
+                (
+                    "battery.rapid_charge.parameters.disable",
+                    format!("{:#x}", rapid_charge.disable),
+                ),
+            ]);
+        }
+
+        fields
     }
 
+    /// Describe this profile in a human readable block, listing its name, the products it
+    /// supports and the `acpi_call` methods it uses, grouped by feature.
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "Profile: {}\nSupported products: {}\n",
+            self.name,
+            self.expected_product_names.join(", ")
+        );
+
+        description += "\nSystem performance:\n";
+        match &self.system_performance {
+            Some(system_performance) => {
+                let commands = &system_performance.commands;
+                description += &format!("  Set command:     {}\n", commands.set);
+                description += &format!("  Get SPMO bit:    {}\n", commands.get_spmo_bit);
+                description += &format!("  Get FCMO bit:    {}\n", commands.get_fcmo_bit);
+
+                if let Some(capabilities) = &commands.capabilities {
+                    description += &format!("  Capabilities:    {}\n", capabilities);
+                }
+            }
+            None => description += "  Not available\n",
+        }
+
+        description += "\nBattery:\n";
+        match &self.battery {
+            Some(battery) => {
+                description += &format!("  Set command:     {}\n", battery.set_command);
+                description += &format!(
+                    "  Conservation:    {} (enable {:#x}, disable {:#x})\n",
+                    battery.conservation.get_command,
+                    battery.conservation.parameters.enable,
+                    battery.conservation.parameters.disable
+                );
+                description += &format!(
+                    "  Rapid charge:    {} (enable {:#x}, disable {:#x})\n",
+                    battery.rapid_charge.get_command,
+                    battery.rapid_charge.parameters.enable,
+                    battery.rapid_charge.parameters.disable
+                );
+            }
+            None => description += "  Not available\n",
+        }
+
+        description
+    }
+
+    /// The environment variable which forces the profile with that name to be used, skipping
+    /// detection. The profile still has to be in the [default search path](Self::default_search_path).
+    pub const ENV_OVERRIDE: &'static str = "IDEAPAD_PROFILE";
+
     /// Default search path for profiles.
     pub const SEARCH_PATH: &'static [Self] = &[
         #[cfg(feature = "ideapad_15iil05")]
@@ -546,9 +1320,131 @@ impl Profile {
         Self::IDEAPAD_AMD,
     ];
 
-    /// Find the appropriate profile with the default search path.
+    /// Register an additional profile, which is searched after the
+    /// [`SEARCH_PATH`](Self::SEARCH_PATH) by [`Self::find`] and everything which uses it, such as
+    /// [`Context::try_default`].
+    ///
+    /// This allows profiles to be shipped in other crates without changing this one. Registered
+    /// profiles live for the rest of the program.
+    ///
+    /// [`Context::try_default`]: crate::context::Context::try_default
+    pub fn register(profile: Self) {
+        REGISTERED
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(profile))
+    }
+
+    /// Get every profile which was [registered](Self::register), in the order they were
+    /// registered.
+    pub fn registered() -> Vec<Self> {
+        REGISTERED
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|profile| Self::clone(profile))
+            .collect()
+    }
+
+    /// Get the name and expected product names of every profile in the
+    /// [default search path](Self::default_search_path), for example to generate a compatibility
+    /// matrix.
+    pub fn catalog() -> impl Iterator<Item = (Cow<'static, str>, Cow<'static, [Cow<'static, str>]>)>
+    {
+        let registered = REGISTERED
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        let entry =
+            |profile: &Profile| (profile.name.clone(), profile.expected_product_names.clone());
+
+        Self::SEARCH_PATH
+            .iter()
+            .map(entry)
+            .chain(registered.into_iter().map(move |profile| entry(&profile)))
+    }
+
+    /// Get the default search path, which is the [`SEARCH_PATH`](Self::SEARCH_PATH) followed by
+    /// the [registered](Self::register) profiles.
+    pub fn default_search_path() -> Vec<Self> {
+        Self::SEARCH_PATH
+            .iter()
+            .cloned()
+            .chain(Self::registered())
+            .collect()
+    }
+
+    /// Check whether any profile in the [default search path](Self::default_search_path) supports
+    /// the product name, for example `81YQ`.
+    pub fn supports_product(name: &str) -> bool {
+        Self::find_product_name(Self::default_search_path(), name).is_ok()
+    }
+
+    /// Find the appropriate profile with the [default search path](Self::default_search_path).
+    ///
+    /// If the [`ENV_OVERRIDE`](Self::ENV_OVERRIDE) environment variable is set, the profile with
+    /// that name is returned instead, or an [`Error::UnknownProfile`] if there is none.
     pub fn find() -> Result<Self> {
-        Self::find_with_search_path(Self::SEARCH_PATH.iter().cloned())
+        match Self::forced() {
+            Some(profile) => profile,
+            None => Self::find_with_search_path(Self::default_search_path()),
+        }
+    }
+
+    /// Like [`Self::find`], but the profile which was found is cached for the rest of the
+    /// program, so detecting it again is instant. This is what [`Context::try_default`] uses.
+    ///
+    /// The cache assumes that the model of the laptop doesn't change while the program is
+    /// running. Use [`Self::clear_profile_cache`] to force the profile to be detected again.
+    ///
+    /// [`Context::try_default`]: crate::context::Context::try_default
+    pub fn find_cached() -> Result<Self> {
+        Self::find_cached_with(Self::find)
+    }
+
+    /// Clear the profile cached by [`Self::find_cached`], along with the cached
+    /// [path style](crate::Context::path_style), so that both are detected again.
+    pub fn clear_profile_cache() {
+        *CACHED.lock().unwrap_or_else(PoisonError::into_inner) = None;
+
+        #[cfg(any(
+            feature = "battery_conservation",
+            feature = "rapid_charge",
+            feature = "system_performance"
+        ))]
+        {
+            *CACHED_PATH_STYLE
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = None;
+        }
+    }
+
+    fn find_cached_with(find: impl FnOnce() -> Result<Self>) -> Result<Self> {
+        let cached = || CACHED.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(profile) = &*cached() {
+            return Ok(profile.clone());
+        }
+
+        // the lock isn't held while detecting, as that could take a while, so clearing the cache
+        // doesn't have to wait for it. if another thread detected the profile in the meantime,
+        // theirs is kept
+        let profile = find()?;
+        Ok(cached().get_or_insert(profile).clone())
+    }
+
+    /// Get the profile which is forced through the [`ENV_OVERRIDE`](Self::ENV_OVERRIDE)
+    /// environment variable, if it is set.
+    fn forced() -> Option<Result<Self>> {
+        let name = env::var(Self::ENV_OVERRIDE).ok()?;
+
+        Some(
+            Self::default_search_path()
+                .into_iter()
+                .find(|profile| profile.name == name)
+                .ok_or(Error::UnknownProfile { name }),
+        )
     }
 
     /// Find the appropriate profile with the specified search path.
@@ -560,19 +1456,616 @@ impl Profile {
     /// If this laptop's model's product name couldn't be found in the search path given, a
     /// [`Error::NoValidProfileInSearchPath`] is returned.
     pub fn find_with_search_path(search_path: impl IntoIterator<Item = Self>) -> Result<Self> {
-        let product_name = smbioslib::table_load_from_device()?
-            .find_map(|system: SMBiosSystemInformation| system.product_name())
-            .ok_or(Error::UnableToFindSystemInformation)?;
+        Self::find_product_name(search_path, &product_name()?)
+    }
 
-        search_path
-            .into_iter()
-            .find(|profile| {
-                profile
-                    .expected_product_names
-                    .contains(&Cow::Borrowed(product_name.as_str()))
-            })
-            .ok_or(Error::NoValidProfileInSearchPath)
+    /// Find the appropriate profile with the [default search path](Self::default_search_path),
+    /// giving up if getting the system information takes longer than the timeout.
+    ///
+    /// Reading the SMBIOS can be slow or even hang on some firmware, so detection is run on a
+    /// separate thread. If it times out, that thread is left to finish in the background, as it
+    /// can't be interrupted.
+    ///
+    /// Like [`Self::find`], the [`ENV_OVERRIDE`](Self::ENV_OVERRIDE) environment variable takes
+    /// precedence over detection.
+    ///
+    /// # Errors
+    /// If getting the system information took longer than the timeout, an
+    /// [`Error::DetectionTimedOut`] is returned. Otherwise, the same errors as
+    /// [`Self::find_with_search_path`] are returned.
+    pub fn find_with_timeout(timeout: Duration) -> Result<Self> {
+        if let Some(profile) = Self::forced() {
+            return profile;
+        }
+
+        Self::find_product_name(
+            Self::default_search_path(),
+            &product_name_with_timeout(product_name, timeout)?,
+        )
+    }
+
+    fn find_product_name(
+        search_path: impl IntoIterator<Item = Self>,
+        product_name: &str,
+    ) -> Result<Self> {
+        let mut considered = Vec::new();
+
+        for profile in search_path {
+            if profile
+                .expected_product_names
+                .contains(&Cow::Borrowed(product_name))
+            {
+                return Ok(profile);
+            }
+
+            considered.push(profile.name.to_string());
+        }
+
+        Err(Error::NoValidProfileInSearchPath {
+            detected_product: product_name.to_string(),
+            considered,
+        })
+    }
+}
+
+fn product_name() -> Result<String> {
+    smbioslib::table_load_from_device()?
+        .find_map(|system: SMBiosSystemInformation| system.product_name())
+        .ok_or(Error::UnableToFindSystemInformation)
+}
+
+fn product_name_with_timeout(detect: fn() -> Result<String>, timeout: Duration) -> Result<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        // the receiver is gone if detection timed out, in which case nobody cares about the result
+        let _ = sender.send(detect());
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(product_name) => product_name,
+        Err(RecvTimeoutError::Timeout) => Err(Error::DetectionTimedOut { timeout }),
+        Err(RecvTimeoutError::Disconnected) => Err(Error::UnableToFindSystemInformation),
+    }
+}
+
+/// Like [`Profile::find_cached`], but for the [path style](crate::Context::path_style) which the
+/// `acpi_call` kernel module accepts, so that it is only probed once. Only a successfully detected
+/// style is cached. It is cleared by [`Profile::clear_profile_cache`].
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+pub(crate) fn find_cached_path_style(
+    detect: impl FnOnce() -> acpi_call::Result<PathStyle>,
+) -> acpi_call::Result<PathStyle> {
+    let cached = || {
+        CACHED_PATH_STYLE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    };
+
+    if let Some(style) = *cached() {
+        return Ok(style);
+    }
+
+    let style = detect()?;
+    Ok(*cached().get_or_insert(style))
+}
+
+/// Format a bit like it is written in a profile, as either one value or an spmo and fcmo value.
+pub(crate) fn format_bit(bit: Bit) -> String {
+    match bit.inner() {
+        BitInner::Same(value) => format!("{:#x}", value),
+        BitInner::Different { spmo, fcmo } => format!("spmo {:#x}, fcmo {:#x}", spmo, fcmo),
     }
 }
 
-mod tests {}
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "ideapad_amd", feature = "toml"))]
+    use crate::profile::Profile;
+    use crate::profile::{
+        product_name_with_timeout, Error, SharedBatteryConfiguration,
+        SharedBatteryConfigurationParameters,
+    };
+    #[cfg(feature = "serde")]
+    use crate::profile::{Bit, SystemPerformanceBits};
+    #[cfg(feature = "ideapad_amd")]
+    use crate::profile::{Capabilities, ConservationThreshold, ProfileIssue};
+    #[cfg(all(feature = "core", feature = "ideapad_amd"))]
+    use crate::{
+        acpi_call::{self, PathStyle},
+        profile::find_cached_path_style,
+    };
+    #[cfg(feature = "system_performance")]
+    use crate::{profile::SystemPerformanceParameters, system_performance::SystemPerformanceMode};
+    #[cfg(feature = "ideapad_amd")]
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    #[cfg(feature = "system_performance")]
+    fn test_value_of() {
+        let parameters = SystemPerformanceParameters::SHARED;
+
+        assert_eq!(
+            parameters.value_of(SystemPerformanceMode::IntelligentCooling),
+            0x000FB001
+        );
+        assert_eq!(
+            parameters.value_of(SystemPerformanceMode::ExtremePerformance),
+            0x0012B001
+        );
+        assert_eq!(
+            parameters.value_of(SystemPerformanceMode::BatterySaving),
+            0x0013B001
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_describe() {
+        let description = Profile::IDEAPAD_AMD.describe();
+
+        assert!(description.contains("IDEAPAD_AMD"));
+        assert!(description.contains("81YQ, 81YM"));
+        assert!(description.contains(r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC"#));
+        assert!(description.contains(r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC"#));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ideapad_15iil05", feature = "ideapad_amd"))]
+    fn test_diff() {
+        let diff = Profile::IDEAPAD_15IIL05.diff(&Profile::IDEAPAD_AMD);
+
+        assert_eq!(
+            diff.iter().map(|diff| diff.field).collect::<Vec<_>>(),
+            [
+                "system_performance.commands.set",
+                "system_performance.commands.get_fcmo_bit",
+                "system_performance.commands.get_spmo_bit",
+                "battery.set_command",
+                "battery.conservation.get_command",
+                "battery.rapid_charge.get_command",
+            ]
+        );
+
+        for diff in diff {
+            let left = diff.left.expect("missing left value");
+            let right = diff.right.expect("missing right value");
+
+            assert!(left.contains(".LPCB."));
+            assert_eq!(left.replace(".LPCB.", ".LPC0."), right);
+        }
+
+        assert!(Profile::IDEAPAD_AMD.diff(&Profile::IDEAPAD_AMD).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_validate_structure_same_enable_disable() {
+        assert_eq!(Profile::IDEAPAD_AMD.validate_structure(), Ok(()));
+
+        let mut profile = Profile::IDEAPAD_AMD;
+        let battery = profile
+            .battery
+            .as_mut()
+            .expect("profile has no battery section");
+        battery.rapid_charge.parameters.disable = battery.rapid_charge.parameters.enable;
+
+        assert_eq!(
+            profile.validate_structure(),
+            Err(vec![ProfileIssue::SameEnableDisable {
+                field: "battery.rapid_charge.parameters",
+                parameter: 0x07,
+            }])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_validate_structure_empty_threshold_command() {
+        let mut profile = Profile::IDEAPAD_AMD;
+        let battery = profile
+            .battery
+            .take()
+            .expect("profile has no battery section")
+            .with_conservation_threshold(ConservationThreshold::r#static(" ", &[60, 80]));
+        profile.battery = Some(battery);
+
+        assert!(matches!(
+            profile.validate(),
+            Err(Error::EmptyCommand {
+                field: "battery.conservation_threshold.set_command"
+            })
+        ));
+        assert_eq!(
+            profile.validate_structure(),
+            Err(vec![ProfileIssue::EmptyCommand {
+                field: "battery.conservation_threshold.set_command",
+            }])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_validate_structure_duplicate_performance_parameters() {
+        let mut profile = Profile::IDEAPAD_AMD;
+        let parameters = &mut profile
+            .system_performance
+            .as_mut()
+            .expect("profile has no system performance section")
+            .parameters;
+        parameters.battery_saving = parameters.intelligent_cooling;
+
+        assert_eq!(
+            profile.validate_structure(),
+            Err(vec![ProfileIssue::DuplicatePerformanceParameter {
+                first: "system_performance.parameters.intelligent_cooling",
+                second: "system_performance.parameters.battery_saving",
+                parameter: 0x000FB001,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_param_for() {
+        let conservation = SharedBatteryConfiguration::r#static(
+            "",
+            SharedBatteryConfigurationParameters::CONSERVATION_SHARED,
+        );
+        let rapid_charge = SharedBatteryConfiguration::r#static(
+            "",
+            SharedBatteryConfigurationParameters::RAPID_CHARGE_SHARED,
+        );
+
+        assert_eq!(conservation.param_for(true), 0x03);
+        assert_eq!(conservation.param_for(false), 0x05);
+        assert_eq!(rapid_charge.param_for(true), 0x07);
+        assert_eq!(rapid_charge.param_for(false), 0x08);
+    }
+
+    #[test]
+    fn test_describe_battery_parameter() {
+        let conservation = SharedBatteryConfigurationParameters::CONSERVATION_SHARED;
+        let rapid_charge = SharedBatteryConfigurationParameters::RAPID_CHARGE_SHARED;
+
+        assert_eq!(conservation.describe(0x03), "enable");
+        assert_eq!(conservation.describe(0x05), "disable");
+        assert_eq!(conservation.describe(0x07), "unknown");
+        assert_eq!(rapid_charge.describe(0x07), "enable");
+        assert_eq!(rapid_charge.describe(0x08), "disable");
+        assert_eq!(rapid_charge.describe(0x03), "unknown");
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_capabilities() {
+        assert_eq!(
+            Profile::IDEAPAD_AMD.capabilities(),
+            Capabilities {
+                has_conservation: true,
+                has_rapid_charge: true,
+                has_system_performance: true,
+            }
+        );
+
+        let mut profile = Profile::IDEAPAD_AMD;
+        profile.system_performance = None;
+        assert!(!profile.capabilities().has_system_performance);
+    }
+
+    /// Serialize the AMD profile to TOML, after changing it with `edit`.
+    #[cfg(all(feature = "toml", feature = "ideapad_amd"))]
+    fn edited_toml(edit: impl FnOnce(&mut toml::Table)) -> String {
+        let mut table = toml::Table::try_from(Profile::IDEAPAD_AMD).expect("failed to serialize");
+        edit(&mut table);
+
+        toml::to_string(&table).expect("failed to serialize")
+    }
+
+    #[test]
+    #[cfg(all(feature = "toml", feature = "ideapad_amd"))]
+    fn test_from_toml_unknown_field() {
+        let toml = edited_toml(|table| {
+            table.remove("schema_version");
+            table.insert("from_the_future".into(), true.into());
+        });
+
+        assert_eq!(
+            Profile::from_toml(&toml).expect("profile with an unknown field was rejected"),
+            Profile::IDEAPAD_AMD
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "toml", feature = "ideapad_amd"))]
+    fn test_from_toml_missing_section() {
+        let toml = edited_toml(|table| {
+            table.remove("system_performance");
+        });
+        let profile = Profile::from_toml(&toml).expect("battery only profile was rejected");
+
+        assert_eq!(profile.system_performance, None);
+        assert_eq!(profile.battery, Profile::IDEAPAD_AMD.battery);
+        assert!(profile
+            .describe()
+            .contains("System performance:\n  Not available\n"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "toml", feature = "ideapad_amd"))]
+    fn test_from_toml_unsupported_version() {
+        let toml = edited_toml(|table| {
+            table.insert("schema_version".into(), 99.into());
+            table.remove("battery");
+        });
+
+        assert!(matches!(
+            Profile::from_toml(&toml),
+            Err(Error::UnsupportedProfileVersion {
+                version: 99,
+                supported: Profile::SCHEMA_VERSION,
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_from_toml_empty_set_command() {
+        let error = Profile::from_toml(
+            r#"
+            name = "CUSTOM"
+            expected_product_names = ["81YM"]
+
+            [system_performance.commands]
+            set = '\_SB.PCI0.LPC0.EC0.VPC0.DYTC'
+            get_fcmo_bit = '\_SB.PCI0.LPC0.EC0.FCMO'
+            get_spmo_bit = '\_SB.PCI0.LPC0.EC0.SPMO'
+
+            [system_performance.bits]
+            intelligent_cooling = 0
+            extreme_performance = 1
+            battery_saving = 2
+
+            [system_performance.parameters]
+            intelligent_cooling = 1028097
+            extreme_performance = 1224705
+            battery_saving = 1290241
+
+            [battery]
+            set_command = ""
+
+            [battery.conservation]
+            get_command = '\_SB.PCI0.LPC0.EC0.BTSM'
+            parameters = { enable = 3, disable = 5 }
+
+            [battery.rapid_charge]
+            get_command = '\_SB.PCI0.LPC0.EC0.QCHO'
+            parameters = { enable = 7, disable = 8 }
+            "#,
+        )
+        .expect_err("profile with an empty set command was accepted");
+
+        assert!(matches!(
+            error,
+            Error::EmptyCommand {
+                field: "battery.set_command"
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ideapad_15iil05", feature = "ideapad_amd"))]
+    fn test_find_no_match() {
+        let error =
+            Profile::find_product_name([Profile::IDEAPAD_15IIL05, Profile::IDEAPAD_AMD], "81ZZ")
+                .expect_err("a profile supports the product");
+
+        assert_eq!(
+            error.to_string(),
+            "detected product name '81ZZ', but none of the profiles [IDEAPAD_15IIL05, IDEAPAD_AMD] support it"
+        );
+        assert!(matches!(
+            error,
+            Error::NoValidProfileInSearchPath { detected_product, considered }
+                if detected_product == "81ZZ"
+                    && considered == ["IDEAPAD_15IIL05", "IDEAPAD_AMD"]
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_supports_product() {
+        assert!(Profile::supports_product("81YQ"));
+        assert!(!Profile::supports_product("20XW"));
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_register() {
+        let profile = Profile::new(
+            "REGISTERED",
+            ["TEST-REGISTER"],
+            Profile::IDEAPAD_AMD.system_performance.unwrap(),
+            Profile::IDEAPAD_AMD.battery.unwrap(),
+        );
+        Profile::register(profile.clone());
+
+        let search_path = Profile::default_search_path();
+        assert_eq!(search_path.last(), Some(&profile));
+        assert_eq!(
+            Profile::find_product_name(search_path, "TEST-REGISTER")
+                .expect("registered profile wasn't found"),
+            profile
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_catalog() {
+        assert!(Profile::catalog().any(
+            |(name, product_names)| name == "IDEAPAD_AMD" && *product_names == ["81YQ", "81YM"]
+        ));
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_detect_profile_env_override() {
+        std::env::set_var(Profile::ENV_OVERRIDE, "IDEAPAD_AMD");
+        let forced = crate::detect_profile();
+        std::env::set_var(Profile::ENV_OVERRIDE, "IDEAPAD_NONEXISTENT");
+        let unknown = crate::detect_profile();
+        std::env::remove_var(Profile::ENV_OVERRIDE);
+
+        assert_eq!(
+            forced.expect("forced profile wasn't found"),
+            Profile::IDEAPAD_AMD
+        );
+        assert!(matches!(
+            unknown,
+            Err(Error::UnknownProfile { name }) if name == "IDEAPAD_NONEXISTENT"
+        ));
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(feature = "ideapad_amd")]
+    fn test_find_cached() {
+        let detections = AtomicUsize::new(0);
+        let detect = || {
+            detections.fetch_add(1, Ordering::SeqCst);
+            Ok(Profile::IDEAPAD_AMD)
+        };
+
+        Profile::clear_profile_cache();
+        let first = Profile::find_cached_with(detect).expect("failed to detect profile");
+        let second = Profile::find_cached_with(detect).expect("failed to detect profile");
+        assert_eq!(first, second);
+        assert_eq!(detections.load(Ordering::SeqCst), 1);
+
+        Profile::clear_profile_cache();
+        Profile::find_cached_with(detect).expect("failed to detect profile");
+        assert_eq!(detections.load(Ordering::SeqCst), 2);
+
+        // the cache isn't locked while detecting
+        Profile::clear_profile_cache();
+        Profile::find_cached_with(|| {
+            Profile::clear_profile_cache();
+            detect()
+        })
+        .expect("failed to detect profile");
+        assert_eq!(detections.load(Ordering::SeqCst), 3);
+        Profile::clear_profile_cache();
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(all(feature = "core", feature = "ideapad_amd"))]
+    fn test_find_cached_path_style() {
+        let detections = AtomicUsize::new(0);
+        let detect = || {
+            detections.fetch_add(1, Ordering::SeqCst);
+            Ok(PathStyle::Padded)
+        };
+
+        Profile::clear_profile_cache();
+        assert!(find_cached_path_style(|| Err(acpi_call::Error::ReadOnly)).is_err());
+        assert_eq!(find_cached_path_style(detect).unwrap(), PathStyle::Padded);
+        assert_eq!(find_cached_path_style(detect).unwrap(), PathStyle::Padded);
+        assert_eq!(detections.load(Ordering::SeqCst), 1);
+
+        Profile::clear_profile_cache();
+        find_cached_path_style(detect).expect("failed to detect path style");
+        assert_eq!(detections.load(Ordering::SeqCst), 2);
+        Profile::clear_profile_cache();
+    }
+
+    #[test]
+    fn test_detection_timed_out() {
+        let slow_detector = || {
+            thread::sleep(Duration::from_secs(5));
+            Ok("81YM".to_string())
+        };
+
+        assert!(matches!(
+            product_name_with_timeout(slow_detector, Duration::from_millis(10)),
+            Err(Error::DetectionTimedOut { timeout }) if timeout == Duration::from_millis(10)
+        ));
+        assert_eq!(
+            product_name_with_timeout(|| Ok("81YM".to_string()), Duration::from_secs(5))
+                .expect("detection timed out"),
+            "81YM"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_deserialize_system_performance_bits() {
+        let bits: SystemPerformanceBits = toml::from_str(
+            r#"
+            intelligent_cooling = 0
+            extreme_performance = { spmo = 1, fcmo = 3 }
+            battery_saving = { spmo = 2, fcmo = 2 }
+            "#,
+        )
+        .expect("failed to deserialize system performance bits");
+
+        assert_eq!(
+            bits,
+            SystemPerformanceBits::new(Bit::same(0), Bit::different(1, 3), Bit::same(2))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_deserialize_hex_parameters() {
+        let decimal: SharedBatteryConfigurationParameters =
+            toml::from_str("enable = 3\ndisable = 5").expect("failed to deserialize integers");
+        let hex: SharedBatteryConfigurationParameters = toml::from_str(
+            r#"enable = "0x03"
+disable = "0X05""#,
+        )
+        .expect("failed to deserialize hexadecimal strings");
+
+        assert_eq!(decimal, SharedBatteryConfigurationParameters::new(3, 5));
+        assert_eq!(hex, decimal);
+
+        let bits: SystemPerformanceBits = toml::from_str(
+            r#"
+            intelligent_cooling = "0x0"
+            extreme_performance = { spmo = "0x1", fcmo = 3 }
+            battery_saving = "2"
+            "#,
+        )
+        .expect("failed to deserialize system performance bits");
+        assert_eq!(
+            bits,
+            SystemPerformanceBits::new(Bit::same(0), Bit::different(1, 3), Bit::same(2))
+        );
+
+        assert!(toml::from_str::<SharedBatteryConfigurationParameters>(
+            r#"enable = "0xZZ"
+disable = 5"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serialize_system_performance_bits_round_trip() {
+        let bits = SystemPerformanceBits::new(Bit::same(0), Bit::different(1, 3), Bit::same(2));
+        let serialized = toml::to_string(&bits).expect("failed to serialize bits");
+
+        assert!(serialized.contains("intelligent_cooling = 0"));
+        assert_eq!(
+            toml::from_str::<SystemPerformanceBits>(&serialized)
+                .expect("failed to deserialize bits"),
+            bits
+        );
+    }
+}
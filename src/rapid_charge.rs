@@ -2,12 +2,17 @@
 //!
 //! Rapid charge charges your battery faster somehow.
 
-use crate::acpi_call::{self, acpi_call, acpi_call_expect_valid};
+use crate::acpi_call;
 use crate::battery::enable::{Begin, EnableBuilder};
-use crate::battery::{BatteryController, BatteryEnableGuard};
+use crate::battery::{
+    AppliedHandler, BatteryController, BatteryEnableGuard, BatteryMode, ConflictResolver,
+    Resolution,
+};
 use crate::battery_conservation::BatteryConservationDisableGuardInner;
 use crate::context::Context;
+use crate::power_supply::PowerSupply;
 use crate::Handler;
+use std::{io, mem};
 use thiserror::Error;
 use try_drop::prelude::*;
 use try_drop::{DropAdapter, GlobalFallbackTryDropStrategyHandler, GlobalTryDropStrategyHandler};
@@ -30,6 +35,50 @@ pub enum Error {
     /// enabled.
     #[error("battery conservation is enabled, disable it before enabling rapid charge")]
     BatteryConservationEnabled,
+
+    /// Occurs when you try to enable rapid charge while the battery is hotter than the threshold
+    /// of the [`TemperatureLimit`].
+    #[error("battery is too hot ({temp_c}°C) to enable rapid charge")]
+    TooHotForRapidCharge {
+        /// The temperature of the battery in degrees celsius.
+        temp_c: f32,
+    },
+
+    /// An error occurred when reading from the power supply.
+    #[error("{error}")]
+    PowerSupply {
+        /// The underlying error itself.
+        #[from]
+        error: io::Error,
+    },
+}
+
+/// Refuses to enable rapid charge when the battery is too hot.
+///
+/// This check is opt-in; see [`Context::temperature_limit`].
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TemperatureLimit {
+    /// The battery temperature in degrees celsius above which rapid charge won't be enabled.
+    pub threshold: f32,
+}
+
+impl TemperatureLimit {
+    /// Create a new temperature limit with the specified threshold in degrees celsius.
+    pub const fn new(threshold: f32) -> Self {
+        Self { threshold }
+    }
+
+    /// Check the battery temperature from the power supply against the threshold.
+    pub fn check(&self, power_supply: &dyn PowerSupply) -> Result<()> {
+        let temp_c = power_supply.battery_temperature()?;
+
+        if temp_c > self.threshold {
+            Err(Error::TooHotForRapidCharge { temp_c })
+        } else {
+            Ok(())
+        }
+    }
 }
 
 /// Builder for enabling rapid charge.
@@ -49,6 +98,9 @@ where
 
 /// Guarantees that rapid charge is enabled for the scope
 /// (excluding external access to `/proc/acpi/call`).
+///
+/// Rapid charge is disabled when the guard is dropped, see [nesting guards](crate::battery#guards).
+/// Use [`RapidChargeEnableGuard::disarm`] to leave rapid charge enabled instead.
 pub struct RapidChargeEnableGuard<
     'rc,
     'ctx,
@@ -79,7 +131,20 @@ where
     }
 
     unsafe fn try_drop(&mut self) -> Result<(), Self::Error> {
-        self.controller.disable()
+        let context = self.controller.context;
+        context.record_drop_error("RapidChargeController::disable", self.controller.disable())
+    }
+}
+
+impl<'rc, 'ctx, D, DD> RapidChargeEnableGuard<'rc, 'ctx, D, DD>
+where
+    'ctx: 'rc,
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// Consume the guard without disabling rapid charge.
+    pub fn disarm(self) {
+        mem::forget(self)
     }
 }
 
@@ -134,23 +199,20 @@ where
 
     /// Disable rapid charge.
     pub fn disable(&mut self) -> acpi_call::Result<()> {
-        acpi_call(
-            self.context.profile.battery.set_command.to_string(),
-            [self.context.profile.battery.rapid_charge.parameters.disable],
-        )?;
+        self.context.ensure_writable()?;
+
+        let battery = self.context.battery_profile()?;
+        let parameter = battery.rapid_charge.param_for(false);
+        self.context.acpi_call(&battery.set_command, [parameter])?;
 
-        Ok(())
+        self.context
+            .verify_write(&battery.set_command, [parameter], false, || self.get())
     }
 
     /// Get the rapid charge status.
     pub fn get(&self) -> acpi_call::Result<bool> {
-        let output = acpi_call_expect_valid(
-            self.context
-                .profile
-                .battery
-                .rapid_charge
-                .get_command
-                .to_string(),
+        let output = self.context.acpi_call_expect_valid(
+            &self.context.battery_profile()?.rapid_charge.get_command,
             [],
         )?;
 
@@ -166,6 +228,42 @@ where
     pub fn disabled(&self) -> acpi_call::Result<bool> {
         self.get().map(|enabled| !enabled)
     }
+
+    /// Check whether enabling or disabling rapid charge would actually change anything, without
+    /// changing it.
+    pub fn would_change(&self, enabled: bool) -> acpi_call::Result<bool> {
+        self.get().map(|current| current != enabled)
+    }
+
+    /// Check the battery temperature if a [`TemperatureLimit`] is configured in the context.
+    pub fn check_temperature(&self) -> Result<()> {
+        match &self.context.temperature_limit {
+            Some(limit) => limit.check(self.context.power_supply()),
+            None => Ok(()),
+        }
+    }
+
+    /// Check whether enabling rapid charge would do anything right now, which is only the case
+    /// while the laptop is plugged in and the battery isn't full yet.
+    ///
+    /// # Errors
+    /// If the power supplies couldn't be read, an [`Error::PowerSupply`] is returned.
+    pub fn is_applicable(&self) -> Result<bool> {
+        let power_supply = self.context.power_supply();
+
+        Ok(power_supply.ac_online()? && power_supply.battery_capacity()? < 100)
+    }
+
+    fn enable_unchecked(&mut self) -> acpi_call::Result<()> {
+        self.context.ensure_writable()?;
+
+        let battery = self.context.battery_profile()?;
+        let parameter = battery.rapid_charge.param_for(true);
+        self.context.acpi_call(&battery.set_command, [parameter])?;
+
+        self.context
+            .verify_write(&battery.set_command, [parameter], true, || self.get())
+    }
 }
 
 impl<'this, 'ctx, D, DD> BatteryController<'this, 'ctx> for RapidChargeController<'ctx, D, DD>
@@ -177,36 +275,45 @@ where
     type EnableGuard = RapidChargeEnableGuard<'this, 'ctx, D, DD>;
     type Error = Error;
 
-    fn enable_ignore(&mut self) -> acpi_call::Result<()> {
-        acpi_call(
-            self.context.profile.battery.set_command.to_string(),
-            [self.context.profile.battery.rapid_charge.parameters.enable],
-        )?;
+    fn enable_ignore(&mut self) -> std::result::Result<AppliedHandler, Self::Error> {
+        self.check_temperature()?;
+        self.enable_unchecked()?;
 
-        Ok(())
+        Ok(AppliedHandler::Ignored)
     }
 
-    fn enable_error(&mut self) -> std::result::Result<(), Self::Error> {
-        if self
-            .context
-            .controllers()
-            .battery_conservation()
-            .enabled()?
-        {
-            Err(Error::BatteryConservationEnabled)
-        } else {
-            self.enable_ignore().map_err(Into::into)
-        }
+    fn enable_error(&mut self) -> std::result::Result<AppliedHandler, Self::Error> {
+        self.enable_with_resolver(&Handler::Error)
+    }
+
+    fn enable_switch(&mut self) -> std::result::Result<AppliedHandler, Self::Error> {
+        self.enable_with_resolver(&Handler::Switch)
     }
 
-    fn enable_switch(&mut self) -> acpi_call::Result<()> {
+    fn enable_with_resolver(
+        &mut self,
+        resolver: &dyn ConflictResolver,
+    ) -> std::result::Result<AppliedHandler, Self::Error> {
+        self.check_temperature()?;
+
         let mut battery_conservation = self.context.controllers().battery_conservation();
+        let mut applied = AppliedHandler::NoConflict;
 
         if battery_conservation.enabled()? {
-            battery_conservation.disable()?
+            match resolver.resolve(BatteryMode::Conservation) {
+                Resolution::Proceed => applied = AppliedHandler::Ignored,
+                Resolution::Abort => return Err(Error::BatteryConservationEnabled),
+                Resolution::SwitchOff => {
+                    battery_conservation.disable()?;
+                    self.context.notify_conflict(BatteryMode::Conservation);
+                    applied = AppliedHandler::SwitchedOff(BatteryMode::Conservation);
+                }
+            }
         }
 
-        self.enable_ignore()
+        self.enable_unchecked()?;
+
+        Ok(applied)
     }
 }
 
@@ -218,7 +325,13 @@ where
     D: FallibleTryDropStrategy,
     DD: FallbackTryDropStrategy,
 {
-    context.controllers().rapid_charge().enable().switch().now()
+    context
+        .controllers()
+        .rapid_charge()
+        .enable()
+        .switch()
+        .now()
+        .map(|_| ())
 }
 
 /// Disable rapid charge.
@@ -259,6 +372,110 @@ where
 
 #[cfg(test)]
 mod tests {
+    use crate::mock::{MockBackend, MockPowerSupply};
+    use crate::rapid_charge::{Error, TemperatureLimit};
+    use crate::{Context, Profile};
+    use std::sync::Arc;
+
+    fn limited_context(temperature: f32) -> Context {
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.power_supply = Some(Arc::new(MockPowerSupply {
+            temperature,
+            ..Default::default()
+        }));
+        context.temperature_limit = Some(TemperatureLimit::new(45.0));
+        context
+    }
+
+    #[test]
+    fn test_temperature_limit_below_threshold() {
+        let context = limited_context(30.5);
+
+        context
+            .controllers()
+            .rapid_charge()
+            .check_temperature()
+            .expect("temperature below the threshold was refused");
+    }
+
+    #[test]
+    fn test_temperature_limit_above_threshold() {
+        let context = limited_context(50.0);
+        let error = context
+            .controllers()
+            .rapid_charge()
+            .enable()
+            .ignore()
+            .now()
+            .expect_err("rapid charge enable succeeded");
+
+        assert!(matches!(
+            error,
+            Error::TooHotForRapidCharge { temp_c } if temp_c == 50.0
+        ));
+    }
+
+    #[test]
+    fn test_is_applicable_on_ac_not_full() {
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.power_supply = Some(Arc::new(MockPowerSupply::default()));
+
+        assert!(context
+            .controllers()
+            .rapid_charge()
+            .is_applicable()
+            .expect("failed to check if rapid charge is applicable"));
+    }
+
+    #[test]
+    fn test_is_applicable_on_battery() {
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.power_supply = Some(Arc::new(MockPowerSupply {
+            ac_online: false,
+            ..Default::default()
+        }));
+
+        assert!(!context
+            .controllers()
+            .rapid_charge()
+            .is_applicable()
+            .expect("failed to check if rapid charge is applicable"));
+    }
+
+    #[test]
+    fn test_enable_guard_disarm() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut rapid_charge = context.controllers().rapid_charge();
+
+        rapid_charge
+            .enable()
+            .ignore()
+            .guard()
+            .expect("failed to enable rapid charge")
+            .disarm();
+
+        assert!(backend.rapid_charge());
+        assert_eq!(backend.calls(), [r#"\_SB.PCI0.LPC0.EC0.VPC0.SBMC 7"#]);
+    }
+
+    #[test]
+    fn test_would_change() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let rapid_charge = context.controllers().rapid_charge();
+
+        assert!(rapid_charge
+            .would_change(true)
+            .expect("failed to check rapid charge"));
+        assert!(!rapid_charge
+            .would_change(false)
+            .expect("failed to check rapid charge"));
+        assert!(!backend.rapid_charge());
+    }
+
     #[cfg(test)]
     fn test_enable_with_handler() {
         todo!()
@@ -0,0 +1,418 @@
+//! A read-only self test of everything this crate needs, which is useful to see why this crate
+//! doesn't work on a laptop.
+
+use crate::context::Context;
+use crate::profile::{format_bit, Profile, SharedBatteryConfiguration};
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+use std::fmt::Display;
+use try_drop::prelude::*;
+
+/// The outcome of a single probe of the self test.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SelfTestStatus {
+    /// The probe succeeded.
+    Pass,
+
+    /// The probe failed.
+    Fail,
+
+    /// The probe wasn't run, for example because its feature isn't compiled in.
+    Skip,
+}
+
+/// A single probe of the self test.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SelfTestItem {
+    /// What was probed.
+    pub name: String,
+
+    /// The outcome of the probe.
+    pub status: SelfTestStatus,
+
+    /// What was found, or why the probe failed or was skipped.
+    pub message: String,
+
+    /// The `acpi_call` methods and parameters the profile uses for the probed feature, or [`None`]
+    /// if the probe isn't about a feature or the profile doesn't have it. This is what
+    /// maintainers need to know from a bug report.
+    pub methods: Option<String>,
+}
+
+impl SelfTestItem {
+    fn new(name: &str, status: SelfTestStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message: message.into(),
+            methods: None,
+        }
+    }
+
+    fn with_methods(mut self, methods: Option<String>) -> Self {
+        self.methods = methods;
+        self
+    }
+
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    fn from_result<T, E: Display>(
+        name: &str,
+        result: Result<T, E>,
+        describe: impl FnOnce(T) -> String,
+    ) -> Self {
+        match result {
+            Ok(value) => Self::new(name, SelfTestStatus::Pass, describe(value)),
+            Err(error) => Self::new(name, SelfTestStatus::Fail, error.to_string()),
+        }
+    }
+}
+
+/// The results of every probe of the self test. See [`Context::self_test`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SelfTestReport {
+    /// The probes, in the order they were run.
+    pub items: Vec<SelfTestItem>,
+}
+
+impl SelfTestReport {
+    /// Run every probe against the context. Probes only ever read from the device.
+    ///
+    /// The probes are:
+    ///
+    ///  1. Detecting the profile, which should find the profile of the context.
+    ///  2. Checking whether the `acpi_call` backend is available.
+    ///  3. Getting the value of every feature, along with the [methods](SelfTestItem::methods)
+    ///     it uses.
+    pub fn run<D, DD>(context: &Context<D, DD>) -> Self
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        const DETECTION: &str = "profile detection";
+
+        #[allow(unused_mut)]
+        let mut items = vec![match Profile::find() {
+            Ok(profile) if profile.name == context.profile.name => SelfTestItem::new(
+                DETECTION,
+                SelfTestStatus::Pass,
+                format!("detected profile '{}'", profile.name),
+            ),
+            Ok(profile) => SelfTestItem::new(
+                DETECTION,
+                SelfTestStatus::Fail,
+                format!(
+                    "detected profile '{}', but the context uses '{}'",
+                    profile.name, context.profile.name
+                ),
+            ),
+            Err(error) => SelfTestItem::new(DETECTION, SelfTestStatus::Fail, error.to_string()),
+        }];
+
+        #[cfg(any(
+            feature = "battery_conservation",
+            feature = "rapid_charge",
+            feature = "system_performance"
+        ))]
+        items.push(SelfTestItem::from_result(
+            "acpi_call",
+            context.backend().available(),
+            |_| "backend is available".to_string(),
+        ));
+
+        #[cfg(feature = "system_performance")]
+        let item = SelfTestItem::from_result(
+            "system performance",
+            context.controllers().system_performance().get(),
+            |mode| format!("mode is {:?}", mode),
+        );
+
+        #[cfg(not(feature = "system_performance"))]
+        let item = SelfTestItem::new(
+            "system performance",
+            SelfTestStatus::Skip,
+            "`system_performance` feature is disabled",
+        );
+
+        items.push(item.with_methods(system_performance_methods(&context.profile)));
+
+        #[cfg(feature = "battery_conservation")]
+        let item = SelfTestItem::from_result(
+            "battery conservation",
+            context.controllers().battery_conservation().get(),
+            enabled,
+        );
+
+        #[cfg(not(feature = "battery_conservation"))]
+        let item = SelfTestItem::new(
+            "battery conservation",
+            SelfTestStatus::Skip,
+            "`battery_conservation` feature is disabled",
+        );
+
+        items.push(
+            item.with_methods(
+                context
+                    .profile
+                    .battery
+                    .as_ref()
+                    .map(|battery| battery_methods(&battery.set_command, &battery.conservation)),
+            ),
+        );
+
+        #[cfg(feature = "rapid_charge")]
+        let item = SelfTestItem::from_result(
+            "rapid charge",
+            context.controllers().rapid_charge().get(),
+            enabled,
+        );
+
+        #[cfg(not(feature = "rapid_charge"))]
+        let item = SelfTestItem::new(
+            "rapid charge",
+            SelfTestStatus::Skip,
+            "`rapid_charge` feature is disabled",
+        );
+
+        items.push(
+            item.with_methods(
+                context
+                    .profile
+                    .battery
+                    .as_ref()
+                    .map(|battery| battery_methods(&battery.set_command, &battery.rapid_charge)),
+            ),
+        );
+
+        Self { items }
+    }
+
+    /// Check whether no probe failed. Skipped probes don't count as failures.
+    pub fn passed(&self) -> bool {
+        self.items
+            .iter()
+            .all(|item| item.status != SelfTestStatus::Fail)
+    }
+
+    /// Get the probe with the specified name.
+    pub fn item(&self, name: &str) -> Option<&SelfTestItem> {
+        self.items.iter().find(|item| item.name == name)
+    }
+}
+
+/// Whether a get method of a profile exists on this machine.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct MethodProbe {
+    /// The path of the method in the profile, for example `battery.conservation.get_command`.
+    pub field: &'static str,
+
+    /// The probed method.
+    pub method: String,
+
+    /// Why probing the method failed, or [`None`] if it succeeded.
+    pub error: Option<String>,
+}
+
+/// The results of probing every get method of a profile, which shows whether the profile fits
+/// this machine. See [`Context::try_each_profile`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ValidationReport {
+    /// The probes, in the order the methods appear in the profile.
+    pub probes: Vec<MethodProbe>,
+}
+
+impl ValidationReport {
+    /// Probe every get method of the profile through the backend of the context. Like the
+    /// [self test](SelfTestReport), this only ever reads from the device.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn run<D, DD>(context: &Context<D, DD>, profile: &Profile) -> Self
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        let probes = get_methods(profile)
+            .into_iter()
+            .map(|(field, method)| MethodProbe {
+                field,
+                method: method.to_string(),
+                error: context
+                    .acpi_call(method, [])
+                    .err()
+                    .map(|error| error.to_string()),
+            })
+            .collect();
+
+        Self { probes }
+    }
+
+    /// Check whether the profile has any get methods, and all of them exist on this machine.
+    pub fn is_valid(&self) -> bool {
+        !self.probes.is_empty() && self.probes.iter().all(|probe| probe.error.is_none())
+    }
+}
+
+/// Get every get method of the profile along with the path of its field.
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+fn get_methods(profile: &Profile) -> Vec<(&'static str, &str)> {
+    let mut methods = Vec::new();
+
+    if let Some(system_performance) = &profile.system_performance {
+        let commands = &system_performance.commands;
+        methods.extend([
+            (
+                "system_performance.commands.get_fcmo_bit",
+                &*commands.get_fcmo_bit,
+            ),
+            (
+                "system_performance.commands.get_spmo_bit",
+                &*commands.get_spmo_bit,
+            ),
+        ]);
+    }
+
+    if let Some(battery) = &profile.battery {
+        methods.extend([
+            (
+                "battery.conservation.get_command",
+                &*battery.conservation.get_command,
+            ),
+            (
+                "battery.rapid_charge.get_command",
+                &*battery.rapid_charge.get_command,
+            ),
+        ]);
+    }
+
+    methods
+}
+
+/// Describe the methods, bits and parameters the profile uses for system performance modes.
+fn system_performance_methods(profile: &Profile) -> Option<String> {
+    let system_performance = profile.system_performance.as_ref()?;
+    let commands = &system_performance.commands;
+    let bits = &system_performance.bits;
+    let parameters = &system_performance.parameters;
+
+    Some(format!(
+        "set {} (intelligent cooling {:#x}, extreme performance {:#x}, battery saving {:#x}), \
+         get {} and {} (intelligent cooling {}, extreme performance {}, battery saving {})",
+        commands.set,
+        parameters.intelligent_cooling,
+        parameters.extreme_performance,
+        parameters.battery_saving,
+        commands.get_spmo_bit,
+        commands.get_fcmo_bit,
+        format_bit(bits.intelligent_cooling),
+        format_bit(bits.extreme_performance),
+        format_bit(bits.battery_saving),
+    ))
+}
+
+/// Describe the methods and parameters the profile uses for a battery mode.
+fn battery_methods(set_command: &str, configuration: &SharedBatteryConfiguration) -> String {
+    format!(
+        "set {} (enable {:#x}, disable {:#x}), get {}",
+        set_command,
+        configuration.parameters.enable,
+        configuration.parameters.disable,
+        configuration.get_command,
+    )
+}
+
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+fn enabled(enabled: bool) -> String {
+    if enabled { "enabled" } else { "disabled" }.to_string()
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use crate::mock::MockBackend;
+    use crate::self_test::SelfTestStatus;
+    use crate::{Context, Profile};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_self_test() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.remove_method(r#"\_SB.PCI0.LPC0.EC0.QCHO"#);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let report = context.self_test();
+
+        assert!(!report.passed());
+        assert_eq!(
+            report.item("acpi_call").map(|item| item.status),
+            Some(SelfTestStatus::Pass)
+        );
+        assert_eq!(
+            report.item("system performance").map(|item| item.status),
+            Some(SelfTestStatus::Pass)
+        );
+        assert_eq!(
+            report
+                .item("battery conservation")
+                .map(|item| &*item.message),
+            Some("disabled")
+        );
+
+        let rapid_charge = report
+            .item("rapid charge")
+            .expect("rapid charge wasn't probed");
+        assert_eq!(rapid_charge.status, SelfTestStatus::Fail);
+        assert!(rapid_charge.message.contains("not found"));
+
+        // nothing was changed
+        assert!(backend.calls().iter().all(|call| !call.contains(' ')));
+
+        let methods = report
+            .item("system performance")
+            .and_then(|item| item.methods.as_deref())
+            .expect("system performance methods weren't reported");
+        assert!(methods.contains(r#"set \_SB.PCI0.LPC0.EC0.VPC0.DYTC"#));
+        assert_eq!(
+            rapid_charge.methods.as_deref(),
+            Some(
+                r#"set \_SB.PCI0.LPC0.EC0.VPC0.SBMC (enable 0x7, disable 0x8), get \_SB.PCI0.LPC0.EC0.QCHO"#
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "ideapad_15iil05", feature = "ideapad_amd"))]
+    fn test_try_each_profile() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_15IIL05);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        let valid = context
+            .try_each_profile()
+            .into_iter()
+            .filter(|(_, report)| report.is_valid())
+            .map(|(profile, _)| profile.name)
+            .collect::<Vec<_>>();
+        assert_eq!(valid, [Profile::IDEAPAD_AMD.name]);
+
+        // nothing was changed
+        assert!(backend.calls().iter().all(|call| !call.contains(' ')));
+    }
+}
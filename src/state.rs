@@ -0,0 +1,671 @@
+//! Snapshots of the state of the device, and the changes between them.
+
+use crate::context::Context;
+use crate::preset::Preset;
+use std::collections::HashMap;
+use std::iter;
+use std::thread;
+use std::time::Instant;
+use try_drop::prelude::*;
+
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+use std::{
+    fmt::{self, Write},
+    panic,
+    thread::ScopedJoinHandle,
+};
+
+#[cfg(feature = "system_performance")]
+use crate::system_performance::SystemPerformanceMode;
+
+/// The settings which [`Context::with_state`] overrides. Settings which are [`None`] are left
+/// alone.
+pub type DesiredState = Preset;
+
+/// The state of the device at a point in time.
+///
+/// Each field is either the value which was read, or a description of the error which occurred
+/// when reading it. Features which aren't compiled in are absent.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DeviceState {
+    /// The name of the profile which the state was read with.
+    pub profile: String,
+
+    /// The system performance mode.
+    #[cfg(feature = "system_performance")]
+    pub system_performance: Result<SystemPerformanceMode, String>,
+
+    /// Whether battery conservation is enabled.
+    #[cfg(feature = "battery_conservation")]
+    pub battery_conservation: Result<bool, String>,
+
+    /// Whether rapid charge is enabled.
+    #[cfg(feature = "rapid_charge")]
+    pub rapid_charge: Result<bool, String>,
+}
+
+impl DeviceState {
+    /// Read the state of the device. This never changes the state of the device.
+    pub fn read<D, DD>(context: &Context<D, DD>) -> Self
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        #[allow(unused_variables)]
+        let controllers = context.controllers();
+
+        Self {
+            profile: context.profile.name.to_string(),
+            #[cfg(feature = "system_performance")]
+            system_performance: controllers
+                .system_performance()
+                .get()
+                .map_err(|error| error.to_string()),
+            #[cfg(feature = "battery_conservation")]
+            battery_conservation: controllers
+                .battery_conservation()
+                .get()
+                .map_err(|error| error.to_string()),
+            #[cfg(feature = "rapid_charge")]
+            rapid_charge: controllers
+                .rapid_charge()
+                .get()
+                .map_err(|error| error.to_string()),
+        }
+    }
+
+    /// Read the state of a single subsystem again, leaving the others as they are. This never
+    /// changes the state of the device.
+    #[cfg(any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    ))]
+    pub fn refresh<D, DD>(&mut self, context: &Context<D, DD>, subsystem: Subsystem)
+    where
+        D: FallibleTryDropStrategy,
+        DD: FallbackTryDropStrategy,
+    {
+        match subsystem {
+            #[cfg(feature = "system_performance")]
+            Subsystem::SystemPerformance => {
+                self.system_performance = context
+                    .controllers()
+                    .system_performance()
+                    .get()
+                    .map_err(|error| error.to_string())
+            }
+            #[cfg(feature = "battery_conservation")]
+            Subsystem::BatteryConservation => {
+                self.battery_conservation = context
+                    .controllers()
+                    .battery_conservation()
+                    .get()
+                    .map_err(|error| error.to_string())
+            }
+            #[cfg(feature = "rapid_charge")]
+            Subsystem::RapidCharge => {
+                self.rapid_charge = context
+                    .controllers()
+                    .rapid_charge()
+                    .get()
+                    .map_err(|error| error.to_string())
+            }
+        }
+    }
+
+    /// Read the state of the device like [`Self::read`], but read each feature on its own thread.
+    ///
+    /// `acpi_call` operations issued through the context are still serialized, since the output
+    /// of `/proc/acpi/call` is shared, so this is only faster if reading a feature spends time
+    /// outside of them, such as with a slow [backend](Context::backend) or a
+    /// [sysfs battery backend](Context::sysfs_battery_backend). Otherwise it only adds the cost of
+    /// spawning threads.
+    pub fn read_parallel<D, DD>(context: &Context<D, DD>) -> Self
+    where
+        D: FallibleTryDropStrategy + Sync,
+        DD: FallbackTryDropStrategy + Sync,
+    {
+        #[allow(unused_variables)]
+        thread::scope(|scope| {
+            #[cfg(feature = "system_performance")]
+            let system_performance = scope.spawn(|| {
+                context
+                    .controllers()
+                    .system_performance()
+                    .get()
+                    .map_err(|error| error.to_string())
+            });
+            #[cfg(feature = "battery_conservation")]
+            let battery_conservation = scope.spawn(|| {
+                context
+                    .controllers()
+                    .battery_conservation()
+                    .get()
+                    .map_err(|error| error.to_string())
+            });
+            #[cfg(feature = "rapid_charge")]
+            let rapid_charge = scope.spawn(|| {
+                context
+                    .controllers()
+                    .rapid_charge()
+                    .get()
+                    .map_err(|error| error.to_string())
+            });
+
+            Self {
+                profile: context.profile.name.to_string(),
+                #[cfg(feature = "system_performance")]
+                system_performance: join(system_performance),
+                #[cfg(feature = "battery_conservation")]
+                battery_conservation: join(battery_conservation),
+                #[cfg(feature = "rapid_charge")]
+                rapid_charge: join(rapid_charge),
+            }
+        })
+    }
+
+    /// Format this state as newline separated `KEY=value` pairs, so that shell scripts could
+    /// `eval` it, for example `IDEAPAD_PERFORMANCE=extreme-performance`.
+    ///
+    /// Values use the names from the [`Display`](std::fmt::Display) implementations, and values which
+    /// couldn't be read are `unknown`.
+    pub fn to_env_string(&self) -> String {
+        #[allow(unused_mut)]
+        let mut env = String::new();
+
+        #[cfg(feature = "system_performance")]
+        write_env(&mut env, "IDEAPAD_PERFORMANCE", &self.system_performance);
+
+        #[cfg(feature = "battery_conservation")]
+        write_env(
+            &mut env,
+            "IDEAPAD_BATTERY_CONSERVATION",
+            &self.battery_conservation,
+        );
+
+        #[cfg(feature = "rapid_charge")]
+        write_env(&mut env, "IDEAPAD_RAPID_CHARGE", &self.rapid_charge);
+
+        env
+    }
+
+    /// Get a one line, human readable summary of this state, for example
+    /// `profile=IDEAPAD_AMD perf=extreme-performance conservation=on rapid-charge=off`.
+    ///
+    /// Values which couldn't be read are shown as `?`.
+    pub fn summary_line(&self) -> String {
+        #[allow(unused_mut)]
+        let mut line = format!("profile={}", self.profile);
+
+        #[cfg(feature = "system_performance")]
+        write_summary(
+            &mut line,
+            "perf",
+            self.system_performance.as_ref().map(|mode| mode.name()),
+        );
+
+        #[cfg(feature = "battery_conservation")]
+        write_summary(
+            &mut line,
+            "conservation",
+            self.battery_conservation
+                .as_ref()
+                .map(|&enabled| on_off(enabled)),
+        );
+
+        #[cfg(feature = "rapid_charge")]
+        write_summary(
+            &mut line,
+            "rapid-charge",
+            self.rapid_charge.as_ref().map(|&enabled| on_off(enabled)),
+        );
+
+        line
+    }
+
+    /// Serialize this state into a JSON object, keyed by the names of the fields. Values which
+    /// couldn't be read are serialized as `{ "error": "..." }` objects.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        #[allow(unused_mut)]
+        let mut object = serde_json::Map::new();
+        object.insert("profile".into(), self.profile.clone().into());
+
+        #[cfg(feature = "system_performance")]
+        object.insert(
+            "system_performance".into(),
+            json_field(&self.system_performance)?,
+        );
+
+        #[cfg(feature = "battery_conservation")]
+        object.insert(
+            "battery_conservation".into(),
+            json_field(&self.battery_conservation)?,
+        );
+
+        #[cfg(feature = "rapid_charge")]
+        object.insert("rapid_charge".into(), json_field(&self.rapid_charge)?);
+
+        serde_json::to_string(&object)
+    }
+
+    /// Get the changes from this state to a newer state.
+    ///
+    /// A value which couldn't be read is treated as unknown, so a value becoming unreadable (or
+    /// readable again) is a change, while two different errors are not.
+    #[allow(unused_variables, unused_mut)]
+    pub fn changes(&self, newer: &Self) -> Vec<StateChange> {
+        let mut changes = Vec::new();
+
+        #[cfg(feature = "system_performance")]
+        if self.system_performance.as_ref().ok() != newer.system_performance.as_ref().ok() {
+            changes.push(StateChange::SystemPerformance {
+                from: self.system_performance.clone().ok(),
+                to: newer.system_performance.clone().ok(),
+            })
+        }
+
+        #[cfg(feature = "battery_conservation")]
+        if self.battery_conservation.as_ref().ok() != newer.battery_conservation.as_ref().ok() {
+            changes.push(StateChange::BatteryConservation {
+                from: self.battery_conservation.clone().ok(),
+                to: newer.battery_conservation.clone().ok(),
+            })
+        }
+
+        #[cfg(feature = "rapid_charge")]
+        if self.rapid_charge.as_ref().ok() != newer.rapid_charge.as_ref().ok() {
+            changes.push(StateChange::RapidCharge {
+                from: self.rapid_charge.clone().ok(),
+                to: newer.rapid_charge.clone().ok(),
+            })
+        }
+
+        changes
+    }
+
+    /// Describe the changes from this state to a newer state in a human readable way, such as
+    /// `performance: extreme-performance -> intelligent-cooling; rapid charge: on -> off`, which is
+    /// what a watcher would log. See [`Self::changes`] and [`StateChange::describe`].
+    ///
+    /// If nothing changed, the description is empty.
+    pub fn describe_changes(&self, newer: &Self) -> String {
+        self.changes(newer)
+            .iter()
+            .map(StateChange::describe)
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+fn write_env(env: &mut String, key: &str, value: &Result<impl fmt::Display, String>) {
+    let _ = match value {
+        Ok(value) => writeln!(env, "{}={}", key, value),
+        Err(_) => writeln!(env, "{}=unknown", key),
+    };
+}
+
+/// Join the thread, resuming its panic if it panicked.
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+fn join<T>(handle: ScopedJoinHandle<T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+fn write_summary(line: &mut String, key: &str, value: Result<&str, &String>) {
+    let _ = write!(line, " {}={}", key, value.unwrap_or("?"));
+}
+
+#[cfg(all(
+    feature = "json",
+    any(
+        feature = "battery_conservation",
+        feature = "rapid_charge",
+        feature = "system_performance"
+    )
+))]
+fn json_field<T: serde::Serialize>(
+    field: &Result<T, String>,
+) -> serde_json::Result<serde_json::Value> {
+    match field {
+        Ok(value) => serde_json::to_value(value),
+        Err(error) => Ok(serde_json::json!({ "error": error })),
+    }
+}
+
+#[cfg(any(
+    feature = "battery_conservation",
+    feature = "rapid_charge",
+    feature = "system_performance"
+))]
+fn describe_change(label: &str, from: Option<&str>, to: Option<&str>) -> String {
+    format!(
+        "{}: {} -> {}",
+        label,
+        from.unwrap_or("?"),
+        to.unwrap_or("?")
+    )
+}
+
+#[cfg(any(feature = "battery_conservation", feature = "rapid_charge"))]
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// A change of a single feature between two [`DeviceState`]s.
+///
+/// Values are [`None`] if they couldn't be read.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StateChange {
+    /// The system performance mode changed.
+    #[cfg(feature = "system_performance")]
+    SystemPerformance {
+        /// The previous system performance mode.
+        from: Option<SystemPerformanceMode>,
+
+        /// The new system performance mode.
+        to: Option<SystemPerformanceMode>,
+    },
+
+    /// Battery conservation was enabled or disabled.
+    #[cfg(feature = "battery_conservation")]
+    BatteryConservation {
+        /// Whether battery conservation was previously enabled.
+        from: Option<bool>,
+
+        /// Whether battery conservation is now enabled.
+        to: Option<bool>,
+    },
+
+    /// Rapid charge was enabled or disabled.
+    #[cfg(feature = "rapid_charge")]
+    RapidCharge {
+        /// Whether rapid charge was previously enabled.
+        from: Option<bool>,
+
+        /// Whether rapid charge is now enabled.
+        to: Option<bool>,
+    },
+}
+
+/// A feature of the device whose state is tracked.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Subsystem {
+    /// The system performance mode.
+    #[cfg(feature = "system_performance")]
+    SystemPerformance,
+
+    /// Battery conservation.
+    #[cfg(feature = "battery_conservation")]
+    BatteryConservation,
+
+    /// Rapid charge.
+    #[cfg(feature = "rapid_charge")]
+    RapidCharge,
+}
+
+impl StateChange {
+    /// Get the subsystem which changed.
+    pub fn subsystem(&self) -> Subsystem {
+        match *self {
+            #[cfg(feature = "system_performance")]
+            Self::SystemPerformance { .. } => Subsystem::SystemPerformance,
+            #[cfg(feature = "battery_conservation")]
+            Self::BatteryConservation { .. } => Subsystem::BatteryConservation,
+            #[cfg(feature = "rapid_charge")]
+            Self::RapidCharge { .. } => Subsystem::RapidCharge,
+        }
+    }
+
+    /// Describe this change in a human readable way, such as `rapid charge: on -> off`. Values
+    /// which couldn't be read are shown as `?`.
+    pub fn describe(&self) -> String {
+        match *self {
+            #[cfg(feature = "system_performance")]
+            Self::SystemPerformance { from, to } => describe_change(
+                "performance",
+                from.map(SystemPerformanceMode::name),
+                to.map(SystemPerformanceMode::name),
+            ),
+            #[cfg(feature = "battery_conservation")]
+            Self::BatteryConservation { from, to } => {
+                describe_change("conservation", from.map(on_off), to.map(on_off))
+            }
+            #[cfg(feature = "rapid_charge")]
+            Self::RapidCharge { from, to } => {
+                describe_change("rapid charge", from.map(on_off), to.map(on_off))
+            }
+        }
+    }
+}
+
+/// Keeps the latest [`DeviceState`] along with when each subsystem last changed, so that it is
+/// known how long the device has been in its current state.
+#[derive(Debug, Clone)]
+pub struct StateHistory {
+    last: DeviceState,
+    last_changed: HashMap<Subsystem, Instant>,
+}
+
+impl StateHistory {
+    /// Start tracking from the initial state. No subsystem has changed yet, as it isn't known
+    /// since when the device has been in the initial state.
+    pub fn new(initial: DeviceState) -> Self {
+        Self {
+            last: initial,
+            last_changed: HashMap::new(),
+        }
+    }
+
+    /// Get the latest state.
+    pub fn last(&self) -> &DeviceState {
+        &self.last
+    }
+
+    /// Record a newer state which was read at the specified instant, returning the changes since
+    /// the previous one. See [`DeviceState::changes`].
+    pub fn record(&mut self, state: DeviceState, at: Instant) -> Vec<StateChange> {
+        let changes = self.last.changes(&state);
+        self.last_changed.extend(
+            changes
+                .iter()
+                .map(StateChange::subsystem)
+                .zip(iter::repeat(at)),
+        );
+
+        self.last = state;
+        changes
+    }
+
+    /// Get when the subsystem last changed, or [`None`] if it hasn't changed since tracking
+    /// started.
+    pub fn last_changed(&self, subsystem: Subsystem) -> Option<Instant> {
+        self.last_changed.get(&subsystem).copied()
+    }
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use crate::state::{DeviceState, StateChange, StateHistory, Subsystem};
+    use crate::system_performance::SystemPerformanceMode;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_to_json() {
+        let state = DeviceState {
+            profile: "IDEAPAD_AMD".into(),
+            system_performance: Ok(SystemPerformanceMode::IntelligentCooling),
+            battery_conservation: Ok(true),
+            rapid_charge: Err("method not found".into()),
+        };
+        let json = state.to_json().expect("failed to serialize state");
+        let value: serde_json::Value = serde_json::from_str(&json).expect("invalid json");
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "profile": "IDEAPAD_AMD",
+                "system_performance": "IntelligentCooling",
+                "battery_conservation": true,
+                "rapid_charge": { "error": "method not found" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = DeviceState {
+            profile: "IDEAPAD_AMD".into(),
+            system_performance: Ok(SystemPerformanceMode::IntelligentCooling),
+            battery_conservation: Ok(true),
+            rapid_charge: Err("method not found".into()),
+        };
+        let new = DeviceState {
+            profile: "IDEAPAD_AMD".into(),
+            system_performance: Ok(SystemPerformanceMode::ExtremePerformance),
+            battery_conservation: Ok(true),
+            rapid_charge: Err("another error".into()),
+        };
+
+        assert_eq!(
+            old.changes(&new),
+            [StateChange::SystemPerformance {
+                from: Some(SystemPerformanceMode::IntelligentCooling),
+                to: Some(SystemPerformanceMode::ExtremePerformance),
+            }]
+        );
+        assert!(new.changes(&new).is_empty());
+    }
+
+    #[test]
+    fn test_describe_changes() {
+        let old = DeviceState {
+            profile: "IDEAPAD_AMD".into(),
+            system_performance: Ok(SystemPerformanceMode::ExtremePerformance),
+            battery_conservation: Ok(false),
+            rapid_charge: Ok(true),
+        };
+        let new = DeviceState {
+            system_performance: Ok(SystemPerformanceMode::IntelligentCooling),
+            rapid_charge: Ok(false),
+            ..old.clone()
+        };
+
+        assert_eq!(
+            old.describe_changes(&new),
+            "performance: extreme-performance -> intelligent-cooling; rapid charge: on -> off"
+        );
+        assert_eq!(
+            new.describe_changes(&DeviceState {
+                battery_conservation: Err("method not found".into()),
+                ..new.clone()
+            }),
+            "conservation: off -> ?"
+        );
+        assert_eq!(new.describe_changes(&new), "");
+    }
+
+    #[test]
+    fn test_to_env_string() {
+        let state = DeviceState {
+            profile: "IDEAPAD_AMD".into(),
+            system_performance: Ok(SystemPerformanceMode::ExtremePerformance),
+            battery_conservation: Ok(true),
+            rapid_charge: Ok(false),
+        };
+
+        assert_eq!(
+            state.to_env_string(),
+            "IDEAPAD_PERFORMANCE=extreme-performance\n\
+             IDEAPAD_BATTERY_CONSERVATION=true\n\
+             IDEAPAD_RAPID_CHARGE=false\n"
+        );
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let state = DeviceState {
+            profile: "IDEAPAD_AMD".into(),
+            system_performance: Ok(SystemPerformanceMode::ExtremePerformance),
+            battery_conservation: Ok(true),
+            rapid_charge: Ok(false),
+        };
+        assert_eq!(
+            state.summary_line(),
+            "profile=IDEAPAD_AMD perf=extreme-performance conservation=on rapid-charge=off"
+        );
+
+        let state = DeviceState {
+            rapid_charge: Err("method not found".into()),
+            ..state
+        };
+        assert_eq!(
+            state.summary_line(),
+            "profile=IDEAPAD_AMD perf=extreme-performance conservation=on rapid-charge=?"
+        );
+    }
+
+    #[test]
+    fn test_state_history() {
+        let state = |system_performance| DeviceState {
+            profile: "IDEAPAD_AMD".into(),
+            system_performance: Ok(system_performance),
+            battery_conservation: Ok(false),
+            rapid_charge: Ok(false),
+        };
+        let started = Instant::now();
+        let mut history = StateHistory::new(state(SystemPerformanceMode::IntelligentCooling));
+        assert_eq!(history.last_changed(Subsystem::SystemPerformance), None);
+
+        history.record(
+            state(SystemPerformanceMode::IntelligentCooling),
+            started + Duration::from_secs(1),
+        );
+        assert_eq!(history.last_changed(Subsystem::SystemPerformance), None);
+
+        history.record(
+            state(SystemPerformanceMode::ExtremePerformance),
+            started + Duration::from_secs(2),
+        );
+        history.record(
+            state(SystemPerformanceMode::ExtremePerformance),
+            started + Duration::from_secs(3),
+        );
+        assert_eq!(
+            history.last_changed(Subsystem::SystemPerformance),
+            Some(started + Duration::from_secs(2))
+        );
+        assert_eq!(history.last_changed(Subsystem::RapidCharge), None);
+        assert_eq!(
+            history.last().system_performance,
+            Ok(SystemPerformanceMode::ExtremePerformance)
+        );
+    }
+}
@@ -2,9 +2,14 @@
 //!
 //! System performance (modes) are a variety of modes used to control the system performance.
 
-use crate::acpi_call::{self, acpi_call, acpi_call_expect_valid};
+use crate::acpi_call;
 use crate::context::Context;
-use crate::profile::{SystemPerformanceBits, SystemPerformanceParameters};
+use crate::profile::{Bit, SystemPerformance, SystemPerformanceBits, SystemPerformanceParameters};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use std::{fmt, mem};
 use thiserror::Error;
 use try_drop::prelude::*;
 use try_drop::{DropAdapter, GlobalFallbackTryDropStrategyHandler, GlobalTryDropStrategyHandler};
@@ -45,6 +50,57 @@ pub enum Error {
         #[from]
         error: acpi_call::Error,
     },
+
+    /// The system performance mode was set again before the cooldown configured in
+    /// [`Context::system_performance_cooldown`] elapsed.
+    #[error("system performance mode was set too recently, try again in {remaining:?}")]
+    TooSoon {
+        /// How long is left until the system performance mode could be set again.
+        remaining: Duration,
+    },
+
+    /// The firmware doesn't currently allow the system performance mode. This is only checked if
+    /// [`Context::reject_unsupported_system_performance_modes`] is set.
+    #[error("system performance mode {mode:?} is not currently supported by the firmware")]
+    UnsupportedMode {
+        /// The unsupported system performance mode.
+        mode: SystemPerformanceMode,
+    },
+
+    /// The system performance mode didn't settle before the [timeout](Context::settle_timeout)
+    /// elapsed.
+    #[error("system performance mode didn't settle to {mode:?} within {waited:?}")]
+    NotSettled {
+        /// The system performance mode which was waited for.
+        mode: SystemPerformanceMode,
+
+        /// How long was waited.
+        waited: Duration,
+    },
+
+    /// An error occurred when reading the CPU package temperature.
+    #[error("{error}")]
+    Thermal {
+        /// The underlying error itself.
+        error: io::Error,
+    },
+
+    /// Extreme performance was requested while on battery. This is only checked if
+    /// [`Context::extreme_performance_requires_ac`] is set.
+    #[error("extreme performance requires ac power")]
+    ExtremePerformanceRequiresAc,
+
+    /// Whether AC power is online couldn't be read.
+    #[error("failed to read whether ac power is online: {error}")]
+    PowerSupply {
+        /// The underlying IO error.
+        error: io::Error,
+    },
+
+    /// A guard was created while a guard which it isn't nested in is still active on the same
+    /// context. See [`SystemPerformanceGuard`].
+    #[error("another system performance guard is already active")]
+    GuardActive,
 }
 
 /// The different system performance modes. Documentation sources can be found
@@ -64,6 +120,23 @@ pub enum SystemPerformanceMode {
 }
 
 impl SystemPerformanceMode {
+    /// Every system performance mode.
+    pub const ALL: [Self; 3] = [
+        Self::IntelligentCooling,
+        Self::ExtremePerformance,
+        Self::BatterySaving,
+    ];
+
+    /// Get the name of this system performance mode, which is what [`Display`](fmt::Display)
+    /// shows.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::IntelligentCooling => "intelligent-cooling",
+            Self::ExtremePerformance => "extreme-performance",
+            Self::BatterySaving => "battery-saving",
+        }
+    }
+
     /// Get system performance mode from a parameter.
     pub const fn from_u32_setter(
         parameters: &SystemPerformanceParameters,
@@ -123,6 +196,15 @@ impl SystemPerformanceMode {
             Self::BatterySaving => parameters.battery_saving,
         }
     }
+
+    /// Get the command and parameter which have to be issued through `acpi_call` to set the
+    /// system performance mode to this mode.
+    pub fn action(self, system_performance: &SystemPerformance) -> (&str, u32) {
+        (
+            &system_performance.commands.set,
+            self.setter(&system_performance.parameters),
+        )
+    }
 }
 
 /// Inner value of [`SystemPerformanceGuard`].
@@ -139,6 +221,20 @@ where
 }
 
 /// Guarantees that a system performance mode will be used for a scope.
+///
+/// The old system performance mode is restored when the guard is dropped, see
+/// [nesting guards](crate::battery#guards). Use [`SystemPerformanceGuard::disarm`] to keep the
+/// new mode instead.
+///
+/// Restoring the old mode isn't subject to the [cooldown](Context::system_performance_cooldown)
+/// or the other checks of [`SystemPerformanceController::set`], as it was already in effect.
+///
+/// # Nesting
+/// A guard mutably borrows its controller for its whole lifetime, so two guards of the same
+/// controller can't overlap. To nest a guard, create it through the [controller](Self::controller)
+/// of the outer guard, which guarantees that the inner guard is dropped first and so restores the
+/// mode of the outer guard. Creating a guard through any other controller of the same context
+/// while a guard is active fails with [`Error::GuardActive`].
 #[must_use]
 pub struct SystemPerformanceGuard<
     'sp,
@@ -160,8 +256,14 @@ where
         controller: &'sp mut SystemPerformanceController<'ctx, D, DD>,
         on_init: SystemPerformanceMode,
         on_drop: SystemPerformanceMode,
-    ) -> acpi_call::Result<Self> {
-        controller.set(on_init)?;
+    ) -> Result<Self> {
+        controller.enter_guard()?;
+
+        if let Err(error) = controller.set(on_init) {
+            controller.leave_guard();
+            return Err(error);
+        }
+
         Ok(Self(DropAdapter(SystemPerformanceGuardInner {
             controller,
             on_drop,
@@ -174,7 +276,20 @@ where
         controller: &'sp mut SystemPerformanceController<'ctx, D, DD>,
         mode: SystemPerformanceMode,
     ) -> Result<Self> {
-        Ok(Self::new(controller, mode, controller.get()?)?)
+        controller.check_guard()?;
+        let on_drop = controller.peek_restore()?;
+        Self::new(controller, mode, on_drop)
+    }
+
+    /// Get the controller of this guard, for example to nest another guard in this one.
+    pub fn controller(&mut self) -> &mut SystemPerformanceController<'ctx, D, DD> {
+        self.0 .0.controller
+    }
+
+    /// Consume the guard without restoring the system performance mode, keeping the current one.
+    pub fn disarm(self) {
+        self.0 .0.controller.leave_guard();
+        mem::forget(self)
     }
 }
 
@@ -183,7 +298,7 @@ where
     D: FallibleTryDropStrategy,
     DD: FallbackTryDropStrategy,
 {
-    type Error = acpi_call::Error;
+    type Error = Error;
     type FallbackTryDropStrategy = DD;
     type TryDropStrategy = D;
 
@@ -196,12 +311,91 @@ where
     }
 
     unsafe fn try_drop(&mut self) -> Result<(), Self::Error> {
-        self.controller.set(self.on_drop)
+        let context = self.controller.context;
+        let result = self.controller.set_unchecked(self.on_drop);
+        self.controller.leave_guard();
+        context.record_drop_error("SystemPerformanceController::set", result)
     }
 }
 
+impl fmt::Display for SystemPerformanceMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The raw bits which were observed for each system performance mode. See
+/// [`SystemPerformanceController::calibrate`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CalibrationReport {
+    /// Every system performance mode, along with the spmo and fcmo bits which were read back
+    /// after setting it.
+    pub observed: Vec<(SystemPerformanceMode, Bit)>,
+
+    /// The original system performance mode, which was restored afterwards. If it couldn't be
+    /// read, this is [`None`] and the last calibrated system performance mode is left in place.
+    pub restored: Option<SystemPerformanceMode>,
+}
+
+impl CalibrationReport {
+    /// Get the bits which were observed for the system performance mode.
+    pub fn bit(&self, mode: SystemPerformanceMode) -> Option<Bit> {
+        self.observed
+            .iter()
+            .find(|(observed, _)| *observed == mode)
+            .map(|&(_, bit)| bit)
+    }
+
+    /// Get the system performance bits which were observed, ready to be put in a profile.
+    pub fn bits(&self) -> Option<SystemPerformanceBits> {
+        Some(SystemPerformanceBits::new(
+            self.bit(SystemPerformanceMode::IntelligentCooling)?,
+            self.bit(SystemPerformanceMode::ExtremePerformance)?,
+            self.bit(SystemPerformanceMode::BatterySaving)?,
+        ))
+    }
+}
+
+/// The outcome of a [verified set](SystemPerformanceController::set_verified).
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SetResult {
+    /// The system performance mode which was requested.
+    pub requested: SystemPerformanceMode,
+
+    /// The system performance mode which the embedded controller reports after the set.
+    pub actual: SystemPerformanceMode,
+
+    /// Whether the requested system performance mode was actually applied.
+    pub applied: bool,
+}
+
+/// The system performance mode as it should be shown to the user, which is never an error. See
+/// [`SystemPerformanceController::status`].
+#[derive(Debug)]
+pub enum SystemPerformanceStatus {
+    /// The spmo and fcmo bits agree on the system performance mode.
+    Known(SystemPerformanceMode),
+
+    /// The spmo and fcmo bits disagree on the system performance mode, which happens while the
+    /// embedded controller is switching between modes.
+    Mismatched {
+        /// The spmo bit.
+        spmo: u32,
+
+        /// The fcmo bit.
+        fcmo: u32,
+    },
+
+    /// The system performance mode couldn't be read.
+    Unreadable(Error),
+}
+
 /// Controller for the system performance mode.
-#[derive(Copy, Clone)]
+///
+/// Unlike the other controllers, this isn't [`Copy`], as a copy could create a guard which
+/// overlaps the guards of the original. See [`SystemPerformanceGuard`].
 pub struct SystemPerformanceController<
     'ctx,
     D = GlobalTryDropStrategyHandler,
@@ -212,6 +406,8 @@ pub struct SystemPerformanceController<
 {
     /// A reference to the context.
     pub context: &'ctx Context<D, DD>,
+
+    guards: usize,
 }
 
 impl<'ctx, D, DD> SystemPerformanceController<'ctx, D, DD>
@@ -221,51 +417,208 @@ where
 {
     /// Create a new system performance controller.
     pub fn new(context: &'ctx Context<D, DD>) -> Self {
-        Self { context }
+        Self { context, guards: 0 }
     }
 
-    /// Set the system performance mode to the specified mode.
-    pub fn set(&mut self, mode: SystemPerformanceMode) -> acpi_call::Result<()> {
-        acpi_call(
-            self.context
-                .profile
-                .system_performance
-                .commands
-                .set
-                .to_string(),
-            [mode.setter(&self.context.profile.system_performance.parameters)],
-        )?;
+    fn active_guards(&self) -> MutexGuard<'ctx, usize> {
+        self.context
+            .active_system_performance_guards
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Check that every guard which is active on the context is one this controller is nested in,
+    /// which are the guards this controller was borrowed from.
+    fn check_guard(&self) -> Result<()> {
+        if *self.active_guards() != self.guards {
+            return Err(Error::GuardActive);
+        }
 
         Ok(())
     }
 
+    fn enter_guard(&mut self) -> Result<()> {
+        let mut active_guards = self.active_guards();
+
+        if *active_guards != self.guards {
+            return Err(Error::GuardActive);
+        }
+
+        *active_guards += 1;
+        self.guards += 1;
+        Ok(())
+    }
+
+    fn leave_guard(&mut self) {
+        *self.active_guards() -= 1;
+        self.guards -= 1;
+    }
+
+    /// Set the system performance mode to the specified mode.
+    ///
+    /// # Errors
+    /// If a [cooldown](Context::system_performance_cooldown) is configured and the system
+    /// performance mode was set within it, an [`Error::TooSoon`] is returned.
+    ///
+    /// If [`Context::reject_unsupported_system_performance_modes`] is set and the mode isn't one of
+    /// the [supported modes](Self::supported_modes), an [`Error::UnsupportedMode`] is returned.
+    ///
+    /// If [`Context::extreme_performance_requires_ac`] is set and extreme performance is requested
+    /// while on battery, an [`Error::ExtremePerformanceRequiresAc`] is returned. If whether AC power
+    /// is online couldn't be read, an [`Error::PowerSupply`] is returned.
+    pub fn set(&mut self, mode: SystemPerformanceMode) -> Result<()> {
+        self.context.ensure_writable()?;
+
+        if self.context.extreme_performance_requires_ac
+            && mode == SystemPerformanceMode::ExtremePerformance
+            && !self
+                .context
+                .power_supply()
+                .ac_online()
+                .map_err(|error| Error::PowerSupply { error })?
+        {
+            return Err(Error::ExtremePerformanceRequiresAc);
+        }
+
+        if self.context.reject_unsupported_system_performance_modes
+            && !self.supported_modes()?.contains(&mode)
+        {
+            return Err(Error::UnsupportedMode { mode });
+        }
+
+        let mut last_set = self.last_set();
+
+        if let (Some(cooldown), Some(last_set)) =
+            (self.context.system_performance_cooldown, *last_set)
+        {
+            let elapsed = self
+                .context
+                .clock()
+                .now()
+                .saturating_duration_since(last_set);
+
+            if elapsed < cooldown {
+                return Err(Error::TooSoon {
+                    remaining: cooldown - elapsed,
+                });
+            }
+        }
+
+        self.write(mode, &mut last_set)
+    }
+
+    /// Set the system performance mode without the cooldown or any of the policy checks of
+    /// [`Self::set`], for restoring a system performance mode which was already in effect.
+    pub(crate) fn set_unchecked(&mut self, mode: SystemPerformanceMode) -> Result<()> {
+        self.context.ensure_writable()?;
+        let mut last_set = self.last_set();
+
+        self.write(mode, &mut last_set)
+    }
+
+    fn last_set(&self) -> MutexGuard<'ctx, Option<Instant>> {
+        self.context
+            .last_system_performance_set
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(
+        &mut self,
+        mode: SystemPerformanceMode,
+        last_set: &mut MutexGuard<'ctx, Option<Instant>>,
+    ) -> Result<()> {
+        let system_performance = self.context.system_performance_profile()?;
+        let parameter = mode.setter(&system_performance.parameters);
+        self.context
+            .acpi_call(&system_performance.commands.set, [parameter])?;
+        **last_set = Some(self.context.clock().now());
+
+        self.context
+            .verify_write(&system_performance.commands.set, [parameter], mode, || {
+                self.get()
+            })
+    }
+
+    /// Set the system performance mode to the specified mode, unless it is extreme performance and
+    /// the CPU package is hotter than `max_temp_c` degrees celsius, in which case intelligent
+    /// cooling is set instead. Returns the system performance mode which was actually set.
+    ///
+    /// The temperature is read from [`Context::thermal`], and only when extreme performance is
+    /// requested.
+    pub fn set_clamped(
+        &mut self,
+        desired: SystemPerformanceMode,
+        max_temp_c: f32,
+    ) -> Result<SystemPerformanceMode> {
+        let mode = match desired {
+            SystemPerformanceMode::ExtremePerformance => {
+                let temp_c = self
+                    .context
+                    .thermal()
+                    .cpu_package_temperature()
+                    .map_err(|error| Error::Thermal { error })?;
+
+                if temp_c > max_temp_c {
+                    SystemPerformanceMode::IntelligentCooling
+                } else {
+                    desired
+                }
+            }
+            mode => mode,
+        };
+
+        self.set(mode)?;
+
+        Ok(mode)
+    }
+
+    /// Set the system performance mode to `new`, but only if it currently is `expected`. Returns
+    /// whether the system performance mode was set.
+    ///
+    /// This is useful for conditional automation, for example bumping battery saving to intelligent
+    /// cooling when plugging in, but leaving extreme performance alone. The current mode is read
+    /// right before setting it, although the embedded controller has no actual compare-and-set, so
+    /// something else could still change it in between.
+    pub fn set_if_current(
+        &mut self,
+        expected: SystemPerformanceMode,
+        new: SystemPerformanceMode,
+    ) -> Result<bool> {
+        if self.get()? != expected {
+            return Ok(false);
+        }
+
+        self.set(new)?;
+
+        Ok(true)
+    }
+
+    /// Set the system performance mode to the specified mode, then read it back to verify that it
+    /// was actually applied.
+    ///
+    /// A mismatch isn't treated as an error, instead it is reported through
+    /// [`SetResult::applied`] along with both system performance modes.
+    pub fn set_verified(&mut self, mode: SystemPerformanceMode) -> Result<SetResult> {
+        self.set(mode)?;
+        let actual = self.get()?;
+
+        Ok(SetResult {
+            requested: mode,
+            actual,
+            applied: actual == mode,
+        })
+    }
+
     /// Get the system performance mode.
     pub fn get(&self) -> Result<SystemPerformanceMode> {
-        let spmo = acpi_call_expect_valid(
-            self.context
-                .profile
-                .system_performance
-                .commands
-                .get_spmo_bit
-                .to_string(),
-            [],
-        )?;
-        let fcmo = acpi_call_expect_valid(
-            self.context
-                .profile
-                .system_performance
-                .commands
-                .get_fcmo_bit
-                .to_string(),
-            [],
-        )?;
-
-        let spm_spmo =
-            SystemPerformanceMode::from_spmo(&self.context.profile.system_performance.bits, spmo)
-                .ok_or(Error::InvalidSystemPerformanceMode { bit: spmo })?;
-        let spm_fcmo =
-            SystemPerformanceMode::from_fcmo(&self.context.profile.system_performance.bits, fcmo)
-                .ok_or(Error::InvalidSystemPerformanceMode { bit: fcmo })?;
+        let (spmo, fcmo) = self.raw_bits()?;
+        let bits = &self.context.system_performance_profile()?.bits;
+
+        let spm_spmo = SystemPerformanceMode::from_spmo(bits, spmo)
+            .ok_or(Error::InvalidSystemPerformanceMode { bit: spmo })?;
+        let spm_fcmo = SystemPerformanceMode::from_fcmo(bits, fcmo)
+            .ok_or(Error::InvalidSystemPerformanceMode { bit: fcmo })?;
 
         if spm_spmo != spm_fcmo {
             return Err(Error::MismatchedFcmoSpmo {
@@ -281,16 +634,191 @@ where
         Ok(spm_spmo)
     }
 
+    /// Get the system performance mode for display, for example by a tray icon which should show
+    /// an ambiguous or unknown mode rather than fail.
+    pub fn status(&self) -> SystemPerformanceStatus {
+        match self.get() {
+            Ok(mode) => SystemPerformanceStatus::Known(mode),
+            Err(Error::MismatchedFcmoSpmo { spmo, fcmo, .. }) => {
+                SystemPerformanceStatus::Mismatched { spmo, fcmo }
+            }
+            Err(error) => SystemPerformanceStatus::Unreadable(error),
+        }
+    }
+
+    /// Read the raw spmo and fcmo bits, without interpreting them.
+    fn raw_bits(&self) -> Result<(u32, u32)> {
+        let commands = &self.context.system_performance_profile()?.commands;
+        let spmo = self
+            .context
+            .acpi_call_expect_valid(&commands.get_spmo_bit, [])?;
+        let fcmo = self
+            .context
+            .acpi_call_expect_valid(&commands.get_fcmo_bit, [])?;
+
+        Ok((spmo, fcmo))
+    }
+
+    /// Set each system performance mode in turn and read back the raw spmo and fcmo bits which
+    /// the embedded controller reports for it. This helps with finding out the
+    /// [bits](crate::profile::SystemPerformanceBits) of a new model, as only the set command and
+    /// parameters of the profile have to be right.
+    ///
+    /// The original system performance mode is restored afterwards, even if calibration failed.
+    /// If the original system performance mode can't be read, which is likely if the bits of the
+    /// profile are wrong, nothing is restored, which is reported through
+    /// [`CalibrationReport::restored`].
+    ///
+    /// A [cooldown](Context::system_performance_cooldown) applies between modes, but not to
+    /// restoring the original system performance mode.
+    pub fn calibrate(&mut self) -> Result<CalibrationReport> {
+        let original = self.get().ok();
+        let observed = SystemPerformanceMode::ALL
+            .into_iter()
+            .map(|mode| {
+                self.set(mode)?;
+                let (spmo, fcmo) = self.raw_bits()?;
+
+                Ok((mode, Bit::different(spmo, fcmo)))
+            })
+            .collect::<Result<Vec<_>>>();
+
+        let restored = original.map_or(Ok(()), |original| self.set_unchecked(original));
+
+        let observed = observed?;
+        restored?;
+
+        Ok(CalibrationReport {
+            observed,
+            restored: original,
+        })
+    }
+
+    /// Set each system performance mode in turn, staying in it for its dwell time, then restore
+    /// the original system performance mode. This is useful for sweeping through the modes to
+    /// benchmark thermal behaviour.
+    ///
+    /// The sequence is stopped before the next step once `stop` is set. The original system
+    /// performance mode is restored even if the sequence was stopped or a step failed, in which
+    /// case the error of the step is returned. Waiting is done through [`Context::clock`].
+    ///
+    /// A [cooldown](Context::system_performance_cooldown) applies between steps, so each dwell time
+    /// has to be at least as long as it, but not to restoring the original system performance mode.
+    pub fn run_sequence(
+        &mut self,
+        steps: &[(SystemPerformanceMode, Duration)],
+        stop: &AtomicBool,
+    ) -> Result<()> {
+        let original = self.get()?;
+        let ran = steps
+            .iter()
+            .take_while(|_| !stop.load(Ordering::SeqCst))
+            .try_for_each(|&(mode, dwell)| {
+                self.set(mode)?;
+                self.context.clock().sleep(dwell);
+                Ok(())
+            });
+        let restored = self.set_unchecked(original);
+
+        ran.and(restored)
+    }
+
+    /// Set the system performance mode, then measure how long it takes until it is read back,
+    /// restoring the original system performance mode afterwards. This helps with picking sane
+    /// timeouts for the hardware.
+    ///
+    /// The system performance mode is read every [`Context::settle_poll_interval`], and waiting
+    /// gives up after [`Context::settle_timeout`] if it is set. Waiting is done through
+    /// [`Context::clock`]. Restoring the original system performance mode isn't subject to the
+    /// [cooldown](Context::system_performance_cooldown).
+    ///
+    /// # Errors
+    /// If the timeout elapsed, an [`Error::NotSettled`] is returned.
+    pub fn measure_settle_time(&mut self, mode: SystemPerformanceMode) -> Result<Duration> {
+        let original = self.get()?;
+        let settled = self.set(mode).and_then(|()| self.wait_for_mode(mode));
+        let restored = self.set_unchecked(original);
+
+        let settled = settled?;
+        restored?;
+
+        Ok(settled)
+    }
+
+    fn wait_for_mode(&self, mode: SystemPerformanceMode) -> Result<Duration> {
+        let clock = self.context.clock();
+        let started = clock.now();
+
+        loop {
+            let current = self.get()?;
+            let waited = clock.now().saturating_duration_since(started);
+
+            if current == mode {
+                return Ok(waited);
+            }
+
+            if let Some(timeout) = self.context.settle_timeout {
+                if waited >= timeout {
+                    return Err(Error::NotSettled { mode, waited });
+                }
+            }
+
+            clock.sleep(self.context.settle_poll_interval);
+        }
+    }
+
+    /// Check whether setting the system performance mode to the specified mode would actually
+    /// change anything, without changing it.
+    pub fn would_change(&self, mode: SystemPerformanceMode) -> Result<bool> {
+        self.get().map(|current| current != mode)
+    }
+
+    /// Get the parameter which is passed to `acpi_call` to set the system performance mode to the
+    /// specified mode, using the parameters of the profile in the context.
+    pub fn setter_for(&self, mode: SystemPerformanceMode) -> Result<u32> {
+        Ok(mode.setter(&self.context.system_performance_profile()?.parameters))
+    }
+
+    /// Get the system performance modes which the firmware currently allows, for example
+    /// [`SystemPerformanceMode::ExtremePerformance`] may only be allowed while on AC power.
+    ///
+    /// If the profile doesn't declare a
+    /// [capabilities command](crate::profile::SystemPerformanceCommands::capabilities), every
+    /// mode is assumed to be allowed.
+    pub fn supported_modes(&self) -> Result<Vec<SystemPerformanceMode>> {
+        let system_performance = self.context.system_performance_profile()?;
+        let command = match &system_performance.commands.capabilities {
+            Some(command) => command,
+            None => return Ok(SystemPerformanceMode::ALL.to_vec()),
+        };
+        let mask = self.context.acpi_call_expect_valid(command, [])?;
+
+        Ok(SystemPerformanceMode::ALL
+            .into_iter()
+            .filter(|mode| {
+                1u32.checked_shl(mode.fcmo(&system_performance.bits))
+                    .is_some_and(|bit| mask & bit != 0)
+            })
+            .collect())
+    }
+
     /// Get a guard that guarantees that the system performance mode will be set to the specified
     /// system performance modes.
     pub fn guard<'sp>(
         &'sp mut self,
         on_init: SystemPerformanceMode,
         on_drop: SystemPerformanceMode,
-    ) -> acpi_call::Result<SystemPerformanceGuard<'sp, 'ctx, D, DD>> {
+    ) -> Result<SystemPerformanceGuard<'sp, 'ctx, D, DD>> {
         SystemPerformanceGuard::new(self, on_init, on_drop)
     }
 
+    /// Get the system performance mode which a [scope guard](Self::guard_for_this_scope) created
+    /// now would restore when dropped, which is the current one. This lets a UI show what will be
+    /// restored before the guard is created.
+    pub fn peek_restore(&self) -> Result<SystemPerformanceMode> {
+        self.get()
+    }
+
     /// Get a guard that guarantees that the system performance mode will be set to the specified
     /// system performance mode, setting back the old one when dropped.
     pub fn guard_for_this_scope<'sp>(
@@ -311,10 +839,719 @@ where
 }
 
 /// Set the system performance mode to the specified mode.
-pub fn set<D, DD>(context: &Context<D, DD>, mode: SystemPerformanceMode) -> acpi_call::Result<()>
+pub fn set<D, DD>(context: &Context<D, DD>, mode: SystemPerformanceMode) -> Result<()>
 where
     D: FallibleTryDropStrategy,
     DD: FallbackTryDropStrategy,
 {
     context.controllers().system_performance().set(mode)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::acpi_call::{self, Backend};
+    use crate::mock::{MockBackend, MockClock, MockPowerSupply};
+    use crate::profile::{Bit, SystemPerformanceBits};
+    use crate::system_performance::{
+        Error, SetResult, SystemPerformanceMode, SystemPerformanceStatus,
+    };
+    use crate::thermal::Thermal;
+    use crate::{Context, Profile};
+    use std::io;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_peek_restore() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_system_performance_mode(SystemPerformanceMode::BatterySaving);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut system_performance = context.controllers().system_performance();
+
+        assert_eq!(
+            system_performance
+                .peek_restore()
+                .expect("failed to get system performance mode"),
+            SystemPerformanceMode::BatterySaving
+        );
+
+        drop(
+            system_performance
+                .guard_for_this_scope(SystemPerformanceMode::ExtremePerformance)
+                .expect("failed to set system performance mode"),
+        );
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::BatterySaving
+        );
+    }
+
+    #[test]
+    fn test_set_if_current() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_system_performance_mode(SystemPerformanceMode::BatterySaving);
+
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut system_performance = context.controllers().system_performance();
+
+        assert!(system_performance
+            .set_if_current(
+                SystemPerformanceMode::BatterySaving,
+                SystemPerformanceMode::IntelligentCooling
+            )
+            .expect("failed to set system performance mode"));
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+
+        let calls = backend.calls().len();
+        assert!(!system_performance
+            .set_if_current(
+                SystemPerformanceMode::BatterySaving,
+                SystemPerformanceMode::ExtremePerformance
+            )
+            .expect("failed to set system performance mode"));
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+
+        // only the current mode was read
+        assert!(backend.calls()[calls..]
+            .iter()
+            .all(|call| !call.contains(' ')));
+    }
+
+    #[test]
+    fn test_status() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let system_performance = context.controllers().system_performance();
+
+        assert!(matches!(
+            system_performance.status(),
+            SystemPerformanceStatus::Known(SystemPerformanceMode::IntelligentCooling)
+        ));
+
+        backend.respond(r#"\_SB.PCI0.LPC0.EC0.SPMO"#, "0x1");
+        assert!(matches!(
+            system_performance.status(),
+            SystemPerformanceStatus::Mismatched {
+                spmo: 0x1,
+                fcmo: 0x0
+            }
+        ));
+
+        backend.remove_method(r#"\_SB.PCI0.LPC0.EC0.SPMO"#);
+        assert!(matches!(
+            system_performance.status(),
+            SystemPerformanceStatus::Unreadable(Error::AcpiCall {
+                error: acpi_call::Error::MethodNotFound { .. }
+            })
+        ));
+    }
+
+    #[test]
+    fn test_extreme_performance_requires_ac() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.extreme_performance_requires_ac = true;
+        context.power_supply = Some(Arc::new(MockPowerSupply::default()));
+
+        context
+            .controllers()
+            .system_performance()
+            .set(SystemPerformanceMode::ExtremePerformance)
+            .expect("failed to set extreme performance on ac");
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::ExtremePerformance
+        );
+
+        context.power_supply = Some(Arc::new(MockPowerSupply {
+            ac_online: false,
+            ..MockPowerSupply::default()
+        }));
+        let mut system_performance = context.controllers().system_performance();
+
+        // restoring extreme performance after unplugging isn't refused
+        drop(
+            system_performance
+                .guard_for_this_scope(SystemPerformanceMode::BatterySaving)
+                .expect("failed to set battery saving"),
+        );
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::ExtremePerformance
+        );
+
+        backend.set_system_performance_mode(SystemPerformanceMode::IntelligentCooling);
+        assert!(matches!(
+            system_performance.set(SystemPerformanceMode::ExtremePerformance),
+            Err(Error::ExtremePerformanceRequiresAc)
+        ));
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let clock = MockClock::default();
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(MockBackend::new(Profile::IDEAPAD_AMD)));
+        context.clock = Some(Arc::new(clock.clone()));
+        context.system_performance_cooldown = Some(Duration::from_secs(5));
+
+        let mut system_performance = context.controllers().system_performance();
+        system_performance
+            .set(SystemPerformanceMode::ExtremePerformance)
+            .expect("failed to set system performance mode");
+
+        clock.advance(Duration::from_secs(2));
+        let error = system_performance
+            .set(SystemPerformanceMode::BatterySaving)
+            .expect_err("system performance mode was set within the cooldown");
+        assert!(matches!(
+            error,
+            Error::TooSoon { remaining } if remaining == Duration::from_secs(3)
+        ));
+
+        clock.advance(Duration::from_secs(3));
+        system_performance
+            .set(SystemPerformanceMode::BatterySaving)
+            .expect("failed to set system performance mode after the cooldown");
+        assert_eq!(
+            system_performance
+                .get()
+                .expect("failed to get system performance mode"),
+            SystemPerformanceMode::BatterySaving
+        );
+    }
+
+    #[test]
+    fn test_cooldown_guard_restore() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.clock = Some(Arc::new(MockClock::default()));
+        context.system_performance_cooldown = Some(Duration::from_secs(5));
+
+        let mut system_performance = context.controllers().system_performance();
+        drop(
+            system_performance
+                .guard_for_this_scope(SystemPerformanceMode::ExtremePerformance)
+                .expect("failed to set system performance mode"),
+        );
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+
+        // the restore counts as a set for the cooldown
+        assert!(matches!(
+            system_performance.set(SystemPerformanceMode::BatterySaving),
+            Err(Error::TooSoon { .. })
+        ));
+    }
+
+    #[test]
+    fn test_nested_guards() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut system_performance = context.controllers().system_performance();
+
+        {
+            let mut outer = system_performance
+                .guard_for_this_scope(SystemPerformanceMode::ExtremePerformance)
+                .expect("failed to set system performance mode");
+
+            {
+                let _inner = outer
+                    .controller()
+                    .guard_for_this_scope(SystemPerformanceMode::BatterySaving)
+                    .expect("failed to set system performance mode");
+                assert_eq!(
+                    backend.system_performance_mode(),
+                    SystemPerformanceMode::BatterySaving
+                );
+            }
+
+            assert_eq!(
+                backend.system_performance_mode(),
+                SystemPerformanceMode::ExtremePerformance
+            );
+        }
+
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+    }
+
+    #[test]
+    fn test_overlapping_guards() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut first = context.controllers().system_performance();
+        let mut second = context.controllers().system_performance();
+
+        let guard = first
+            .guard_for_this_scope(SystemPerformanceMode::ExtremePerformance)
+            .expect("failed to set system performance mode");
+        assert!(matches!(
+            second.guard_for_this_scope(SystemPerformanceMode::BatterySaving),
+            Err(Error::GuardActive)
+        ));
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::ExtremePerformance
+        );
+
+        // disarming the guard releases it as well
+        guard.disarm();
+        second
+            .guard_for_this_scope(SystemPerformanceMode::BatterySaving)
+            .expect("failed to set system performance mode");
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::ExtremePerformance
+        );
+    }
+
+    #[test]
+    fn test_guard_disarm() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut system_performance = context.controllers().system_performance();
+
+        system_performance
+            .guard(
+                SystemPerformanceMode::ExtremePerformance,
+                SystemPerformanceMode::BatterySaving,
+            )
+            .expect("failed to set system performance mode")
+            .disarm();
+
+        assert_eq!(backend.calls().len(), 1);
+        assert_eq!(
+            system_performance
+                .get()
+                .expect("failed to get system performance mode"),
+            SystemPerformanceMode::ExtremePerformance
+        );
+    }
+
+    #[test]
+    fn test_action() {
+        let system_performance = &Profile::IDEAPAD_15IIL05.system_performance.unwrap();
+
+        assert_eq!(
+            SystemPerformanceMode::IntelligentCooling.action(system_performance),
+            (r#"\_SB.PCI0.LPCB.EC0.VPC0.DYTC"#, 0x000FB001)
+        );
+        assert_eq!(
+            SystemPerformanceMode::ExtremePerformance.action(system_performance),
+            (r#"\_SB.PCI0.LPCB.EC0.VPC0.DYTC"#, 0x0012B001)
+        );
+        assert_eq!(
+            SystemPerformanceMode::BatterySaving.action(system_performance),
+            (r#"\_SB.PCI0.LPCB.EC0.VPC0.DYTC"#, 0x0013B001)
+        );
+    }
+
+    #[test]
+    fn test_setter_for() {
+        let context = Context::new(Profile::IDEAPAD_AMD);
+        let system_performance = context.controllers().system_performance();
+        let parameters = &Profile::IDEAPAD_AMD.system_performance.unwrap().parameters;
+
+        assert_eq!(
+            system_performance
+                .setter_for(SystemPerformanceMode::IntelligentCooling)
+                .ok(),
+            Some(parameters.intelligent_cooling)
+        );
+        assert_eq!(
+            system_performance
+                .setter_for(SystemPerformanceMode::ExtremePerformance)
+                .ok(),
+            Some(parameters.extreme_performance)
+        );
+        assert_eq!(
+            system_performance
+                .setter_for(SystemPerformanceMode::BatterySaving)
+                .ok(),
+            Some(parameters.battery_saving)
+        );
+    }
+
+    #[test]
+    fn test_supported_modes() {
+        let mut profile = Profile::IDEAPAD_AMD;
+        let system_performance = profile.system_performance.as_mut().unwrap();
+        system_performance.commands = system_performance
+            .commands
+            .clone()
+            .with_capabilities(r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTQ"#);
+        let backend = MockBackend::new(profile.clone());
+        // extreme performance (fcmo bit 1) isn't allowed
+        backend.respond(r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTQ"#, "0x5");
+
+        let mut context = Context::new(profile);
+        context.backend = Some(Arc::new(backend.clone()));
+        let system_performance = context.controllers().system_performance();
+
+        assert_eq!(
+            system_performance
+                .supported_modes()
+                .expect("failed to get supported modes"),
+            [
+                SystemPerformanceMode::IntelligentCooling,
+                SystemPerformanceMode::BatterySaving,
+            ]
+        );
+
+        context.reject_unsupported_system_performance_modes = true;
+        let mut system_performance = context.controllers().system_performance();
+        assert!(matches!(
+            system_performance.set(SystemPerformanceMode::ExtremePerformance),
+            Err(Error::UnsupportedMode {
+                mode: SystemPerformanceMode::ExtremePerformance
+            })
+        ));
+        system_performance
+            .set(SystemPerformanceMode::BatterySaving)
+            .expect("failed to set a supported mode");
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::BatterySaving
+        );
+    }
+
+    #[test]
+    fn test_missing_section() {
+        let mut profile = Profile::IDEAPAD_AMD;
+        profile.system_performance = None;
+        let backend = MockBackend::new(profile.clone());
+        let mut context = Context::new(profile);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        assert!(matches!(
+            context.controllers().system_performance().get(),
+            Err(Error::AcpiCall {
+                error: acpi_call::Error::FeatureUnavailableInProfile {
+                    section: "system_performance"
+                }
+            })
+        ));
+        assert!(backend.calls().is_empty());
+
+        // the battery section is still there
+        assert!(!context
+            .controllers()
+            .battery_conservation()
+            .get()
+            .expect("failed to get battery conservation status"));
+    }
+
+    #[test]
+    fn test_set_verified() {
+        let profile = Profile::IDEAPAD_AMD;
+        let backend = MockBackend::new(profile.clone());
+        let mut context = Context::new(profile.clone());
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut system_performance = context.controllers().system_performance();
+
+        assert_eq!(
+            system_performance
+                .set_verified(SystemPerformanceMode::ExtremePerformance)
+                .expect("failed to set system performance mode"),
+            SetResult {
+                requested: SystemPerformanceMode::ExtremePerformance,
+                actual: SystemPerformanceMode::ExtremePerformance,
+                applied: true,
+            }
+        );
+
+        // the embedded controller ignores the set and stays in intelligent cooling
+        let section = profile.system_performance.as_ref().unwrap();
+        let bits = &section.bits;
+        let commands = &section.commands;
+        let mode = SystemPerformanceMode::IntelligentCooling;
+        backend.respond(&commands.get_spmo_bit, &format!("{:#x}", mode.spmo(bits)));
+        backend.respond(&commands.get_fcmo_bit, &format!("{:#x}", mode.fcmo(bits)));
+
+        assert_eq!(
+            system_performance
+                .set_verified(SystemPerformanceMode::BatterySaving)
+                .expect("failed to set system performance mode"),
+            SetResult {
+                requested: SystemPerformanceMode::BatterySaving,
+                actual: SystemPerformanceMode::IntelligentCooling,
+                applied: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_would_change() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_system_performance_mode(SystemPerformanceMode::BatterySaving);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let system_performance = context.controllers().system_performance();
+
+        assert!(!system_performance
+            .would_change(SystemPerformanceMode::BatterySaving)
+            .expect("failed to check system performance mode"));
+        assert!(system_performance
+            .would_change(SystemPerformanceMode::ExtremePerformance)
+            .expect("failed to check system performance mode"));
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::BatterySaving
+        );
+    }
+
+    #[test]
+    fn test_run_sequence() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        backend.set_system_performance_mode(SystemPerformanceMode::BatterySaving);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let mut system_performance = context.controllers().system_performance();
+        let sets = |backend: &MockBackend| {
+            backend
+                .calls()
+                .into_iter()
+                .filter_map(|call| {
+                    call.strip_prefix(r#"\_SB.PCI0.LPC0.EC0.VPC0.DYTC "#)?
+                        .parse()
+                        .ok()
+                })
+                .collect::<Vec<u32>>()
+        };
+
+        system_performance
+            .run_sequence(
+                &[
+                    (SystemPerformanceMode::ExtremePerformance, Duration::ZERO),
+                    (SystemPerformanceMode::IntelligentCooling, Duration::ZERO),
+                ],
+                &AtomicBool::new(false),
+            )
+            .expect("failed to run sequence");
+        assert_eq!(sets(&backend), [0x0012B001, 0x000FB001, 0x0013B001]);
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::BatterySaving
+        );
+
+        // a stopped sequence only restores the original mode
+        system_performance
+            .run_sequence(
+                &[(SystemPerformanceMode::ExtremePerformance, Duration::ZERO)],
+                &AtomicBool::new(true),
+            )
+            .expect("failed to run sequence");
+        assert_eq!(sets(&backend)[3..], [0x0013B001]);
+    }
+
+    #[test]
+    fn test_run_sequence_cooldown() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        context.clock = Some(Arc::new(MockClock::default()));
+        context.system_performance_cooldown = Some(Duration::from_secs(5));
+
+        context
+            .controllers()
+            .system_performance()
+            .run_sequence(
+                &[
+                    (
+                        SystemPerformanceMode::ExtremePerformance,
+                        Duration::from_secs(5),
+                    ),
+                    (SystemPerformanceMode::BatterySaving, Duration::from_secs(1)),
+                ],
+                &AtomicBool::new(false),
+            )
+            .expect("failed to run sequence");
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::IntelligentCooling
+        );
+    }
+
+    /// Applies the system performance mode only after it was read a few times, like a slow
+    /// embedded controller.
+    struct Lagging {
+        inner: MockBackend,
+        pending: Mutex<Option<(String, usize)>>,
+    }
+
+    impl Backend for Lagging {
+        fn call(&self, command: &str) -> acpi_call::Result<String> {
+            let set = &Profile::IDEAPAD_AMD
+                .system_performance
+                .unwrap()
+                .commands
+                .set;
+            let mut pending = self.pending.lock().unwrap();
+
+            if command.starts_with(set.as_ref()) {
+                *pending = Some((command.to_string(), 4));
+                return Ok("0x0".to_string());
+            }
+
+            let output = self.inner.call(command);
+
+            if let Some((set, reads)) = pending.as_mut() {
+                *reads -= 1;
+
+                if *reads == 0 {
+                    self.inner.call(set)?;
+                    *pending = None;
+                }
+            }
+
+            output
+        }
+    }
+
+    #[test]
+    fn test_measure_settle_time() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let clock = MockClock::default();
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(Lagging {
+            inner: backend.clone(),
+            pending: Mutex::new(None),
+        }));
+        context.clock = Some(Arc::new(clock.clone()));
+        context.settle_poll_interval = Duration::from_millis(100);
+
+        // each read issues two calls, so the third read is the first one with the new mode
+        let settle_time = context
+            .controllers()
+            .system_performance()
+            .measure_settle_time(SystemPerformanceMode::ExtremePerformance)
+            .expect("failed to measure settle time");
+        assert_eq!(settle_time, Duration::from_millis(200));
+
+        context.settle_timeout = Some(Duration::from_millis(100));
+        assert!(matches!(
+            context
+                .controllers()
+                .system_performance()
+                .measure_settle_time(SystemPerformanceMode::BatterySaving),
+            Err(Error::NotSettled {
+                mode: SystemPerformanceMode::BatterySaving,
+                ..
+            })
+        ));
+    }
+
+    /// Always reports the same CPU package temperature.
+    struct FixedTemperature(f32);
+
+    impl Thermal for FixedTemperature {
+        fn cpu_package_temperature(&self) -> io::Result<f32> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_set_clamped() {
+        for (temp_c, desired, expected) in [
+            (
+                60.0,
+                SystemPerformanceMode::ExtremePerformance,
+                SystemPerformanceMode::ExtremePerformance,
+            ),
+            (
+                95.0,
+                SystemPerformanceMode::ExtremePerformance,
+                SystemPerformanceMode::IntelligentCooling,
+            ),
+            (
+                95.0,
+                SystemPerformanceMode::BatterySaving,
+                SystemPerformanceMode::BatterySaving,
+            ),
+        ] {
+            let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+            let mut context = Context::new(Profile::IDEAPAD_AMD);
+            context.backend = Some(Arc::new(backend.clone()));
+            context.thermal = Some(Arc::new(FixedTemperature(temp_c)));
+
+            let applied = context
+                .controllers()
+                .system_performance()
+                .set_clamped(desired, 85.0)
+                .expect("failed to set system performance mode");
+            assert_eq!(applied, expected);
+            assert_eq!(backend.system_performance_mode(), expected);
+        }
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let bits = SystemPerformanceBits::new(Bit::same(0), Bit::different(1, 3), Bit::same(2));
+        let mut profile = Profile::IDEAPAD_AMD;
+        profile.system_performance.as_mut().unwrap().bits = bits;
+        let backend = MockBackend::new(profile.clone());
+        backend.set_system_performance_mode(SystemPerformanceMode::ExtremePerformance);
+
+        // the profile of the context doesn't know the bits yet
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+        let report = context
+            .controllers()
+            .system_performance()
+            .calibrate()
+            .expect("failed to calibrate");
+
+        assert_eq!(
+            report.bit(SystemPerformanceMode::ExtremePerformance),
+            Some(Bit::different(1, 3))
+        );
+        assert_eq!(report.bits(), Some(bits));
+        // the original mode can't be read with the wrong bits, so nothing is restored
+        assert_eq!(report.restored, None);
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::BatterySaving
+        );
+
+        context.profile = profile;
+        backend.set_system_performance_mode(SystemPerformanceMode::ExtremePerformance);
+        let report = context
+            .controllers()
+            .system_performance()
+            .calibrate()
+            .expect("failed to calibrate");
+        assert_eq!(
+            report.restored,
+            Some(SystemPerformanceMode::ExtremePerformance)
+        );
+        assert_eq!(
+            backend.system_performance_mode(),
+            SystemPerformanceMode::ExtremePerformance
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Read the temperature of the CPU package.
+//!
+//! Like [`crate::power_supply`], these readings don't go through `acpi_call`, but through the
+//! thermal and hwmon classes the kernel exposes in `/sys/class/thermal` and `/sys/class/hwmon`.
+
+use crate::power_supply::read_attribute;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The default path where the kernel exposes the thermal zones.
+pub const THERMAL_SYSFS_PATH: &str = "/sys/class/thermal";
+
+/// The default path where the kernel exposes the hardware monitors.
+pub const HWMON_SYSFS_PATH: &str = "/sys/class/hwmon";
+
+/// The type of the thermal zone which reports the CPU package temperature on Intel models.
+const PACKAGE_THERMAL_ZONE: &str = "x86_pkg_temp";
+
+/// The names of the hardware monitors which report the CPU package temperature as their first
+/// temperature, for Intel and AMD models respectively.
+const PACKAGE_HWMONS: &[&str] = &["coretemp", "k10temp"];
+
+/// A source of CPU temperature readings.
+pub trait Thermal: Send + Sync {
+    /// Get the temperature of the CPU package in degrees celsius.
+    fn cpu_package_temperature(&self) -> io::Result<f32>;
+}
+
+/// Reads the CPU package temperature from sysfs, preferring the `x86_pkg_temp` thermal zone and
+/// falling back to the `coretemp` or `k10temp` hardware monitor.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Sysfs {
+    /// The directory which contains the thermal zones, usually [`THERMAL_SYSFS_PATH`].
+    pub thermal_root: PathBuf,
+
+    /// The directory which contains the hardware monitors, usually [`HWMON_SYSFS_PATH`].
+    pub hwmon_root: PathBuf,
+}
+
+impl Sysfs {
+    /// Read the temperature from the specified thermal zone and hardware monitor directories.
+    pub fn new(thermal_root: impl Into<PathBuf>, hwmon_root: impl Into<PathBuf>) -> Self {
+        Self {
+            thermal_root: thermal_root.into(),
+            hwmon_root: hwmon_root.into(),
+        }
+    }
+
+    /// Get the path of the temperature file of the CPU package, if any.
+    fn package_temperature_path(&self) -> Option<PathBuf> {
+        let thermal_zone = find_entry(&self.thermal_root, "type", |r#type| {
+            r#type == PACKAGE_THERMAL_ZONE
+        });
+
+        if let Some(thermal_zone) = thermal_zone {
+            return Some(thermal_zone.join("temp"));
+        }
+
+        find_entry(&self.hwmon_root, "name", |name| {
+            PACKAGE_HWMONS.contains(&name)
+        })
+        .map(|hwmon| hwmon.join("temp1_input"))
+    }
+}
+
+impl Default for Sysfs {
+    fn default() -> Self {
+        Self::new(THERMAL_SYSFS_PATH, HWMON_SYSFS_PATH)
+    }
+}
+
+impl Thermal for Sysfs {
+    fn cpu_package_temperature(&self) -> io::Result<f32> {
+        let path = self.package_temperature_path().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no cpu package temperature found in '{}' or '{}'",
+                    self.thermal_root.display(),
+                    self.hwmon_root.display()
+                ),
+            )
+        })?;
+
+        // the kernel reports the temperature in millidegrees celsius
+        let millidegrees: i32 = read_attribute(&path)?;
+
+        Ok(millidegrees as f32 / 1000.0)
+    }
+}
+
+/// Find the first entry of the directory, sorted by name, whose attribute matches.
+fn find_entry(root: &Path, attribute: &str, matches: impl Fn(&str) -> bool) -> Option<PathBuf> {
+    let mut entries = fs::read_dir(root)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    entries.into_iter().find(|entry| {
+        fs::read_to_string(entry.join(attribute)).is_ok_and(|value| matches(value.trim()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thermal::{Sysfs, Thermal};
+    use std::fs;
+    use std::path::Path;
+
+    fn entry(root: &Path, name: &str, attribute: &str, value: &str, temperature: (&str, i32)) {
+        let entry = root.join(name);
+        fs::create_dir(&entry).expect("failed to create entry");
+        fs::write(entry.join(attribute), format!("{}\n", value))
+            .expect("failed to write attribute");
+        fs::write(entry.join(temperature.0), format!("{}\n", temperature.1))
+            .expect("failed to write temperature");
+    }
+
+    #[test]
+    fn test_cpu_package_temperature() {
+        let thermal = tempfile::tempdir().expect("failed to create fixture directory");
+        let hwmon = tempfile::tempdir().expect("failed to create fixture directory");
+        entry(
+            hwmon.path(),
+            "hwmon0",
+            "name",
+            "acpitz",
+            ("temp1_input", 40000),
+        );
+        entry(
+            hwmon.path(),
+            "hwmon1",
+            "name",
+            "k10temp",
+            ("temp1_input", 61500),
+        );
+
+        let sysfs = Sysfs::new(thermal.path(), hwmon.path());
+        assert_eq!(
+            sysfs
+                .cpu_package_temperature()
+                .expect("failed to read temperature"),
+            61.5
+        );
+
+        // the thermal zone is preferred over the hardware monitor
+        entry(
+            thermal.path(),
+            "thermal_zone0",
+            "type",
+            "x86_pkg_temp",
+            ("temp", 72000),
+        );
+        assert_eq!(
+            sysfs
+                .cpu_package_temperature()
+                .expect("failed to read temperature"),
+            72.0
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Watch the state of the device with udev instead of polling it.
+//!
+//! Whenever the kernel reports an event from the `power_supply` subsystem, for example when the AC
+//! adapter is plugged in or the battery starts charging, the state of the device is read again and
+//! compared with the previous state. Between events, the embedded controller isn't touched at all.
+
+use crate::context::Context;
+use crate::state::{DeviceState, StateChange, StateHistory, Subsystem};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::Instant;
+use try_drop::prelude::*;
+use try_drop::{GlobalFallbackTryDropStrategyHandler, GlobalTryDropStrategyHandler};
+
+/// The udev subsystem which is listened to.
+pub const SUBSYSTEM: &str = "power_supply";
+
+/// Watches the state of the device, driven by udev `power_supply` events.
+pub struct UdevWatcher<
+    'ctx,
+    D = GlobalTryDropStrategyHandler,
+    DD = GlobalFallbackTryDropStrategyHandler,
+> where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// A reference to the context.
+    pub context: &'ctx Context<D, DD>,
+
+    history: StateHistory,
+}
+
+impl<'ctx, D, DD> UdevWatcher<'ctx, D, DD>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// Create a new watcher, taking the initial snapshot of the device state.
+    pub fn new(context: &'ctx Context<D, DD>) -> Self {
+        Self {
+            context,
+            history: StateHistory::new(context.snapshot()),
+        }
+    }
+
+    /// Get the last snapshot of the device state.
+    pub fn last(&self) -> &DeviceState {
+        self.history.last()
+    }
+
+    /// Get when the subsystem last changed according to the [clock](Context::clock) of the
+    /// context, or [`None`] if it hasn't changed since the watcher was created.
+    pub fn last_changed(&self, subsystem: Subsystem) -> Option<Instant> {
+        self.history.last_changed(subsystem)
+    }
+
+    /// Handle a `power_supply` event by taking a new snapshot of the device state, returning the
+    /// changes since the previous one.
+    pub fn handle_event(&mut self) -> Vec<StateChange> {
+        let current = self.context.snapshot();
+        self.history.record(current, self.context.clock().now())
+    }
+
+    /// Listen for `power_supply` events forever, calling `on_change` for every change of the
+    /// device state.
+    ///
+    /// # Errors
+    /// If the udev monitor couldn't be created or waited on, the underlying [`io::Error`] is
+    /// returned.
+    pub fn run(&mut self, mut on_change: impl FnMut(StateChange)) -> io::Result<()> {
+        let socket = udev::MonitorBuilder::new()?
+            .match_subsystem(SUBSYSTEM)?
+            .listen()?;
+
+        loop {
+            wait_until_readable(&socket)?;
+
+            // a single transition usually comes with a burst of events, which are all handled with
+            // one snapshot
+            if socket.iter().count() > 0 {
+                self.handle_event().into_iter().for_each(&mut on_change);
+            }
+        }
+    }
+}
+
+fn wait_until_readable(socket: &impl AsRawFd) -> io::Result<()> {
+    let mut poll_fd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    loop {
+        // SAFETY: `poll_fd` is a single valid `pollfd` which outlives the call
+        if unsafe { libc::poll(&mut poll_fd, 1, -1) } >= 0 {
+            return Ok(());
+        }
+
+        let error = io::Error::last_os_error();
+
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use crate::mock::MockBackend;
+    use crate::state::StateChange;
+    use crate::udev_watcher::UdevWatcher;
+    use crate::{Context, Profile};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_handle_event() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        let mut watcher = UdevWatcher::new(&context);
+        assert!(watcher.handle_event().is_empty());
+
+        backend.set_rapid_charge(true);
+        assert_eq!(
+            watcher.handle_event(),
+            [StateChange::RapidCharge {
+                from: Some(false),
+                to: Some(true),
+            }]
+        );
+        assert_eq!(watcher.last().rapid_charge, Ok(true));
+    }
+}
@@ -0,0 +1,206 @@
+//! Watch the state of the device by polling it.
+//!
+//! Every tick, the state of the device is read again and compared with the previous state. Unlike
+//! the `udev_watcher`, this doesn't need udev, but the embedded controller is touched every tick,
+//! so watching only the [subsystem](Watcher::for_subsystem) which is cared about is cheaper.
+
+use crate::context::Context;
+use crate::state::{DeviceState, StateChange, StateHistory, Subsystem};
+use std::time::{Duration, Instant};
+use try_drop::prelude::*;
+use try_drop::{GlobalFallbackTryDropStrategyHandler, GlobalTryDropStrategyHandler};
+
+/// Watches the state of the device, reading it again every interval.
+pub struct Watcher<
+    'ctx,
+    D = GlobalTryDropStrategyHandler,
+    DD = GlobalFallbackTryDropStrategyHandler,
+> where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// A reference to the context.
+    pub context: &'ctx Context<D, DD>,
+
+    /// How long to wait between ticks.
+    pub interval: Duration,
+
+    /// How many consecutive ticks a changed state has to be read for before its changes are
+    /// reported. This suppresses transient states which the embedded controller reports while
+    /// switching between modes. Both `0` and `1` report changes immediately, which is the default.
+    pub debounce: u32,
+
+    subsystem: Option<Subsystem>,
+    history: StateHistory,
+    pending: Option<(DeviceState, u32)>,
+}
+
+impl<'ctx, D, DD> Watcher<'ctx, D, DD>
+where
+    D: FallibleTryDropStrategy,
+    DD: FallbackTryDropStrategy,
+{
+    /// Create a new watcher of every subsystem, taking the initial snapshot of the device state.
+    pub fn new(context: &'ctx Context<D, DD>, interval: Duration) -> Self {
+        Self {
+            context,
+            interval,
+            debounce: 1,
+            subsystem: None,
+            history: StateHistory::new(context.snapshot()),
+            pending: None,
+        }
+    }
+
+    /// Create a new watcher of a single subsystem, which only reads that subsystem every tick and
+    /// so only reports its changes.
+    ///
+    /// The initial snapshot of the device state still reads every subsystem, but the other
+    /// subsystems aren't read again, so they go stale in [`Self::last`].
+    pub fn for_subsystem(
+        context: &'ctx Context<D, DD>,
+        subsystem: Subsystem,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            subsystem: Some(subsystem),
+            ..Self::new(context, interval)
+        }
+    }
+
+    /// Only report changes which persist for the specified number of consecutive ticks. See
+    /// [`Self::debounce`].
+    pub fn with_debounce(mut self, debounce: u32) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Get the last snapshot of the device state.
+    pub fn last(&self) -> &DeviceState {
+        self.history.last()
+    }
+
+    /// Get when the subsystem last changed according to the [clock](Context::clock) of the
+    /// context, or [`None`] if it hasn't changed since the watcher was created.
+    pub fn last_changed(&self, subsystem: Subsystem) -> Option<Instant> {
+        self.history.last_changed(subsystem)
+    }
+
+    /// Read the device state once, returning the changes since the previous tick. If
+    /// [debouncing](Self::debounce), the changes are only returned once the changed state was read
+    /// for enough consecutive ticks, and changes which are reverted before then are never returned.
+    pub fn tick(&mut self) -> Vec<StateChange> {
+        let current = match self.subsystem {
+            #[cfg(any(
+                feature = "battery_conservation",
+                feature = "rapid_charge",
+                feature = "system_performance"
+            ))]
+            Some(subsystem) => {
+                let mut current = self.history.last().clone();
+                current.refresh(self.context, subsystem);
+                current
+            }
+            _ => self.context.snapshot(),
+        };
+
+        if self.history.last().changes(&current).is_empty() {
+            self.pending = None;
+            return Vec::new();
+        }
+
+        let ticks = match self.pending.take() {
+            Some((pending, ticks)) if pending.changes(&current).is_empty() => ticks + 1,
+            _ => 1,
+        };
+
+        if ticks < self.debounce {
+            self.pending = Some((current, ticks));
+            return Vec::new();
+        }
+
+        self.history.record(current, self.context.clock().now())
+    }
+
+    /// Poll the device state forever, calling `on_change` for every change of the device state.
+    /// Waiting between ticks is done through the [clock](Context::clock) of the context.
+    pub fn run(&mut self, mut on_change: impl FnMut(StateChange)) {
+        loop {
+            self.context.clock().sleep(self.interval);
+            self.tick().into_iter().for_each(&mut on_change);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "core"))]
+mod tests {
+    use crate::mock::MockBackend;
+    use crate::state::{StateChange, Subsystem};
+    use crate::watcher::Watcher;
+    use crate::{Context, Profile};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_for_subsystem() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        let mut watcher = Watcher::for_subsystem(
+            &context,
+            Subsystem::BatteryConservation,
+            Duration::from_secs(1),
+        );
+        let initial_calls = backend.calls().len();
+        assert!(watcher.tick().is_empty());
+
+        backend.set_battery_conservation(true);
+        backend.set_rapid_charge(true);
+        assert_eq!(
+            watcher.tick(),
+            [StateChange::BatteryConservation {
+                from: Some(false),
+                to: Some(true),
+            }]
+        );
+        assert_eq!(watcher.last().battery_conservation, Ok(true));
+
+        // only the battery conservation get command was issued after the initial snapshot
+        assert!(backend.calls()[initial_calls..]
+            .iter()
+            .all(|call| call == r#"\_SB.PCI0.LPC0.EC0.BTSM"#));
+    }
+
+    #[test]
+    fn test_debounce() {
+        let backend = MockBackend::new(Profile::IDEAPAD_AMD);
+        let mut context = Context::new(Profile::IDEAPAD_AMD);
+        context.backend = Some(Arc::new(backend.clone()));
+
+        let mut watcher = Watcher::for_subsystem(
+            &context,
+            Subsystem::BatteryConservation,
+            Duration::from_secs(1),
+        )
+        .with_debounce(2);
+
+        // the change is reverted within the debounce window
+        backend.set_battery_conservation(true);
+        assert!(watcher.tick().is_empty());
+        backend.set_battery_conservation(false);
+        assert!(watcher.tick().is_empty());
+        assert!(watcher.tick().is_empty());
+        assert_eq!(watcher.last_changed(Subsystem::BatteryConservation), None);
+
+        backend.set_battery_conservation(true);
+        assert!(watcher.tick().is_empty());
+        assert_eq!(
+            watcher.tick(),
+            [StateChange::BatteryConservation {
+                from: Some(false),
+                to: Some(true),
+            }]
+        );
+    }
+}
@@ -0,0 +1,6 @@
+#[test]
+#[cfg(all(feature = "system_performance", feature = "ideapad_amd"))]
+fn compile_fail() {
+    let tests = trybuild::TestCases::new();
+    tests.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,15 @@
+use ideapad::prelude::*;
+
+fn main() {
+    let context = Context::new(Profile::IDEAPAD_AMD);
+    let mut controller = context.controllers().system_performance();
+    let outer = controller
+        .guard_for_this_scope(SystemPerformanceMode::ExtremePerformance)
+        .unwrap();
+    let inner = controller
+        .guard_for_this_scope(SystemPerformanceMode::BatterySaving)
+        .unwrap();
+
+    drop(inner);
+    drop(outer);
+}